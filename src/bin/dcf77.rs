@@ -0,0 +1,334 @@
+//! Command line tool to encode, decode and explain DCF77 telegrams
+//!
+//! Usage:
+//! - `dcf77 decode <hex|bitstring>` prints every field, its parity and errors,
+//!   filling the unknown bits (`?`) of a bitstring that can be deduced and
+//!   taking the unknown third party bits and call bit as 0
+//! - `dcf77 encode <RFC3339 time>` prints the telegram of a CET or CEST time
+//! - `dcf77 explain <hex|bitstring>` prints an annotated second by second breakdown
+//! - `dcf77 schedule --from <RFC3339 time> --to <RFC3339 time> [--format csv|jsonl|binary]`
+//!   prints the telegram of every minute of a range; the binary format is the
+//!   big endian `u64` bit field of every minute
+//! - `dcf77 vcd <hex|bitstring>` prints the Value Change Dump of the broadcast
+//!   of a telegram, and `dcf77 vcd --capture <file>` the one of the edges of a capture
+
+use std::io::{ErrorKind, Write};
+use std::process::ExitCode;
+use chrono::prelude::*;
+use dcf77_chrono::*;
+use dcf77_chrono::capture::{CaptureReader, Record};
+use dcf77_chrono::dst::DstRules;
+use dcf77_chrono::frame::Frame;
+use dcf77_chrono::receiver::Edge;
+use dcf77_chrono::schedule::*;
+use dcf77_chrono::telegram::*;
+use dcf77_chrono::vcd::{edges_to_vcd, minute_to_vcd};
+
+/// Usage printed when the arguments are wrong
+const USAGE: &str = "Usage:
+    dcf77 decode <hex|bitstring>
+    dcf77 encode <RFC3339 time>
+    dcf77 explain <hex|bitstring>
+    dcf77 schedule --from <RFC3339 time> --to <RFC3339 time> [--format csv|jsonl|binary]
+    dcf77 vcd <hex|bitstring>
+    dcf77 vcd --capture <file>";
+
+/// Parses a telegram given in hexadecimal (`0x` optional) or as a string of 0 and 1
+///
+/// The bitstring starts with the second 0, may hold separators between the
+/// fields and `?` for the unknown bits.
+fn parse_telegram(input: &str) -> Result<Frame, String> {
+    match input.parse::<Frame>() {
+        Ok(frame) => Ok(frame),
+        Err(bitstring_error) => {
+            let digits = input.trim().trim_start_matches("0x").trim_start_matches("0X");
+            u64::from_str_radix(digits, 16)
+                .map(Frame::from)
+                .map_err(|_| format!("Invalid telegram: {}", bitstring_error))
+        }
+    }
+}
+
+/// Prints the annotated second by second breakdown of a telegram
+fn print_seconds(output: &mut impl Write, input: Frame) -> std::io::Result<()> {
+    writeln!(output, "{:>6}  {:>3}  {:<12} {:>6}  Description", "Second", "Bit", "Field", "Weight")?;
+    for second in 0..SECONDS_PER_MINUTE {
+        let field = field_of_second(second).unwrap();
+        let weight = weight_of_second(second).map(|weight| weight.to_string()).unwrap_or_default();
+        let bit = match input.bit(second) {
+            _ if second == SECONDS_PER_MINUTE - 1 => "-".to_string(),
+            Some(bit) => u8::from(bit).to_string(),
+            None => "?".to_string()
+        };
+        writeln!(output, "{:>6}  {:>3}  {:<12} {:>6}  {}", second, bit, field.name(), weight, field.description())?;
+    }
+    Ok(())
+}
+
+/// Prints the check of every field and the decoded minute
+///
+/// The unknown bits are filled by the soft decoder when they can be deduced,
+/// the unknown third party bits and call bit being taken as 0.
+fn decode(output: &mut impl Write, input: Frame) -> std::io::Result<ExitCode> {
+    writeln!(output, "Bitstring: {:#}", input)?;
+    let input = match u64::try_from(input.resolve_for_decoding()) {
+        Ok(input) => input,
+        Err(error) => {
+            writeln!(output, "Error:     {}", error)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    writeln!(output, "Hex:       0x{:X}", input)?;
+    writeln!(output)?;
+    writeln!(output, "{:<12} {:>7}  {:>6}  Status", "Field", "Seconds", "Value")?;
+    for report in field_reports(input) {
+        let seconds = report.field.seconds();
+        let seconds = if seconds.start() == seconds.end() {
+            seconds.start().to_string()
+        } else {
+            format!("{}-{}", seconds.start(), seconds.end())
+        };
+        let status = report.error.unwrap_or_else(|| "ok".to_string());
+        writeln!(output, "{:<12} {:>7}  {:>6}  {}", report.field.name(), seconds, report.value, status)?;
+    }
+    writeln!(output)?;
+    match from_dcf77(input) {
+        Ok(decoded_minutes) => {
+            let time_zone = if decoded_minutes.daily_saving_time { "CEST" } else { "CET" };
+            writeln!(output, "Decoded:   {} {}", decoded_minutes.date.format("%y-%m-%d %H:%M"), time_zone)?;
+            writeln!(output, "UTC:       {}", DstRules::german().resolve(decoded_minutes).unwrap_or_else(|_| to_utc(decoded_minutes)).to_rfc3339())?;
+            writeln!(output, "Call bit:  {}", decoded_minutes.antenna)?;
+            writeln!(output, "3rd party: 0x{:04X}", decoded_minutes.third_party)?;
+            writeln!(output, "A1:        {}", decoded_minutes.announce_daily_saving_time)?;
+            writeln!(output, "A2:        {}", decoded_minutes.bit_leap_second)?;
+            match DstRules::german().check(decoded_minutes) {
+                Ok(()) => writeln!(output, "DST rules: ok")?,
+                Err(error) => writeln!(output, "DST rules: {}", error)?
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(error) => {
+            writeln!(output, "Error:     {}", error)?;
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+/// Codes the telegram of a CET or CEST time
+///
+/// The day of the week is the one of the full date, not of the year within
+/// the century held by the DCF77 struct.
+fn encode_telegram(date: DateTime<FixedOffset>) -> Result<u64, String> {
+    let daily_saving_time = match date.offset().local_minus_utc() {
+        3600 => false,
+        7200 => true,
+        _ => return Err("The time must be given in CET (+01:00) or CEST (+02:00)".to_string())
+    };
+    let local_date = date.naive_local();
+    let dcf_data = DCF77 {
+        date: Utc.from_utc_datetime(&local_date.with_year(local_date.year().rem_euclid(100)).unwrap()),
+        antenna: false,
+        announce_daily_saving_time: false,
+        daily_saving_time,
+        standard_time: !daily_saving_time,
+        bit_leap_second: false,
+        third_party: 0
+    };
+    to_dcf77_with_day_of_week(dcf_data, local_date.weekday()).map_err(|error| format!("Error on creating the dcf77 {}", error))
+}
+
+/// Prints the telegram of a CET or CEST time
+fn encode(output: &mut impl Write, input: &str) -> std::io::Result<ExitCode> {
+    let date = match DateTime::parse_from_rfc3339(input) {
+        Ok(date) => date,
+        Err(error) => {
+            eprintln!("Invalid RFC3339 time {:?}: {}", input, error);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    let coded_minutes = match encode_telegram(date) {
+        Ok(coded_minutes) => coded_minutes,
+        Err(error) => {
+            eprintln!("{}", error);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    if DstRules::german().is_daylight_saving(date.with_timezone(&Utc)) != (date.offset().local_minus_utc() == 7200) {
+        eprintln!("Warning: the German rules give the other time zone at {}", input);
+    }
+    writeln!(output, "Hex:       0x{:X}", coded_minutes)?;
+    writeln!(output, "Bitstring: {}", Frame::from(coded_minutes))?;
+    writeln!(output)?;
+    print_seconds(output, Frame::from(coded_minutes))?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Output format of the schedule
+#[derive(Copy, Clone, PartialEq)]
+enum ScheduleFormat {
+    Csv,
+    JsonLines,
+    Binary
+}
+
+/// Writes one minute of the schedule
+fn write_minute(output: &mut impl Write, minute: &ScheduledMinute, format: ScheduleFormat) -> std::io::Result<()> {
+    let utc = minute.utc.to_rfc3339_opts(SecondsFormat::Secs, true);
+    let local = minute.local.to_rfc3339_opts(SecondsFormat::Secs, false);
+    match format {
+        ScheduleFormat::Csv => writeln!(output, "{},{},0x{:X},{},{}", utc, local, minute.frame, Frame::from(minute.frame), minute.leap_second),
+        ScheduleFormat::JsonLines => writeln!(output, "{{\"utc\":\"{}\",\"local\":\"{}\",\"frame\":\"0x{:X}\",\"bits\":\"{}\",\"leap_second\":{}}}",
+                                              utc, local, minute.frame, Frame::from(minute.frame), minute.leap_second),
+        ScheduleFormat::Binary => output.write_all(&minute.frame.to_be_bytes())
+    }
+}
+
+/// Prints the telegram of every minute of a range
+fn schedule(output: &mut impl Write, options: &[&str]) -> std::io::Result<ExitCode> {
+    let mut from = None;
+    let mut to = None;
+    let mut format = ScheduleFormat::Csv;
+    for option in options.chunks(2) {
+        match option {
+            ["--from", value] | ["--to", value] => {
+                let date = match DateTime::parse_from_rfc3339(value) {
+                    Ok(date) => date.with_timezone(&Utc),
+                    Err(error) => {
+                        eprintln!("Invalid RFC3339 time {:?}: {}", value, error);
+                        return Ok(ExitCode::FAILURE);
+                    }
+                };
+                if option[0] == "--from" { from = Some(date) } else { to = Some(date) }
+            }
+            ["--format", "csv"] => format = ScheduleFormat::Csv,
+            ["--format", "jsonl"] => format = ScheduleFormat::JsonLines,
+            ["--format", "binary"] => format = ScheduleFormat::Binary,
+            _ => {
+                eprintln!("{}", USAGE);
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+    }
+    let (Some(from), Some(to)) = (from, to) else {
+        eprintln!("{}", USAGE);
+        return Ok(ExitCode::FAILURE);
+    };
+    if format == ScheduleFormat::Csv {
+        writeln!(output, "utc,local,frame,bits,leap_second")?;
+    }
+    for minute in Schedule::new(from, to) {
+        write_minute(output, &minute, format)?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the Value Change Dump of the edges of a capture file
+fn capture_vcd(output: &mut impl Write, path: &str) -> std::io::Result<ExitCode> {
+    let edges: Result<Vec<Edge>, std::io::Error> = std::fs::File::open(path)
+        .and_then(|file| CaptureReader::new(std::io::BufReader::new(file)))
+        .and_then(|reader| reader
+            .filter_map(|record| match record {
+                Ok(Record::Edge(edge)) => Some(Ok(edge)),
+                Ok(_) => None,
+                Err(error) => Some(Err(error))
+            })
+            .collect());
+    match edges {
+        Ok(edges) => {
+            write!(output, "{}", edges_to_vcd(&edges))?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(error) => {
+            eprintln!("Error on reading the capture {}: {}", path, error);
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+/// Runs the command given by the arguments, writing its output
+fn run(output: &mut impl Write, arguments: &[&str]) -> std::io::Result<ExitCode> {
+    match arguments {
+        ["encode", argument] => encode(output, argument),
+        [command @ ("decode" | "explain"), argument] => {
+            let input = match parse_telegram(argument) {
+                Ok(input) => input,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    return Ok(ExitCode::FAILURE);
+                }
+            };
+            if *command == "decode" {
+                decode(output, input)
+            } else {
+                print_seconds(output, input)?;
+                Ok(ExitCode::SUCCESS)
+            }
+        }
+        ["schedule", options @ ..] => schedule(output, options),
+        ["vcd", "--capture", path] => capture_vcd(output, path),
+        ["vcd", argument] => {
+            let input = parse_telegram(argument).and_then(|frame| u64::try_from(frame.resolve_for_decoding()).map_err(|error| error.to_string()));
+            match input {
+                Ok(input) => {
+                    write!(output, "{}", minute_to_vcd(input, false))?;
+                    Ok(ExitCode::SUCCESS)
+                }
+                Err(error) => {
+                    eprintln!("{}", error);
+                    Ok(ExitCode::FAILURE)
+                }
+            }
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    let arguments: Vec<&str> = arguments.iter().map(String::as_str).collect();
+    let mut output = std::io::BufWriter::new(std::io::stdout().lock());
+    match run(&mut output, &arguments).and_then(|exit_code| output.flush().map(|_| exit_code)) {
+        Ok(exit_code) => exit_code,
+        // The reader of the output went away, as `head` does
+        Err(error) if error.kind() == ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error on writing the output {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_encode_day_of_week() {
+        // The 1st of June 1995 is a Thursday, the one of 2095 a Wednesday
+        let coded_minutes = encode_telegram(DateTime::parse_from_rfc3339("1995-06-01T12:00:00+02:00").unwrap()).unwrap();
+        let day_of_week = field_reports(coded_minutes).into_iter().find(|report| report.field == Field::DayOfWeek).unwrap();
+        assert!(day_of_week.value == 4 && day_of_week.error.is_none());
+        let coded_minutes = encode_telegram(DateTime::parse_from_rfc3339("2095-06-01T12:00:00+02:00").unwrap()).unwrap();
+        let day_of_week = field_reports(coded_minutes).into_iter().find(|report| report.field == Field::DayOfWeek).unwrap();
+        assert!(day_of_week.value == 3);
+        assert!(from_dcf77(coded_minutes).is_ok());
+        assert!(encode_telegram(DateTime::parse_from_rfc3339("1995-06-01T12:00:00+03:00").unwrap()).is_err());
+    }
+    #[test]
+    fn test_broken_pipe() {
+        /// Output whose reader went away
+        struct ClosedPipe;
+        impl Write for ClosedPipe {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::from(ErrorKind::BrokenPipe))
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let result = run(&mut ClosedPipe, &["schedule", "--from", "2023-06-14T08:00:00Z", "--to", "2023-06-15T08:00:00Z"]);
+        assert!(result.unwrap_err().kind() == ErrorKind::BrokenPipe);
+    }
+}
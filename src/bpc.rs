@@ -0,0 +1,378 @@
+//! Coding and decoding of the BPC (China, 68.5 kHz) time code
+//!
+//! BPC sends one frame every 20 seconds, three frames per minute. Each second
+//! carries a dibit symbol [0..4) given by the width of the pulse (100, 200, 300
+//! or 400 ms), and the first second of each frame has no pulse at all (P0).
+//!
+//! A frame is stored in a `u64` with two bits per symbol, the first symbol of
+//! the frame being the most significant one, in the same spirit as the DCF77
+//! bit field where the bit of the second `n` is stored at position `59 - n`:
+//!
+//! | Symbol | Content                                  |
+//! |--------|------------------------------------------|
+//! | 0      | P0 frame marker                          |
+//! | 1      | P1 frame index (0, 1, 2)                 |
+//! | 2      | P2 reserved                              |
+//! | 3..5   | Hour [0..12)                             |
+//! | 5..8   | Minutes [0..60)                          |
+//! | 8..10  | Day of the week [1..7]                   |
+//! | 10     | P3 PM bit and even parity of symbols 1..10 |
+//! | 11..14 | Day [1..31]                              |
+//! | 14..16 | Month [1..12]                            |
+//! | 16..19 | Year [0..64) since 2000                  |
+//! | 19     | P4 year bit 6 and even parity of symbols 11..20 |
+
+use std::io::Error;
+use std::io::ErrorKind;
+use std::time::Duration;
+use chrono::prelude::*;
+
+/// Number of symbols in a BPC frame
+pub const SYMBOLS_PER_FRAME: usize = 20;
+
+/// Struct that represents the BPC information
+///
+/// The date is the one broadcast by the station (Beijing time) stored as a
+/// chrono `DateTime<Utc>`, the same way `DCF77` does. The seconds tell which of
+/// the three frames of the minute is meant: 0, 20 or 40.
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::bpc::*;
+///let test_time = BPC {
+///    date: Utc.with_ymd_and_hms(2023, 5, 17, 15, 42, 20).unwrap()
+///};
+///let coded_frame = to_bpc(test_time).unwrap();
+///let decoded_frame = from_bpc(coded_frame).unwrap();
+///assert!(decoded_frame.date == test_time.date);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BPC {
+    /// Date in UTC timezone
+    pub date: DateTime<Utc>
+}
+
+/// Position of the frame index (P1) in the BPC frame
+const FRAME_INDEX_POSITION: u8 = 36;
+
+/// Mask for the frame index (P1) in the BPC frame
+const FRAME_INDEX_MASK: u64 = 0x3;
+
+/// Position of the hour in the BPC frame
+const HOUR_POSITION: u8 = 30;
+
+/// Mask for the hour in the BPC frame
+const HOUR_MASK: u64 = 0xF;
+
+/// Position of the minutes in the BPC frame
+const MINUTES_POSITION: u8 = 24;
+
+/// Mask for the minutes in the BPC frame
+const MINUTES_MASK: u64 = 0x3F;
+
+/// Position of the day of the week in the BPC frame
+const DAY_OF_WEEK_POSITION: u8 = 20;
+
+/// Mask for the day of the week in the BPC frame
+const DAY_OF_WEEK_MASK: u64 = 0xF;
+
+/// Mask for the PM bit (high bit of P3) in the BPC frame
+const PM_BIT_MASK: u64 = 1 << 19;
+
+/// Mask for the parity of the time (low bit of P3) in the BPC frame
+const PARITY_TIME_BIT_MASK: u64 = 1 << 18;
+
+/// Bits of the symbols 1..10 covered by the time parity
+const PARITY_TIME_COVERAGE: u64 = 0x3F_FFFC_0000;
+
+/// Position of the day in the BPC frame
+const DAY_POSITION: u8 = 12;
+
+/// Mask for the day in the BPC frame
+const DAY_MASK: u64 = 0x3F;
+
+/// Position of the month in the BPC frame
+const MONTH_POSITION: u8 = 8;
+
+/// Mask for the month in the BPC frame
+const MONTH_MASK: u64 = 0xF;
+
+/// Position of the year in the BPC frame
+const YEAR_POSITION: u8 = 2;
+
+/// Mask for the year in the BPC frame
+const YEAR_MASK: u64 = 0x3F;
+
+/// Mask for the most significant year bit (high bit of P4) in the BPC frame
+const YEAR_HIGH_BIT_MASK: u64 = 1 << 1;
+
+/// Mask for the parity of the date (low bit of P4) in the BPC frame
+const PARITY_DATE_BIT_MASK: u64 = 1;
+
+/// Bits of the symbols 11..19 and the year bit 6 covered by the date parity
+const PARITY_DATE_COVERAGE: u64 = 0x3_FFFE;
+
+/// First year that can be represented in a BPC frame
+const BASE_YEAR: i32 = 2000;
+
+/// Width of the pulse of the symbol 0, each next symbol is 100 ms longer
+const SYMBOL_WIDTH_MS: u128 = 100;
+
+/// Allowed deviation of a pulse width from its nominal value
+const SYMBOL_TOLERANCE_MS: u128 = 50;
+
+/// Checks the parity (even) of the bits covered by a mask
+fn proof_parity(input: u64, coverage: u64) -> bool {
+    (input & coverage).count_ones() % 2 == 0
+}
+
+/// Extracts a binary field out of a BPC frame
+fn extract(input: u64, position: u8, mask: u64) -> u32 {
+    ((input >> position) & mask) as u32
+}
+
+/// Places a binary field into a BPC frame checking its maximum
+fn place(input: u32, position: u8, mask: u64) -> Result<u64, Error> {
+    if u64::from(input) > mask {
+        let error_payload = format!("Above max! : 0x{:X} -> 0x{:X}", input, mask);
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    Ok(u64::from(input) << position)
+}
+
+/// Translates the width of a pulse into a BPC symbol [0..4)
+///
+/// # Examples
+/// ```
+///use std::time::Duration;
+///use dcf77_chrono::bpc::*;
+///assert!(symbol_from_pulse_width(Duration::from_millis(310)).unwrap() == 2);
+///assert!(symbol_from_pulse_width(Duration::from_millis(900)).is_err());
+/// ```
+pub fn symbol_from_pulse_width(width: Duration) -> Result<u8, Error> {
+    let width = width.as_millis();
+    for symbol in 0..4 {
+        let nominal = SYMBOL_WIDTH_MS * (symbol + 1);
+        if width.abs_diff(nominal) < SYMBOL_TOLERANCE_MS {
+            return Ok(symbol as u8);
+        }
+    }
+    let error_payload = format!("Invalid pulse width: {} ms", width);
+    Err(Error::new(ErrorKind::InvalidData, error_payload))
+}
+
+/// Gives the nominal pulse width of a BPC symbol [0..4)
+pub fn pulse_width_from_symbol(symbol: u8) -> Result<Duration, Error> {
+    if symbol < 4 {
+        Ok(Duration::from_millis((u64::from(symbol) + 1) * SYMBOL_WIDTH_MS as u64))
+    } else {
+        let error_payload = format!("Invalid symbol: {}", symbol);
+        Err(Error::new(ErrorKind::InvalidData, error_payload))
+    }
+}
+
+/// Splits a BPC frame into its 20 symbols, the first one being the P0 marker
+pub fn frame_to_symbols(input: u64) -> [u8; SYMBOLS_PER_FRAME] {
+    let mut output = [0; SYMBOLS_PER_FRAME];
+    for (index, symbol) in output.iter_mut().enumerate() {
+        *symbol = ((input >> (2 * (SYMBOLS_PER_FRAME - 1 - index))) & 0x3) as u8;
+    }
+    output
+}
+
+/// Joins 20 symbols into a BPC frame
+pub fn symbols_to_frame(input: &[u8; SYMBOLS_PER_FRAME]) -> u64 {
+    input.iter().fold(0, |frame, symbol| (frame << 2) | u64::from(symbol & 0x3))
+}
+
+/// Decodes the date out of a BPC frame
+///
+/// A BPC frame is given as input and a BPC struct is returned when successful
+pub fn from_bpc(input: u64) -> Result<BPC, Error> {
+    if !proof_parity(input, PARITY_TIME_COVERAGE | PARITY_TIME_BIT_MASK) {
+        let error_payload = format!("Invalid time parity - Input: 0x{:X}", input);
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    if !proof_parity(input, PARITY_DATE_COVERAGE | PARITY_DATE_BIT_MASK) {
+        let error_payload = format!("Invalid date parity - Input: 0x{:X}", input);
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    let mut hour = extract(input, HOUR_POSITION, HOUR_MASK);
+    if 0 < input & PM_BIT_MASK {
+        hour += 12;
+    }
+    let mut year = extract(input, YEAR_POSITION, YEAR_MASK) as i32;
+    if 0 < input & YEAR_HIGH_BIT_MASK {
+        year += 64;
+    }
+    let second = extract(input, FRAME_INDEX_POSITION, FRAME_INDEX_MASK) * 20;
+    match Utc.with_ymd_and_hms(BASE_YEAR + year,
+                               extract(input, MONTH_POSITION, MONTH_MASK),
+                               extract(input, DAY_POSITION, DAY_MASK),
+                               hour,
+                               extract(input, MINUTES_POSITION, MINUTES_MASK),
+                               second).single() {
+        Some(date) => Ok(BPC {date}),
+        None => {
+            let error_payload = format!("Invalid date - Input: 0x{:X}", input);
+            Err(Error::new(ErrorKind::InvalidData, error_payload))
+        }
+    }
+}
+
+/// Encodes a BPC frame containing the information of a BPC struct
+///
+/// A BPC struct is given as input and a BPC frame is returned when successful.
+/// The seconds of the date select the frame within the minute.
+pub fn to_bpc(bpc_data: BPC) -> Result<u64, Error> {
+    let given_date = bpc_data.date.date_naive();
+    let given_time = bpc_data.date.time();
+    let year = given_date.year() - BASE_YEAR;
+    if !(0..128).contains(&year) {
+        let error_payload = format!("Year out of range: {}", given_date.year());
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    let mut output = place(given_time.second() / 20, FRAME_INDEX_POSITION, FRAME_INDEX_MASK)?
+        | place(given_time.hour() % 12, HOUR_POSITION, HOUR_MASK)?
+        | place(given_time.minute(), MINUTES_POSITION, MINUTES_MASK)?
+        | place(given_date.weekday().number_from_monday(), DAY_OF_WEEK_POSITION, DAY_OF_WEEK_MASK)?
+        | place(given_date.day(), DAY_POSITION, DAY_MASK)?
+        | place(given_date.month(), MONTH_POSITION, MONTH_MASK)?
+        | place(year as u32 % 64, YEAR_POSITION, YEAR_MASK)?;
+    if given_time.hour() >= 12 {
+        output |= PM_BIT_MASK;
+    }
+    if year >= 64 {
+        output |= YEAR_HIGH_BIT_MASK;
+    }
+    if !proof_parity(output, PARITY_TIME_COVERAGE) {
+        output |= PARITY_TIME_BIT_MASK;
+    }
+    if !proof_parity(output, PARITY_DATE_COVERAGE) {
+        output |= PARITY_DATE_BIT_MASK;
+    }
+    Ok(output)
+}
+
+/// Streaming decoder that assembles BPC frames out of received symbols
+///
+/// Symbols are pushed one per second. A second without pulse (`None`) is the
+/// P0 marker that starts a new frame; once 19 more symbols have been received
+/// the frame is decoded.
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::bpc::*;
+///let test_time = BPC {
+///    date: Utc.with_ymd_and_hms(2023, 5, 17, 3, 2, 40).unwrap()
+///};
+///let mut decoder = BPCDecoder::new();
+///let mut output = None;
+///for (index, symbol) in frame_to_symbols(to_bpc(test_time).unwrap()).iter().enumerate() {
+///    let received = if index == 0 { None } else { Some(*symbol) };
+///    output = decoder.push_symbol(received);
+///}
+///assert!(output.unwrap().unwrap().date == test_time.date);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct BPCDecoder {
+    symbols: Vec<u8>,
+    synchronized: bool
+}
+
+impl BPCDecoder {
+    /// Creates a decoder waiting for the next frame marker
+    pub fn new() -> BPCDecoder {
+        BPCDecoder::default()
+    }
+
+    /// Pushes the symbol of one second, `None` meaning that no pulse was received
+    ///
+    /// Returns the decoded frame when the last symbol of a frame is pushed.
+    pub fn push_symbol(&mut self, input: Option<u8>) -> Option<Result<BPC, Error>> {
+        match input {
+            None => {
+                self.symbols.clear();
+                self.symbols.push(0);
+                self.synchronized = true;
+                None
+            }
+            Some(symbol) if self.synchronized => {
+                self.symbols.push(symbol & 0x3);
+                if self.symbols.len() == SYMBOLS_PER_FRAME {
+                    self.synchronized = false;
+                    let mut frame = [0; SYMBOLS_PER_FRAME];
+                    frame.copy_from_slice(&self.symbols);
+                    self.symbols.clear();
+                    Some(from_bpc(symbols_to_frame(&frame)))
+                } else {
+                    None
+                }
+            }
+            Some(_) => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_coder_decoder() {
+        let original_test_time = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        for fake_input in 0..=0xfff {
+            let test_time = BPC {
+                date: original_test_time + chrono::Duration::seconds(fake_input * 6_980)
+            };
+            let test_time = BPC {
+                date: test_time.date.with_second(test_time.date.second() / 20 * 20).unwrap()
+            };
+            match to_bpc(test_time) {
+                Ok(coded_frame) => {
+                    match from_bpc(coded_frame) {
+                        Ok(decoded_frame) => {
+                            assert!(test_time.date == decoded_frame.date)
+                        }
+                        Err(error) => {
+                            panic!("Error on decoding 0x{:X} {:?}", coded_frame, error)
+                        }
+                    }
+                }
+                Err(error) => {
+                    panic!("Error on coding the bpc {:?}", error)
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_parity() {
+        let test_time = BPC {
+            date: Utc.with_ymd_and_hms(2088, 12, 31, 23, 59, 40).unwrap()
+        };
+        let coded_frame = to_bpc(test_time).unwrap();
+        assert!(from_bpc(coded_frame).is_ok());
+        for bit in 0..38 {
+            assert!(from_bpc(coded_frame ^ (1 << bit)).is_err());
+        }
+    }
+    #[test]
+    fn test_symbols() {
+        for symbol in 0..4 {
+            let width = pulse_width_from_symbol(symbol).unwrap();
+            assert!(symbol_from_pulse_width(width).unwrap() == symbol);
+        }
+        assert!(pulse_width_from_symbol(4).is_err());
+        assert!(symbol_from_pulse_width(Duration::from_millis(30)).is_err());
+        let frame = 0x1_2345_6789;
+        assert!(symbols_to_frame(&frame_to_symbols(frame)) == frame);
+    }
+    #[test]
+    fn test_decoder_needs_marker() {
+        let mut decoder = BPCDecoder::new();
+        for _ in 0..SYMBOLS_PER_FRAME * 2 {
+            assert!(decoder.push_symbol(Some(1)).is_none());
+        }
+    }
+}
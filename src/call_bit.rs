@@ -0,0 +1,199 @@
+//! Monitoring of the call bit of the second 15
+//!
+//! The call bit (R) is set by the PTB when the transmitter runs irregularly,
+//! for instance on its backup antenna. The `CallBitMonitor` follows it over
+//! the decoded minutes, gives an event on every change of status with the
+//! duration of the period that ended, and keeps a bounded history of those
+//! periods.
+//!
+//! Every minute is placed at the UTC instant its telegram started to be
+//! sent, one minute before the minute it describes.
+
+use std::collections::VecDeque;
+use chrono::prelude::*;
+use crate::{DCF77, to_utc};
+
+/// Number of ended periods kept by default
+pub const DEFAULT_HISTORY: usize = 100;
+
+/// Status of the transmitter given by the call bit
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CallBitStatus {
+    /// Call bit cleared
+    Normal,
+    /// Call bit set, the transmitter runs irregularly
+    Irregular
+}
+
+impl From<bool> for CallBitStatus {
+    fn from(call_bit: bool) -> CallBitStatus {
+        if call_bit {
+            CallBitStatus::Irregular
+        } else {
+            CallBitStatus::Normal
+        }
+    }
+}
+
+/// Period of minutes with the same status
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CallBitPeriod {
+    /// Status during the period
+    pub status: CallBitStatus,
+    /// Start of the first minute of the period
+    pub start: DateTime<Utc>,
+    /// End of the last minute seen with the status
+    pub end: DateTime<Utc>,
+    /// Minutes decoded during the period
+    pub minutes: u32
+}
+
+impl CallBitPeriod {
+    /// Duration of the period
+    pub fn duration(&self) -> chrono::Duration {
+        self.end - self.start
+    }
+}
+
+/// Change of the status of the transmitter
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CallBitEvent {
+    /// Start of the first minute with the new status
+    pub time: DateTime<Utc>,
+    /// New status
+    pub status: CallBitStatus,
+    /// Period that ended with the change
+    pub previous: CallBitPeriod
+}
+
+/// Tracker of the call bit over the decoded minutes
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::*;
+///use dcf77_chrono::call_bit::*;
+///let mut monitor = CallBitMonitor::new();
+///let mut events = Vec::new();
+///for minute in 0..10 {
+///    let dcf_data = DCF77 {
+///        date: Utc.with_ymd_and_hms(23, 1, 10, 12, minute, 0).unwrap(),
+///        antenna: (3..7).contains(&minute),
+///        announce_daily_saving_time: false,
+///        daily_saving_time: false,
+///        standard_time: true,
+///        bit_leap_second: false,
+///        third_party: 0
+///    };
+///    events.extend(monitor.push(dcf_data));
+///}
+///assert!(events.len() == 2);
+///assert!(events[0].status == CallBitStatus::Irregular);
+///assert!(events[1].previous.duration() == chrono::Duration::minutes(4));
+///assert!(monitor.status() == Some(CallBitStatus::Normal));
+/// ```
+#[derive(Clone, Debug)]
+pub struct CallBitMonitor {
+    current: Option<CallBitPeriod>,
+    history: VecDeque<CallBitPeriod>,
+    capacity: usize
+}
+
+impl Default for CallBitMonitor {
+    fn default() -> CallBitMonitor {
+        CallBitMonitor::with_history(DEFAULT_HISTORY)
+    }
+}
+
+impl CallBitMonitor {
+    /// Creates a monitor keeping the last `DEFAULT_HISTORY` periods
+    pub fn new() -> CallBitMonitor {
+        CallBitMonitor::default()
+    }
+
+    /// Creates a monitor keeping the last `capacity` periods
+    pub fn with_history(capacity: usize) -> CallBitMonitor {
+        CallBitMonitor {current: None, history: VecDeque::new(), capacity}
+    }
+
+    /// Status of the last minute, `None` before the first one
+    pub fn status(&self) -> Option<CallBitStatus> {
+        self.current.map(|period| period.status)
+    }
+
+    /// Period of the current status, still going on
+    pub fn current(&self) -> Option<CallBitPeriod> {
+        self.current
+    }
+
+    /// Ended periods, the oldest first
+    pub fn history(&self) -> impl Iterator<Item = &CallBitPeriod> {
+        self.history.iter()
+    }
+
+    /// Pushes a decoded minute, giving the change of status it brings
+    ///
+    /// The first minute only sets the status, and minutes older than the
+    /// last one are ignored.
+    pub fn push(&mut self, dcf_data: DCF77) -> Option<CallBitEvent> {
+        let start = to_utc(dcf_data) - chrono::Duration::minutes(1);
+        let end = start + chrono::Duration::minutes(1);
+        let status = CallBitStatus::from(dcf_data.antenna);
+        let Some(current) = self.current.as_mut() else {
+            self.current = Some(CallBitPeriod {status, start, end, minutes: 1});
+            return None;
+        };
+        if start < current.end - chrono::Duration::minutes(1) {
+            return None;
+        }
+        if status == current.status {
+            current.end = end;
+            current.minutes += 1;
+            return None;
+        }
+        let previous = *current;
+        *current = CallBitPeriod {status, start, end, minutes: 1};
+        if self.capacity > 0 {
+            if self.history.len() == self.capacity {
+                self.history.pop_front();
+            }
+            self.history.push_back(previous);
+        }
+        Some(CallBitEvent {time: start, status, previous})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::Schedule;
+    #[test]
+    fn test_history() {
+        let from = Utc.with_ymd_and_hms(2023, 3, 26, 0, 30, 0).unwrap();
+        let mut monitor = CallBitMonitor::with_history(2);
+        let mut events = Vec::new();
+        for (index, minute) in Schedule::new(from, from + chrono::Duration::hours(1)).enumerate() {
+            // Irregular from 00:39 to 00:44 and from 00:59 to 01:19 UTC, with
+            // a gap in the reception at 01:00 UTC, the switch to CEST
+            if index == 31 {
+                continue;
+            }
+            let dcf_data = DCF77 {antenna: (10..15).contains(&index) || (30..50).contains(&index), ..minute.dcf};
+            events.extend(monitor.push(dcf_data));
+        }
+        assert!(events.len() == 4);
+        assert!(events[0].time == Utc.with_ymd_and_hms(2023, 3, 26, 0, 39, 0).unwrap());
+        assert!(events[0].previous.minutes == 10);
+        assert!(events[1].previous.duration() == chrono::Duration::minutes(5));
+        assert!(events[3].previous.status == CallBitStatus::Irregular);
+        assert!(events[3].previous.minutes == 19);
+        assert!(events[3].previous.duration() == chrono::Duration::minutes(20));
+        let history: Vec<CallBitPeriod> = monitor.history().copied().collect();
+        assert!(history == [events[2].previous, events[3].previous]);
+        assert!(monitor.current().unwrap().minutes == 10);
+        // A minute older than the last one is ignored
+        let old = Schedule::new(from, from + chrono::Duration::minutes(1)).next().unwrap().dcf;
+        assert!(monitor.push(DCF77 {antenna: true, ..old}).is_none());
+        assert!(monitor.status() == Some(CallBitStatus::Normal));
+    }
+}
@@ -0,0 +1,720 @@
+//! Recording and replay of receiver sessions
+//!
+//! A capture is a sequence of `Record`s: receiver metadata, raw edges or bits
+//! with their time, the frames decoded out of them and the errors found. Two
+//! formats are available and the reader detects which one it is given.
+//!
+//! JSON Lines, one object per line, times in microseconds:
+//! ```text
+//! {"type":"metadata","key":"receiver","value":"Conrad DCF-1"}
+//! {"type":"edge","time_us":1000000,"level":true}
+//! {"type":"bit","time_us":1000000,"value":"1"}
+//! {"type":"frame","time_us":60000000,"bits":"00000000000000000100100001100000100100101011001100110001001"}
+//! {"type":"error","time_us":61000000,"message":"2 pulses missing"}
+//! ```
+//! The `value` of a bit is `"0"`, `"1"` or `"?"`, and the `bits` of a frame
+//! use the text of the `frame` module.
+//!
+//! Binary, starting with the 8 bytes `DCF77CAP` and the version byte 1,
+//! followed by records made of a tag byte and a payload. The time of a record
+//! is given as the difference in microseconds with the previous record with a
+//! time, as a zigzag LEB128 number (0, -1, 1, -2... coded 0, 1, 2, 3...):
+//! - `0x01` / `0x02`: edge to the low / high level, with the time
+//! - `0x03` / `0x04` / `0x05`: bit 0 / 1 / unknown, with the time
+//! - `0x06`: frame, with the time, the bits and the mask of known bits as little endian `u64`
+//! - `0x07`: error, with the time, the length of the message as LEB128 and the UTF-8 message
+//! - `0x08`: metadata, with the length and UTF-8 bytes of the key and then of the value
+//!
+//! `replay` pushes a capture through a `Receiver`, at the original pace,
+//! faster, or as fast as possible, and compares the frames it decodes with the
+//! recorded ones.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Error, ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
+use crate::frame::Frame;
+use crate::receiver::{Edge, Receiver, ReceiverEvent};
+
+/// Start of a binary capture
+pub const BINARY_MAGIC: [u8; 8] = *b"DCF77CAP";
+
+/// Version of the binary capture format
+pub const BINARY_VERSION: u8 = 1;
+
+/// Tag of an edge to the low level
+const TAG_EDGE_LOW: u8 = 0x01;
+
+/// Tag of an edge to the high level
+const TAG_EDGE_HIGH: u8 = 0x02;
+
+/// Tag of a bit 0
+const TAG_BIT_ZERO: u8 = 0x03;
+
+/// Tag of a bit 1
+const TAG_BIT_ONE: u8 = 0x04;
+
+/// Tag of an unknown bit
+const TAG_BIT_UNKNOWN: u8 = 0x05;
+
+/// Tag of a frame
+const TAG_FRAME: u8 = 0x06;
+
+/// Tag of an error
+const TAG_ERROR: u8 = 0x07;
+
+/// Tag of a metadata entry
+const TAG_METADATA: u8 = 0x08;
+
+/// Record of a capture
+#[derive(Clone, Debug, PartialEq)]
+pub enum Record {
+    /// Information about the receiver or the session
+    Metadata {
+        /// Name of the information
+        key: String,
+        /// Value of the information
+        value: String
+    },
+    /// Edge of the demodulated signal
+    Edge(Edge),
+    /// Bit whose pulse started at `time`
+    Bit {
+        /// Start of the pulse
+        time: Duration,
+        /// Value of the bit, `None` when unknown
+        value: Option<bool>
+    },
+    /// Frame decoded at a minute marker
+    Frame {
+        /// Time of the minute marker
+        marker: Duration,
+        /// Bits of the minute
+        frame: Frame
+    },
+    /// Error found by the decoder
+    Error {
+        /// Time of the problem
+        time: Duration,
+        /// Description of the problem
+        message: String
+    }
+}
+
+impl Record {
+    /// Time of the record, metadata having none
+    pub fn time(&self) -> Option<Duration> {
+        match self {
+            Record::Metadata {..} => None,
+            Record::Edge(edge) => Some(edge.time),
+            Record::Bit {time, ..} | Record::Error {time, ..} => Some(*time),
+            Record::Frame {marker, ..} => Some(*marker)
+        }
+    }
+}
+
+/// Records the events of a receiver, giving back the events derived from the
+/// frames (call bit changes and third party packets) since the frames are already recorded
+impl TryFrom<ReceiverEvent> for Record {
+    type Error = ReceiverEvent;
+
+    fn try_from(event: ReceiverEvent) -> Result<Record, ReceiverEvent> {
+        match event {
+            ReceiverEvent::Bit {time, value, ..} => Ok(Record::Bit {time, value}),
+            ReceiverEvent::Minute {marker, frame} => Ok(Record::Frame {marker, frame}),
+            ReceiverEvent::Error {time, message} => Ok(Record::Error {time, message}),
+            event @ (ReceiverEvent::CallBit {..} | ReceiverEvent::Packet {..}) => Err(event)
+        }
+    }
+}
+
+/// Format of a capture
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CaptureFormat {
+    /// One JSON object per line
+    JsonLines,
+    /// Compact binary records
+    Binary
+}
+
+/// Escapes a string for JSON
+fn escape_json(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    for character in input.chars() {
+        match character {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            character if u32::from(character) < 0x20 => output.push_str(&format!("\\u{:04x}", u32::from(character))),
+            character => output.push(character)
+        }
+    }
+    output
+}
+
+/// Value of a flat JSON object
+#[derive(Clone, Debug, PartialEq)]
+enum JsonValue {
+    Text(String),
+    Number(u64),
+    Boolean(bool)
+}
+
+/// Builds the error of an invalid capture
+fn invalid_capture(error_payload: String) -> Error {
+    Error::new(ErrorKind::InvalidData, error_payload)
+}
+
+/// Parses a JSON string starting after its opening quote
+fn parse_json_string(characters: &mut std::iter::Peekable<std::str::Chars>) -> Result<String, Error> {
+    let mut output = String::new();
+    loop {
+        match characters.next() {
+            Some('"') => return Ok(output),
+            Some('\\') => match characters.next() {
+                Some('"') => output.push('"'),
+                Some('\\') => output.push('\\'),
+                Some('/') => output.push('/'),
+                Some('n') => output.push('\n'),
+                Some('r') => output.push('\r'),
+                Some('t') => output.push('\t'),
+                Some('b') => output.push('\u{8}'),
+                Some('f') => output.push('\u{c}'),
+                Some('u') => {
+                    let digits: String = characters.by_ref().take(4).collect();
+                    let code = u32::from_str_radix(&digits, 16).map_err(|_| invalid_capture(format!("Invalid escape \\u{}", digits)))?;
+                    output.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                }
+                other => return Err(invalid_capture(format!("Invalid escape {:?}", other)))
+            },
+            Some(character) => output.push(character),
+            None => return Err(invalid_capture("Unterminated string".to_string()))
+        }
+    }
+}
+
+/// Parses a JSON object whose values are strings, unsigned numbers or booleans
+fn parse_json_object(line: &str) -> Result<HashMap<String, JsonValue>, Error> {
+    let mut object = HashMap::new();
+    let mut characters = line.trim().chars().peekable();
+    if characters.next() != Some('{') {
+        return Err(invalid_capture(format!("Not a JSON object: {}", line)));
+    }
+    loop {
+        while characters.next_if(|character| character.is_whitespace()).is_some() {}
+        match characters.next() {
+            Some('}') if object.is_empty() => break,
+            Some('"') => {}
+            _ => return Err(invalid_capture(format!("Expected a key: {}", line)))
+        }
+        let key = parse_json_string(&mut characters)?;
+        while characters.next_if(|character| character.is_whitespace()).is_some() {}
+        if characters.next() != Some(':') {
+            return Err(invalid_capture(format!("Expected ':' after {:?}: {}", key, line)));
+        }
+        while characters.next_if(|character| character.is_whitespace()).is_some() {}
+        let value = match characters.peek() {
+            Some('"') => {
+                characters.next();
+                JsonValue::Text(parse_json_string(&mut characters)?)
+            }
+            Some(character) if character.is_ascii_digit() => {
+                let mut digits = String::new();
+                while let Some(digit) = characters.next_if(|character| character.is_ascii_digit()) {
+                    digits.push(digit);
+                }
+                JsonValue::Number(digits.parse().map_err(|_| invalid_capture(format!("Invalid number {}", digits)))?)
+            }
+            Some('t') | Some('f') => {
+                let word: String = std::iter::from_fn(|| characters.next_if(|character| character.is_ascii_alphabetic())).collect();
+                match word.as_str() {
+                    "true" => JsonValue::Boolean(true),
+                    "false" => JsonValue::Boolean(false),
+                    _ => return Err(invalid_capture(format!("Invalid value {}", word)))
+                }
+            }
+            _ => return Err(invalid_capture(format!("Invalid value for {:?}: {}", key, line)))
+        };
+        object.insert(key, value);
+        while characters.next_if(|character| character.is_whitespace()).is_some() {}
+        match characters.next() {
+            Some(',') => {}
+            Some('}') => break,
+            _ => return Err(invalid_capture(format!("Expected ',' or '}}': {}", line)))
+        }
+    }
+    if characters.any(|character| !character.is_whitespace()) {
+        return Err(invalid_capture(format!("Characters after the object: {}", line)));
+    }
+    Ok(object)
+}
+
+/// Formats a record as a JSON line, without the line feed
+pub fn record_to_json(record: &Record) -> String {
+    match record {
+        Record::Metadata {key, value} => format!("{{\"type\":\"metadata\",\"key\":\"{}\",\"value\":\"{}\"}}", escape_json(key), escape_json(value)),
+        Record::Edge(edge) => format!("{{\"type\":\"edge\",\"time_us\":{},\"level\":{}}}", edge.time.as_micros(), edge.level),
+        Record::Bit {time, value} => {
+            let value = match value {
+                Some(true) => "1",
+                Some(false) => "0",
+                None => "?"
+            };
+            format!("{{\"type\":\"bit\",\"time_us\":{},\"value\":\"{}\"}}", time.as_micros(), value)
+        }
+        Record::Frame {marker, frame} => format!("{{\"type\":\"frame\",\"time_us\":{},\"bits\":\"{}\"}}", marker.as_micros(), frame),
+        Record::Error {time, message} => format!("{{\"type\":\"error\",\"time_us\":{},\"message\":\"{}\"}}", time.as_micros(), escape_json(message))
+    }
+}
+
+/// Parses a record out of a JSON line
+pub fn record_from_json(line: &str) -> Result<Record, Error> {
+    let object = parse_json_object(line)?;
+    let text = |key: &str| match object.get(key) {
+        Some(JsonValue::Text(text)) => Ok(text.clone()),
+        _ => Err(invalid_capture(format!("Missing text {:?}: {}", key, line)))
+    };
+    let time = || match object.get("time_us") {
+        Some(JsonValue::Number(time)) => Ok(Duration::from_micros(*time)),
+        _ => Err(invalid_capture(format!("Missing time_us: {}", line)))
+    };
+    match text("type")?.as_str() {
+        "metadata" => Ok(Record::Metadata {key: text("key")?, value: text("value")?}),
+        "edge" => match object.get("level") {
+            Some(JsonValue::Boolean(level)) => Ok(Record::Edge(Edge {time: time()?, level: *level})),
+            _ => Err(invalid_capture(format!("Missing level: {}", line)))
+        },
+        "bit" => {
+            let value = match text("value")?.as_str() {
+                "0" => Some(false),
+                "1" => Some(true),
+                "?" => None,
+                value => return Err(invalid_capture(format!("Invalid bit {:?}", value)))
+            };
+            Ok(Record::Bit {time: time()?, value})
+        }
+        "frame" => Ok(Record::Frame {marker: time()?, frame: text("bits")?.parse()?}),
+        "error" => Ok(Record::Error {time: time()?, message: text("message")?}),
+        record_type => Err(invalid_capture(format!("Unknown record type {:?}", record_type)))
+    }
+}
+
+/// Writes an unsigned LEB128 number
+fn write_leb128(output: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            output.push(byte);
+            return;
+        }
+        output.push(byte | 0x80);
+    }
+}
+
+/// Reads an unsigned LEB128 number
+fn read_leb128(input: &mut impl Read) -> Result<u64, Error> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        input.read_exact(&mut byte)?;
+        value |= u64::from(byte[0] & 0x7F) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid_capture("LEB128 number too long".to_string()))
+}
+
+/// Reads a string prefixed with its LEB128 length
+fn read_string(input: &mut impl Read) -> Result<String, Error> {
+    let length = read_leb128(input)?;
+    let mut bytes = Vec::new();
+    input.take(length).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != length {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "Truncated string"));
+    }
+    String::from_utf8(bytes).map_err(|error| invalid_capture(format!("Invalid UTF-8: {}", error)))
+}
+
+/// Writer of a capture
+#[derive(Debug)]
+pub struct CaptureWriter<W: Write> {
+    output: W,
+    format: CaptureFormat,
+    last_time: Duration
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Starts a capture, writing the header of the binary format
+    pub fn new(mut output: W, format: CaptureFormat) -> Result<CaptureWriter<W>, Error> {
+        if format == CaptureFormat::Binary {
+            output.write_all(&BINARY_MAGIC)?;
+            output.write_all(&[BINARY_VERSION])?;
+        }
+        Ok(CaptureWriter {output, format, last_time: Duration::ZERO})
+    }
+
+    /// Writes a record
+    pub fn write(&mut self, record: &Record) -> Result<(), Error> {
+        if self.format == CaptureFormat::JsonLines {
+            return writeln!(self.output, "{}", record_to_json(record));
+        }
+        let mut bytes = Vec::new();
+        let tag = match record {
+            Record::Metadata {..} => TAG_METADATA,
+            Record::Edge(Edge {level: false, ..}) => TAG_EDGE_LOW,
+            Record::Edge(Edge {level: true, ..}) => TAG_EDGE_HIGH,
+            Record::Bit {value: Some(false), ..} => TAG_BIT_ZERO,
+            Record::Bit {value: Some(true), ..} => TAG_BIT_ONE,
+            Record::Bit {value: None, ..} => TAG_BIT_UNKNOWN,
+            Record::Frame {..} => TAG_FRAME,
+            Record::Error {..} => TAG_ERROR
+        };
+        bytes.push(tag);
+        if let Some(time) = record.time() {
+            let delta = time.as_micros() as i64 - self.last_time.as_micros() as i64;
+            write_leb128(&mut bytes, ((delta << 1) ^ (delta >> 63)) as u64);
+            self.last_time = time;
+        }
+        match record {
+            Record::Metadata {key, value} => {
+                for text in [key, value] {
+                    write_leb128(&mut bytes, text.len() as u64);
+                    bytes.extend_from_slice(text.as_bytes());
+                }
+            }
+            Record::Frame {frame, ..} => {
+                bytes.extend_from_slice(&frame.bits.to_le_bytes());
+                bytes.extend_from_slice(&frame.known.to_le_bytes());
+            }
+            Record::Error {message, ..} => {
+                write_leb128(&mut bytes, message.len() as u64);
+                bytes.extend_from_slice(message.as_bytes());
+            }
+            Record::Edge(_) | Record::Bit {..} => {}
+        }
+        self.output.write_all(&bytes)
+    }
+
+    /// Flushes the output
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.output.flush()
+    }
+
+    /// Gives the output back
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+}
+
+/// Reader of a capture, iterating over its records
+#[derive(Debug)]
+pub struct CaptureReader<R: BufRead> {
+    input: R,
+    format: CaptureFormat,
+    last_time: Duration
+}
+
+impl<R: BufRead> CaptureReader<R> {
+    /// Opens a capture, detecting its format
+    pub fn new(mut input: R) -> Result<CaptureReader<R>, Error> {
+        let format = if input.fill_buf()?.starts_with(&BINARY_MAGIC) {
+            input.consume(BINARY_MAGIC.len());
+            let mut version = [0];
+            input.read_exact(&mut version)?;
+            if version[0] != BINARY_VERSION {
+                return Err(invalid_capture(format!("Unsupported capture version {}", version[0])));
+            }
+            CaptureFormat::Binary
+        } else {
+            CaptureFormat::JsonLines
+        };
+        Ok(CaptureReader {input, format, last_time: Duration::ZERO})
+    }
+
+    /// Format of the capture
+    pub fn format(&self) -> CaptureFormat {
+        self.format
+    }
+
+    /// Reads the next binary record
+    fn read_binary(&mut self) -> Result<Option<Record>, Error> {
+        let mut tag = [0];
+        if self.input.read(&mut tag)? == 0 {
+            return Ok(None);
+        }
+        if tag[0] == TAG_METADATA {
+            let key = read_string(&mut self.input)?;
+            let value = read_string(&mut self.input)?;
+            return Ok(Some(Record::Metadata {key, value}));
+        }
+        let zigzag = read_leb128(&mut self.input)?;
+        let delta = (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64);
+        let Ok(time) = u64::try_from(self.last_time.as_micros() as i64 + delta) else {
+            return Err(invalid_capture("Record before the start of the capture".to_string()));
+        };
+        let time = Duration::from_micros(time);
+        self.last_time = time;
+        let record = match tag[0] {
+            TAG_EDGE_LOW => Record::Edge(Edge {time, level: false}),
+            TAG_EDGE_HIGH => Record::Edge(Edge {time, level: true}),
+            TAG_BIT_ZERO => Record::Bit {time, value: Some(false)},
+            TAG_BIT_ONE => Record::Bit {time, value: Some(true)},
+            TAG_BIT_UNKNOWN => Record::Bit {time, value: None},
+            TAG_FRAME => {
+                let mut words = [0; 16];
+                self.input.read_exact(&mut words)?;
+                let bits = u64::from_le_bytes(words[..8].try_into().unwrap());
+                let known = u64::from_le_bytes(words[8..].try_into().unwrap());
+                Record::Frame {marker: time, frame: Frame {bits, known}}
+            }
+            TAG_ERROR => Record::Error {time, message: read_string(&mut self.input)?},
+            tag => return Err(invalid_capture(format!("Unknown record tag 0x{:02X}", tag)))
+        };
+        Ok(Some(record))
+    }
+}
+
+impl<R: BufRead> Iterator for CaptureReader<R> {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Result<Record, Error>> {
+        match self.format {
+            CaptureFormat::Binary => self.read_binary().transpose(),
+            CaptureFormat::JsonLines => loop {
+                let mut line = String::new();
+                match self.input.read_line(&mut line) {
+                    Ok(0) => return None,
+                    Ok(_) if line.trim().is_empty() => continue,
+                    Ok(_) => return Some(record_from_json(&line)),
+                    Err(error) => return Some(Err(error))
+                }
+            }
+        }
+    }
+}
+
+/// Pace of a replay
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ReplaySpeed {
+    /// Same pace as the recording
+    Original,
+    /// Pace of the recording multiplied by a factor, which must be positive
+    Accelerated(f64),
+    /// No waiting between the records
+    Unpaced
+}
+
+/// Outcome of a replay
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplaySummary {
+    /// Number of records read
+    pub records: usize,
+    /// Metadata of the capture
+    pub metadata: Vec<(String, String)>,
+    /// Events produced by the receiver
+    pub events: Vec<ReceiverEvent>,
+    /// Frames found in the capture, as recorded
+    pub recorded_frames: Vec<(Duration, Frame)>,
+    /// Errors found in the capture, as recorded
+    pub recorded_errors: Vec<(Duration, String)>
+}
+
+impl ReplaySummary {
+    /// Frames decoded by the receiver during the replay
+    pub fn decoded_frames(&self) -> Vec<(Duration, Frame)> {
+        self.events.iter().filter_map(|event| match event {
+            ReceiverEvent::Minute {marker, frame} => Some((*marker, *frame)),
+            _ => None
+        }).collect()
+    }
+
+    /// Errors given by the receiver during the replay
+    pub fn decoded_errors(&self) -> Vec<(Duration, String)> {
+        self.events.iter().filter_map(|event| match event {
+            ReceiverEvent::Error {time, message} => Some((*time, message.clone())),
+            _ => None
+        }).collect()
+    }
+
+    /// Whether the receiver decoded the same frames and gave the same errors as the recorded ones
+    pub fn matches_recording(&self) -> bool {
+        self.decoded_frames() == self.recorded_frames && self.decoded_errors() == self.recorded_errors
+    }
+}
+
+/// Pushes the records of a capture through a receiver
+///
+/// The edges of the capture are pushed to the receiver, and so are its bits
+/// when it holds no edge before them; bits recorded along with the edges are
+/// taken as decoder output. The recorded frames and errors are kept for the
+/// comparison with the decoded ones.
+///
+/// An accelerated replay whose factor is not positive is an `InvalidInput`
+/// error.
+///
+/// # Examples
+/// ```
+///use std::time::Duration;
+///use dcf77_chrono::capture::*;
+///use dcf77_chrono::receiver::*;
+///let mut records = vec![Record::Metadata {key: "receiver".to_string(), value: "test".to_string()}];
+///for minute in 0..3 {
+///    let start = Duration::from_secs(60 * minute);
+///    records.extend(edges_from_frame(0x486092B3312, start, false).into_iter().map(Record::Edge));
+///}
+///let mut capture = CaptureWriter::new(Vec::new(), CaptureFormat::Binary).unwrap();
+///for record in &records {
+///    capture.write(record).unwrap();
+///}
+///let capture = capture.into_inner();
+///let reader = CaptureReader::new(capture.as_slice()).unwrap();
+///let summary = replay(reader, &mut Receiver::new(), ReplaySpeed::Unpaced).unwrap();
+///assert!(summary.records == records.len());
+///assert!(summary.decoded_frames().len() == 1);
+/// ```
+pub fn replay<I>(records: I, receiver: &mut Receiver, speed: ReplaySpeed) -> Result<ReplaySummary, Error>
+where I: IntoIterator<Item = Result<Record, Error>> {
+    if let ReplaySpeed::Accelerated(factor) = speed {
+        if factor.is_nan() || factor <= 0.0 {
+            let error_payload = format!("Replay factor must be positive: {}", factor);
+            return Err(Error::new(ErrorKind::InvalidInput, error_payload));
+        }
+    }
+    let mut summary = ReplaySummary::default();
+    let mut has_edges = false;
+    let mut start: Option<(Instant, Duration)> = None;
+    for record in records {
+        let record = record?;
+        summary.records += 1;
+        if let Some(time) = record.time() {
+            let factor = match speed {
+                ReplaySpeed::Original => Some(1.0),
+                ReplaySpeed::Accelerated(factor) => Some(factor),
+                ReplaySpeed::Unpaced => None
+            };
+            if let Some(factor) = factor {
+                let (start_instant, start_time) = *start.get_or_insert((Instant::now(), time));
+                let target = start_instant + time.saturating_sub(start_time).div_f64(factor);
+                if let Some(wait) = target.checked_duration_since(Instant::now()) {
+                    std::thread::sleep(wait);
+                }
+            }
+        }
+        match record {
+            Record::Metadata {key, value} => summary.metadata.push((key, value)),
+            Record::Edge(edge) => {
+                has_edges = true;
+                summary.events.extend(receiver.push_edge(edge));
+            }
+            Record::Bit {time, value} if !has_edges => summary.events.extend(receiver.push_bit(time, value)),
+            Record::Bit {..} => {}
+            Record::Error {time, message} => summary.recorded_errors.push((time, message)),
+            Record::Frame {marker, frame} => summary.recorded_frames.push((marker, frame))
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::receiver::edges_from_frame;
+    const TEST_FRAME: u64 = 0x486092B3312;
+    fn test_session() -> Vec<Record> {
+        let mut records = vec![Record::Metadata {key: "receiver".to_string(), value: "Test \"module\"\n".to_string()}];
+        let mut receiver = Receiver::new();
+        for minute in 0..4 {
+            for edge in edges_from_frame(TEST_FRAME, Duration::from_millis(60_000 * minute + 250), false) {
+                records.push(Record::Edge(edge));
+                records.extend(receiver.push_edge(edge).into_iter().filter_map(|event| Record::try_from(event).ok()));
+            }
+        }
+        records.push(Record::Error {time: Duration::from_secs(240), message: "Signal lost".to_string()});
+        records
+    }
+    #[test]
+    fn test_formats() {
+        let records = test_session();
+        for format in [CaptureFormat::JsonLines, CaptureFormat::Binary] {
+            let mut writer = CaptureWriter::new(Vec::new(), format).unwrap();
+            for record in &records {
+                writer.write(record).unwrap();
+            }
+            let capture = writer.into_inner();
+            let reader = CaptureReader::new(capture.as_slice()).unwrap();
+            assert!(reader.format() == format);
+            let read_records: Vec<Record> = reader.map(Result::unwrap).collect();
+            assert!(read_records == records);
+        }
+    }
+    #[test]
+    fn test_json_lines() {
+        let record = Record::Frame {marker: Duration::from_secs(60), frame: "0".repeat(59).parse().unwrap()};
+        let line = record_to_json(&record);
+        assert!(line == format!("{{\"type\":\"frame\",\"time_us\":60000000,\"bits\":\"{}\"}}", "0".repeat(59)));
+        assert!(record_from_json(&line).unwrap() == record);
+        let record = record_from_json(" { \"type\" : \"bit\", \"value\":\"?\", \"time_us\": 5 } ").unwrap();
+        assert!(record == Record::Bit {time: Duration::from_micros(5), value: None});
+        let record = record_from_json("{\"type\":\"metadata\",\"key\":\"\\u00e9\",\"value\":\"\"}").unwrap();
+        assert!(record == Record::Metadata {key: "é".to_string(), value: String::new()});
+        assert!(record_from_json("{\"type\":\"edge\",\"time_us\":5}").is_err());
+        assert!(record_from_json("{\"type\":\"bit\",\"time_us\":5,\"value\":\"2\"}").is_err());
+        assert!(record_from_json("{\"type\":\"unknown\"}").is_err());
+        assert!(record_from_json("{\"type\":\"error\"").is_err());
+        assert!(record_from_json("[]").is_err());
+    }
+    #[test]
+    fn test_invalid_binary() {
+        assert!(CaptureReader::new(&b"DCF77CAP\x02"[..]).is_err());
+        let mut reader = CaptureReader::new(&b"DCF77CAP\x01\x09\x00"[..]).unwrap();
+        assert!(reader.next().unwrap().is_err());
+        let mut reader = CaptureReader::new(&b"DCF77CAP\x01\x06\x00\x01"[..]).unwrap();
+        assert!(reader.next().unwrap().is_err());
+        let mut reader = CaptureReader::new(&b"DCF77CAP\x01\x03\x01"[..]).unwrap();
+        assert!(reader.next().unwrap().is_err());
+    }
+    #[test]
+    fn test_replay() {
+        let records = test_session();
+        let summary = replay(records.iter().cloned().map(Ok), &mut Receiver::new(), ReplaySpeed::Unpaced).unwrap();
+        assert!(summary.records == records.len());
+        assert!(summary.metadata.len() == 1);
+        assert!(summary.recorded_frames.len() == 2);
+        assert!(summary.recorded_errors == [(Duration::from_secs(240), "Signal lost".to_string())]);
+        // The receiver did not lose the signal
+        assert!(summary.decoded_frames() == summary.recorded_frames);
+        assert!(!summary.matches_recording());
+        let summary = replay(records[..records.len() - 1].iter().cloned().map(Ok), &mut Receiver::new(), ReplaySpeed::Unpaced).unwrap();
+        assert!(summary.matches_recording());
+        let bits: Vec<Result<Record, Error>> = records.iter()
+            .filter(|record| matches!(record, Record::Bit {..}))
+            .cloned()
+            .map(Ok)
+            .collect();
+        // The bits are only recorded once the receiver found the first minute
+        // marker, so the replay of the bits needs a minute to find it again
+        let summary = replay(bits, &mut Receiver::new(), ReplaySpeed::Unpaced).unwrap();
+        assert!(summary.decoded_frames().len() == 1);
+        assert!(summary.decoded_frames().iter().all(|(_, frame)| u64::try_from(*frame).unwrap() == TEST_FRAME));
+    }
+    #[test]
+    fn test_accelerated_replay() {
+        let records: Vec<Result<Record, Error>> = edges_from_frame(TEST_FRAME, Duration::ZERO, false)[..8]
+            .iter()
+            .map(|edge| Ok(Record::Edge(*edge)))
+            .collect();
+        let started = Instant::now();
+        let summary = replay(records, &mut Receiver::new(), ReplaySpeed::Accelerated(20.0)).unwrap();
+        let elapsed = started.elapsed();
+        assert!(summary.records == 8);
+        assert!(elapsed >= Duration::from_millis(150));
+        assert!(elapsed < Duration::from_secs(3));
+        for factor in [0.0, -1.0, f64::NAN] {
+            let error = replay(Vec::new(), &mut Receiver::new(), ReplaySpeed::Accelerated(factor)).unwrap_err();
+            assert!(error.kind() == ErrorKind::InvalidInput);
+        }
+    }
+}
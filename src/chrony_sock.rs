@@ -0,0 +1,211 @@
+//! Writer for the chrony SOCK reference clock
+//!
+//! chronyd reads the samples of `refclock SOCK` as `struct sock_sample`
+//! datagrams from a Unix datagram socket. Each sample carries the local time
+//! of the measurement, the offset between the true time and the local time, a
+//! pulse flag and the leap status.
+//!
+//! The measurement time of a decoded minute is the local time at which its
+//! minute marker was seen, the true time is the decoded minute translated with
+//! `to_utc` and the leap status is taken from the leap second announcement
+//! bit (A2).
+
+use std::io::Error;
+use std::io::ErrorKind;
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use chrono::prelude::*;
+use crate::{DCF77, to_utc};
+
+/// Magic number that identifies the datagrams of the SOCK protocol
+pub const SOCK_MAGIC: i32 = 0x534F_434B;
+
+/// Leap status: no leap second announced
+pub const LEAP_NORMAL: i32 = 0;
+
+/// Leap status: a leap second will be inserted at the end of the day
+pub const LEAP_INSERT: i32 = 1;
+
+/// Size of a `struct sock_sample` datagram
+pub const SOCK_SAMPLE_SIZE: usize = std::mem::size_of::<RawSockSample>();
+
+/// Layout of `struct sock_sample` as read by chronyd
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct RawSockSample {
+    tv: libc::timeval,
+    offset: f64,
+    pulse: libc::c_int,
+    leap: libc::c_int,
+    pad: libc::c_int,
+    magic: libc::c_int
+}
+
+/// Sample sent to chronyd
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SockSample {
+    /// Local time of the measurement
+    pub time: DateTime<Utc>,
+    /// Offset in seconds between the true time and the local time
+    pub offset: f64,
+    /// The sample comes from a pulse per second signal
+    pub pulse: bool,
+    /// Leap status
+    pub leap: i32
+}
+
+/// Creates the sample of a decoded DCF77 minute
+///
+/// `marker` is the local time at which the minute marker of the decoded minute
+/// was received.
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::*;
+///use dcf77_chrono::chrony_sock::*;
+///let test_time = DCF77 {
+///    date: Utc.with_ymd_and_hms(2023, 2, 1, 13, 34, 0).unwrap(),
+///    antenna: false,
+///    announce_daily_saving_time: false,
+///    daily_saving_time: false,
+///    standard_time: true,
+///    bit_leap_second: false,
+///    third_party: 0
+///};
+///let marker = Utc.with_ymd_and_hms(2023, 2, 1, 12, 34, 0).unwrap() + chrono::Duration::milliseconds(250);
+///let sample = sample_from_dcf77(test_time, marker);
+///assert!(sample.offset == -0.25);
+///assert!(sample.leap == LEAP_NORMAL);
+/// ```
+pub fn sample_from_dcf77(dcf_data: DCF77, marker: DateTime<Utc>) -> SockSample {
+    let offset = to_utc(dcf_data) - marker;
+    SockSample {
+        time: marker,
+        offset: offset.num_nanoseconds().unwrap_or(i64::MAX) as f64 / 1e9,
+        pulse: false,
+        leap: if dcf_data.bit_leap_second { LEAP_INSERT } else { LEAP_NORMAL }
+    }
+}
+
+/// Creates the `struct sock_sample` datagram of a sample
+pub fn to_datagram(sample: SockSample) -> Result<[u8; SOCK_SAMPLE_SIZE], Error> {
+    let tv_sec = libc::time_t::try_from(sample.time.timestamp())
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "Sample time out of range"))?;
+    let raw_sample = RawSockSample {
+        tv: libc::timeval {
+            tv_sec,
+            tv_usec: sample.time.timestamp_subsec_micros() as libc::suseconds_t
+        },
+        offset: sample.offset,
+        pulse: libc::c_int::from(sample.pulse),
+        leap: sample.leap,
+        pad: 0,
+        magic: SOCK_MAGIC
+    };
+    let mut output = [0; SOCK_SAMPLE_SIZE];
+    // SAFETY: RawSockSample is a plain C struct of the same size as the output
+    unsafe {
+        std::ptr::write_unaligned(output.as_mut_ptr() as *mut RawSockSample, raw_sample);
+    }
+    Ok(output)
+}
+
+/// Parses a `struct sock_sample` datagram, as chronyd does
+pub fn from_datagram(input: &[u8]) -> Result<SockSample, Error> {
+    if input.len() != SOCK_SAMPLE_SIZE {
+        let error_payload = format!("Invalid sample size: {} -> {}", input.len(), SOCK_SAMPLE_SIZE);
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    // SAFETY: the input has the size of RawSockSample and every bit pattern is valid for it
+    let raw_sample = unsafe { std::ptr::read_unaligned(input.as_ptr() as *const RawSockSample) };
+    if raw_sample.magic != SOCK_MAGIC {
+        let error_payload = format!("Invalid magic number: 0x{:X}", raw_sample.magic);
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    let time = u32::try_from(raw_sample.tv.tv_usec).ok()
+        .filter(|microseconds| *microseconds < 1_000_000)
+        .and_then(|microseconds| DateTime::from_timestamp(raw_sample.tv.tv_sec as i64, microseconds * 1000))
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Invalid sample time"))?;
+    Ok(SockSample {
+        time,
+        offset: raw_sample.offset,
+        pulse: raw_sample.pulse != 0,
+        leap: raw_sample.leap
+    })
+}
+
+/// Writer sending samples to the socket of a chrony SOCK reference clock
+#[derive(Debug)]
+pub struct SockWriter {
+    socket: UnixDatagram
+}
+
+impl SockWriter {
+    /// Connects to the socket created by chronyd
+    pub fn connect<P: AsRef<Path>>(path: P) -> Result<SockWriter, Error> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(SockWriter {socket})
+    }
+
+    /// Sends a sample
+    pub fn send(&self, sample: SockSample) -> Result<(), Error> {
+        self.socket.send(&to_datagram(sample)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_datagram_layout() {
+        let sample = SockSample {
+            time: Utc.with_ymd_and_hms(2023, 2, 1, 12, 34, 0).unwrap() + chrono::Duration::microseconds(5),
+            offset: 0.5,
+            pulse: true,
+            leap: LEAP_INSERT
+        };
+        let datagram = to_datagram(sample).unwrap();
+        if cfg!(all(target_os = "linux", target_pointer_width = "64")) {
+            assert!(datagram[0..8] == 1_675_254_840_i64.to_ne_bytes());
+            assert!(datagram[8..16] == 5_i64.to_ne_bytes());
+            assert!(datagram[16..24] == 0.5_f64.to_ne_bytes());
+            assert!(datagram[24..28] == 1_i32.to_ne_bytes());
+            assert!(datagram[28..32] == 1_i32.to_ne_bytes());
+            assert!(datagram[36..40] == SOCK_MAGIC.to_ne_bytes());
+        }
+        assert!(from_datagram(&datagram).unwrap() == sample);
+        assert!(from_datagram(&datagram[1..]).is_err());
+        let mut wrong_datagram = datagram;
+        wrong_datagram[SOCK_SAMPLE_SIZE - 1] ^= 0xFF;
+        assert!(from_datagram(&wrong_datagram).is_err());
+    }
+    #[test]
+    fn test_send() {
+        let path = std::env::temp_dir().join(format!("dcf77_chrono_sock_{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let chronyd = UnixDatagram::bind(&path).unwrap();
+        let test_time = DCF77 {
+            date: Utc.with_ymd_and_hms(2016, 12, 31, 23, 0, 0).unwrap(),
+            antenna: false,
+            announce_daily_saving_time: false,
+            daily_saving_time: false,
+            standard_time: true,
+            bit_leap_second: true,
+            third_party: 0
+        };
+        let marker = Utc.with_ymd_and_hms(2016, 12, 31, 21, 59, 59).unwrap() + chrono::Duration::milliseconds(750);
+        let writer = SockWriter::connect(&path).unwrap();
+        writer.send(sample_from_dcf77(test_time, marker)).unwrap();
+        let mut buffer = [0; 2 * SOCK_SAMPLE_SIZE];
+        let received = chronyd.recv(&mut buffer).unwrap();
+        let sample = from_datagram(&buffer[..received]).unwrap();
+        assert!(sample.time == marker);
+        assert!(sample.offset == 0.25);
+        assert!(!sample.pulse);
+        assert!(sample.leap == LEAP_INSERT);
+        let _ = std::fs::remove_file(&path);
+    }
+}
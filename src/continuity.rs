@@ -0,0 +1,315 @@
+//! Minute to minute continuity of decoded DCF77 minutes
+//!
+//! Consecutive minute markers are a whole number of minutes apart, one second
+//! more when a leap second was inserted, and the minutes they end must follow
+//! each other by as much. The `ContinuityChecker` compares every decoded
+//! minute with the previous one and flags what breaks that continuity:
+//! repeated or skipped minutes, jumps of the time, calendar rollovers that do
+//! not happen, and Daylight Saving Time switches or leap seconds that were not
+//! announced during the hour before.
+//!
+//! A single wrong minute is reported once: the minutes that follow may come
+//! back to the time of the minutes before it, or go on from it when the time
+//! really jumped.
+//!
+//! The markers are durations since an arbitrary origin, as given by the
+//! `receiver` module.
+
+use std::time::Duration;
+use chrono::prelude::*;
+use crate::{DCF77, from_dcf77};
+use crate::dst::{DstRules, local_time};
+
+/// Number of minutes whose telegrams carry an announcement before the event
+const ANNOUNCEMENT_MINUTES: i64 = 60;
+
+/// Largest time skip reported as skipped minutes, larger ones being jumps
+pub const MAX_SKIPPED_MINUTES: i64 = 59;
+
+/// Anomaly of a decoded minute
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Anomaly {
+    /// The frame could not be decoded
+    InvalidFrame {
+        /// Description of the problem
+        message: String
+    },
+    /// The time zone bits do not follow the Daylight Saving Time rules
+    TimeZone {
+        /// Description of the problem
+        message: String
+    },
+    /// The minute is the same as the previous one
+    RepeatedMinute {
+        /// UTC instant of the minute
+        time: DateTime<Utc>
+    },
+    /// The time moved forward by a few more minutes than the markers
+    SkippedMinutes {
+        /// UTC instant expected from the markers
+        expected: DateTime<Utc>,
+        /// UTC instant decoded
+        found: DateTime<Utc>,
+        /// Number of minutes skipped
+        count: i64
+    },
+    /// The time moved back or forward by an hour or more against the markers
+    Jump {
+        /// UTC instant expected from the markers
+        expected: DateTime<Utc>,
+        /// UTC instant decoded
+        found: DateTime<Utc>
+    },
+    /// The date did not roll over the way the calendar does
+    Rollover {
+        /// Local time expected from the previous minute
+        expected: NaiveDateTime,
+        /// Local time decoded
+        found: NaiveDateTime
+    },
+    /// The time zone switched without A1 during the hour before
+    UnannouncedSwitch {
+        /// UTC instant of the switch
+        switch: DateTime<Utc>
+    },
+    /// A leap second was inserted without A2 during the hour before
+    UnannouncedLeapSecond {
+        /// UTC instant right after the leap second
+        leap_second: DateTime<Utc>
+    }
+}
+
+/// Checker of the continuity of decoded minutes
+///
+/// # Examples
+/// ```
+///use std::time::Duration;
+///use chrono::prelude::*;
+///use dcf77_chrono::continuity::*;
+///use dcf77_chrono::schedule::*;
+///let from = Utc.with_ymd_and_hms(2023, 6, 14, 8, 0, 0).unwrap();
+///let mut checker = ContinuityChecker::new();
+///let mut anomalies = Vec::new();
+///// The telegram of 08:02 is lost but the markers go on
+///let minutes = Schedule::new(from, from + chrono::Duration::minutes(5)).filter(|minute| minute.utc.minute() != 2);
+///for (index, minute) in minutes.enumerate() {
+///    anomalies.extend(checker.push_frame(minute.frame, Duration::from_secs(60 * index as u64)));
+///}
+///assert!(anomalies.len() == 1);
+///assert!(matches!(anomalies[0], Anomaly::SkippedMinutes {count: 1, ..}));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ContinuityChecker {
+    rules: DstRules,
+    previous: Option<(Duration, DateTime<Utc>, DCF77)>,
+    trusted: Option<(Duration, DateTime<Utc>)>,
+    last_without_a1: Option<DateTime<Utc>>,
+    last_without_a2: Option<DateTime<Utc>>
+}
+
+impl ContinuityChecker {
+    /// Creates a checker following the German Daylight Saving Time rules
+    pub fn new() -> ContinuityChecker {
+        ContinuityChecker::default()
+    }
+
+    /// Replaces the German Daylight Saving Time rules
+    pub fn with_dst_rules(mut self, rules: DstRules) -> ContinuityChecker {
+        self.rules = rules;
+        self
+    }
+
+    /// Forgets the minutes pushed so far
+    pub fn reset(&mut self) {
+        self.previous = None;
+        self.trusted = None;
+        self.last_without_a1 = None;
+        self.last_without_a2 = None;
+    }
+
+    /// Pushes the frame of a minute together with the time of the marker that starts it
+    pub fn push_frame(&mut self, input: u64, marker: Duration) -> Vec<Anomaly> {
+        match from_dcf77(input) {
+            Ok(dcf_data) => self.push(dcf_data, marker),
+            Err(error) => vec![Anomaly::InvalidFrame {message: error.to_string()}]
+        }
+    }
+
+    /// Pushes a decoded minute together with the time of the marker that starts it
+    ///
+    /// Markers must be pushed in order. A minute whose time cannot be resolved
+    /// is reported and otherwise ignored.
+    pub fn push(&mut self, dcf_data: DCF77, marker: Duration) -> Vec<Anomaly> {
+        let mut anomalies = Vec::new();
+        if let Err(error) = self.rules.check(dcf_data) {
+            anomalies.push(Anomaly::TimeZone {message: error.to_string()});
+        }
+        let found = match self.rules.resolve(dcf_data) {
+            Ok(found) => found,
+            Err(error) => {
+                anomalies.push(Anomaly::InvalidFrame {message: error.to_string()});
+                return anomalies;
+            }
+        };
+        let mut leap_second = false;
+        let mut continuous = true;
+        if let Some((previous_marker, previous, previous_data)) = self.previous {
+            let (expected, leap) = expected_time(previous_marker, previous, marker);
+            leap_second = leap;
+            let back = self.trusted.is_some_and(|(trusted_marker, trusted)| expected_time(trusted_marker, trusted, marker).0 == found);
+            if found != expected && !back {
+                continuous = false;
+                anomalies.push(compare(previous, previous_data, expected, found, dcf_data));
+            }
+            if dcf_data.daily_saving_time != previous_data.daily_saving_time
+                && !is_announced(dcf_data.announce_daily_saving_time, self.last_without_a1, found) {
+                anomalies.push(Anomaly::UnannouncedSwitch {switch: found});
+            }
+        }
+        if leap_second && !is_announced(dcf_data.bit_leap_second, self.last_without_a2, found) {
+            anomalies.push(Anomaly::UnannouncedLeapSecond {leap_second: found});
+        }
+        if !dcf_data.announce_daily_saving_time {
+            self.last_without_a1 = Some(found);
+        }
+        if !dcf_data.bit_leap_second {
+            self.last_without_a2 = Some(found);
+        }
+        self.previous = Some((marker, found, dcf_data));
+        if continuous {
+            self.trusted = Some((marker, found));
+        }
+        anomalies
+    }
+}
+
+/// Time expected at a marker from an earlier minute, with whether a leap second was inserted in between
+fn expected_time(previous_marker: Duration, previous: DateTime<Utc>, marker: Duration) -> (DateTime<Utc>, bool) {
+    let elapsed = marker.saturating_sub(previous_marker).as_secs_f64();
+    let minutes = (elapsed / 60.0).round();
+    let leap_second = minutes >= 1.0 && elapsed - minutes * 60.0 >= 0.5;
+    (previous + chrono::Duration::minutes(minutes as i64), leap_second)
+}
+
+/// Classifies a minute that is not the one expected from the previous minute and the markers
+fn compare(previous: DateTime<Utc>, previous_data: DCF77, expected: DateTime<Utc>, found: DateTime<Utc>, dcf_data: DCF77) -> Anomaly {
+    if found == previous {
+        return Anomaly::RepeatedMinute {time: found};
+    }
+    // The local time expected, the offset being the one decoded
+    let offset = chrono::Duration::seconds(crate::utc_offset(dcf_data).local_minus_utc().into());
+    let expected_local = expected.naive_utc() + offset;
+    let found_local = local_time(dcf_data);
+    let previous_local = local_time(previous_data);
+    if found_local.time() == expected_local.time()
+        && found_local.date() != expected_local.date()
+        && expected_local.date() != previous_local.date() {
+        return Anomaly::Rollover {expected: expected_local, found: found_local};
+    }
+    let skipped = (found - expected).num_minutes();
+    if found > expected && skipped <= MAX_SKIPPED_MINUTES {
+        Anomaly::SkippedMinutes {expected, found, count: skipped}
+    } else {
+        Anomaly::Jump {expected, found}
+    }
+}
+
+/// Whether an event at `event` was announced by all the minutes seen during the hour before
+///
+/// The minute of the event carries the announcement too.
+fn is_announced(announcement: bool, last_without: Option<DateTime<Utc>>, event: DateTime<Utc>) -> bool {
+    announcement && last_without.map_or(true, |last| event - last >= chrono::Duration::minutes(ANNOUNCEMENT_MINUTES))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::Schedule;
+    /// Minutes of a schedule with the markers of their broadcast
+    fn broadcast(from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<(DCF77, Duration)> {
+        let mut marker = Duration::ZERO;
+        Schedule::new(from, to).map(|minute| {
+            if minute.leap_second {
+                marker += Duration::from_secs(1);
+            }
+            let item = (minute.dcf, marker);
+            marker += Duration::from_secs(60);
+            item
+        }).collect()
+    }
+    fn check(minutes: &[(DCF77, Duration)]) -> Vec<Anomaly> {
+        let mut checker = ContinuityChecker::new();
+        minutes.iter().flat_map(|(dcf_data, marker)| checker.push(*dcf_data, *marker)).collect()
+    }
+    #[test]
+    fn test_broadcast() {
+        // A leap second and a year rollover, then both switches of 2023
+        let minutes = broadcast(Utc.with_ymd_and_hms(2016, 12, 31, 22, 30, 0).unwrap(), Utc.with_ymd_and_hms(2017, 1, 1, 0, 30, 0).unwrap());
+        assert!(check(&minutes).is_empty());
+        for from in [Utc.with_ymd_and_hms(2023, 3, 25, 23, 0, 0).unwrap(), Utc.with_ymd_and_hms(2023, 10, 28, 23, 0, 0).unwrap()] {
+            let switch = broadcast(from, from + chrono::Duration::hours(3));
+            assert!(check(&switch).is_empty());
+        }
+    }
+    #[test]
+    fn test_skipped_and_repeated() {
+        let from = Utc.with_ymd_and_hms(2023, 6, 14, 8, 0, 0).unwrap();
+        let mut minutes = broadcast(from, from + chrono::Duration::minutes(10));
+        // 08:03 UTC repeats 08:02, 08:06 is 08:09 and 08:08 is a day early
+        minutes[3].0 = minutes[2].0;
+        minutes[6].0 = minutes[9].0;
+        minutes[8].0.date -= chrono::Duration::days(1);
+        // A lost minute does not break the continuity
+        minutes.remove(4);
+        let anomalies = check(&minutes);
+        assert!(anomalies.len() == 3);
+        assert!(anomalies[0] == Anomaly::RepeatedMinute {time: from + chrono::Duration::minutes(2)});
+        assert!(anomalies[1] == Anomaly::SkippedMinutes {
+            expected: from + chrono::Duration::minutes(6),
+            found: from + chrono::Duration::minutes(9),
+            count: 3
+        });
+        assert!(matches!(anomalies[2], Anomaly::Jump {..}));
+        // A time that really jumped is reported once
+        let mut jumped = broadcast(from, from + chrono::Duration::minutes(10));
+        for (dcf_data, _) in &mut jumped[4..] {
+            dcf_data.date += chrono::Duration::hours(3);
+        }
+        assert!(check(&jumped) == [Anomaly::Jump {
+            expected: from + chrono::Duration::minutes(4),
+            found: from + chrono::Duration::minutes(184)
+        }]);
+    }
+    #[test]
+    fn test_rollover() {
+        let minutes = broadcast(Utc.with_ymd_and_hms(2023, 12, 31, 22, 58, 0).unwrap(), Utc.with_ymd_and_hms(2023, 12, 31, 23, 2, 0).unwrap());
+        let mut wrong = minutes.clone();
+        // The year stays 2023 after midnight
+        for (dcf_data, _) in &mut wrong[2..] {
+            dcf_data.date = dcf_data.date.with_year(23).unwrap();
+        }
+        assert!(check(&minutes).is_empty());
+        assert!(check(&wrong) == [Anomaly::Rollover {
+            expected: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+            found: NaiveDate::from_ymd_opt(2023, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap()
+        }]);
+    }
+    #[test]
+    fn test_unannounced() {
+        let from = Utc.with_ymd_and_hms(2023, 10, 29, 0, 30, 0).unwrap();
+        let mut minutes = broadcast(from, from + chrono::Duration::hours(1));
+        minutes[10].0.announce_daily_saving_time = false;
+        let anomalies = check(&minutes);
+        assert!(anomalies == [Anomaly::UnannouncedSwitch {switch: Utc.with_ymd_and_hms(2023, 10, 29, 1, 0, 0).unwrap()}]);
+        let from = Utc.with_ymd_and_hms(2016, 12, 31, 23, 50, 0).unwrap();
+        let mut minutes = broadcast(from, from + chrono::Duration::minutes(20));
+        for (dcf_data, _) in &mut minutes {
+            dcf_data.bit_leap_second = false;
+        }
+        let anomalies = check(&minutes);
+        assert!(anomalies == [Anomaly::UnannouncedLeapSecond {leap_second: Utc.with_ymd_and_hms(2017, 1, 1, 0, 0, 0).unwrap()}]);
+        // A leap second announced from the first minute seen
+        let anomalies = check(&broadcast(from, from + chrono::Duration::minutes(20)));
+        assert!(anomalies.is_empty());
+    }
+}
@@ -92,14 +92,17 @@ const YEAR_POSITION: u8 = 2;
 /// Maximum value for the year in a DCF77 bit field
 const MAX_YEAR: u32 = 165;
 
-/// Position of the parity bit for the years in the DCF77 bit field
-const PARITY_YEAR_BIT_MASK: u64 = 1 << 1;
+/// Position of the parity bit for the date in the DCF77 bit field
+const PARITY_DATE_BIT_MASK: u64 = 1 << 1;
+
+/// Bits of the day, day of the week, month and year covered by the date parity
+const DATE_BITS_MASK: u64 = 0xFF_FFFC;
 
 /// Codes a given year [0..) into DCF77 bit field
 pub fn code_year(input: i32) -> Result<u64, Error> {
     let section = SectionInBitfield {data_bit_mask: YEAR_BIT_MASK,
                                      data_position: YEAR_POSITION,
-                                     parity_mask: PARITY_YEAR_BIT_MASK,
+                                     parity_mask: 0,
                                      max_data: MAX_YEAR};
     code_dcf77(input.try_into().unwrap(), section)
 }
@@ -108,13 +111,40 @@ pub fn code_year(input: i32) -> Result<u64, Error> {
 pub fn process_year(input: u64) -> Result<u32, Error> {
     let section = SectionInBitfield {data_bit_mask: YEAR_BIT_MASK,
                                      data_position: YEAR_POSITION,
-                                     parity_mask: PARITY_YEAR_BIT_MASK,
+                                     parity_mask: 0,
                                      max_data: MAX_YEAR};
     decode_dcf77(input, section)
 }
 
+/// Codes the date parity for the day, day of the week, month and year already in the bit field
+pub fn code_date_parity(input: u64) -> u64 {
+    if proof_parity(input & DATE_BITS_MASK) {
+        0
+    } else {
+        PARITY_DATE_BIT_MASK
+    }
+}
+
+/// Checks the date parity that covers the day, day of the week, month and year
+pub fn check_date_parity(input: u64) -> Result<(), Error> {
+    if proof_parity(input & (DATE_BITS_MASK | PARITY_DATE_BIT_MASK)) {
+        Ok(())
+    } else {
+        let error_payload = format!("Invalid date parity - Input: 0x{:X} Mask: 0x{:X} Parity Mask: 0x{:X}", input, DATE_BITS_MASK, PARITY_DATE_BIT_MASK);
+        Err(Error::new(ErrorKind::InvalidData, error_payload))
+    }
+}
+
+/// Replaces the day of the week of a DCF77 bit field and codes the date parity again
+pub fn replace_day_of_week(input: u64, day_of_week: u8) -> Result<u64, Error> {
+    let coded_day_of_week = code_day_of_the_week(day_of_week)?;
+    let output = (input & !(DAY_OF_WEEK_BIT_MASK << DAY_OF_WEEK_POSITION) & !PARITY_DATE_BIT_MASK) | coded_day_of_week;
+    Ok(output | code_date_parity(output))
+}
+
 #[cfg(test)]
 mod tests {
+    use super::check_date_parity;
     use crate::to_dcf77;
     use crate::from_dcf77;
     use crate::DCF77;
@@ -123,12 +153,13 @@ mod tests {
     fn test_process_day_of_week() {
         for fake_input in 1..=7 {
             let test_date = DCF77 {
-                date: Utc.ymd(21, 11, fake_input).and_hms(10, 10, 0),
+                date: Utc.with_ymd_and_hms(21, 11, fake_input, 10, 10, 0).unwrap(),
                 antenna: false,
                 announce_daily_saving_time: false,
                 daily_saving_time: false,
                 standard_time: false,
-                bit_leap_second: false
+                bit_leap_second: false,
+                third_party: 0
             };
             match to_dcf77(test_date) {
                 Ok(coded_day_of_week) => {
@@ -151,12 +182,13 @@ mod tests {
     fn test_process_day() {
         for fake_input in 1..=30 {
             let test_date = DCF77 {
-                date: Utc.ymd(21, 11, fake_input).and_hms(10, 10, 0),
+                date: Utc.with_ymd_and_hms(21, 11, fake_input, 10, 10, 0).unwrap(),
                 antenna: false,
                 announce_daily_saving_time: false,
                 daily_saving_time: false,
                 standard_time: false,
-                bit_leap_second: false
+                bit_leap_second: false,
+                third_party: 0
             };
             match to_dcf77(test_date) {
                 Ok(coded_date) => {
@@ -179,12 +211,13 @@ mod tests {
     fn test_process_month() {
         for fake_input in 1..=12 {
             let test_date = DCF77 {
-                date: Utc.ymd(21, fake_input, 12).and_hms(21, 10, 0),
+                date: Utc.with_ymd_and_hms(21, fake_input, 12, 21, 10, 0).unwrap(),
                 antenna: false,
                 announce_daily_saving_time: false,
                 daily_saving_time: false,
                 standard_time: false,
-                bit_leap_second: false
+                bit_leap_second: false,
+                third_party: 0
             };
             match to_dcf77(test_date) {
                 Ok(coded_date) => {
@@ -207,12 +240,13 @@ mod tests {
     fn test_process_year() {
         for fake_input in 0..=0xfff {
             let test_date = DCF77 {
-                date: Utc.ymd(i32::from(fake_input), 10, 10).and_hms(21, 10, 0),
+                date: Utc.with_ymd_and_hms(fake_input, 10, 10, 21, 10, 0).unwrap(),
                 antenna: false,
                 announce_daily_saving_time: false,
                 daily_saving_time: false,
                 standard_time: false,
-                bit_leap_second: false
+                bit_leap_second: false,
+                third_party: 0
             };
             match to_dcf77(test_date) {
                 Ok(coded_date) => {
@@ -231,4 +265,24 @@ mod tests {
             }
         }
     }
+    #[test]
+    fn test_date_parity() {
+        let test_date = DCF77 {
+            date: Utc.with_ymd_and_hms(23, 10, 29, 2, 59, 0).unwrap(),
+            antenna: false,
+            announce_daily_saving_time: false,
+            daily_saving_time: false,
+            standard_time: true,
+            bit_leap_second: false,
+            third_party: 0
+        };
+        let coded_date = to_dcf77(test_date).unwrap();
+        assert!(check_date_parity(coded_date).is_ok());
+        // Every bit of the seconds 36 to 58 is covered by the date parity
+        for bit in 1..24 {
+            assert!(check_date_parity(coded_date ^ (1 << bit)).is_err());
+            assert!(from_dcf77(coded_date ^ (1 << bit)).is_err());
+        }
+        assert!(check_date_parity(coded_date ^ (1 << 24)).is_ok());
+    }
 }
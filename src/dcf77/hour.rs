@@ -78,7 +78,8 @@ mod tests {
                 announce_daily_saving_time: false,
                 daily_saving_time: false,
                 standard_time: false,
-                bit_leap_second: false
+                bit_leap_second: false,
+                third_party: 0
             };
             match to_dcf77(test_time) {
                 Ok(coded_minutes) => {
@@ -107,7 +108,8 @@ mod tests {
                 announce_daily_saving_time: false,
                 daily_saving_time: false,
                 standard_time: false,
-                bit_leap_second: false
+                bit_leap_second: false,
+                third_party: 0
             };
             match to_dcf77(test_time) {
                 Ok(coded_hour) => {
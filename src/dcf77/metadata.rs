@@ -1,17 +1,20 @@
+use std::io::Error;
+use std::io::ErrorKind;
+
 // As documented: https://www.cyber-sciences.com/wp-content/uploads/2019/01/TN-103_DCF77.pdf
-/// Mask for the Antenna bit (R) in the DCF77 bit field
+/// Mask for the call bit (R), historically the antenna bit, in the DCF77 bit field
 const ANTENNA_BIT_MASK: u64 = 1<<44;
 
-/// Codes a given antenna bit into the DCF77 bit field
+/// Codes a given call bit into the DCF77 bit field
 pub fn code_antenna(input: bool) -> u64 {
     if input {
         ANTENNA_BIT_MASK
     } else {
-        0 as u64
+        0
     }
 }
 
-/// Extracts the antenna bit out of a dcf77 bitfield
+/// Extracts the call bit out of a dcf77 bitfield
 pub fn process_antenna(input: u64) -> bool {
     0 < input & ANTENNA_BIT_MASK
 }
@@ -24,7 +27,7 @@ pub fn code_announce_daylight_saving_switching(input: bool) -> u64 {
     if input {
         ANNOUNCE_DAYLIGHT_SAVING_SWITCHING
     } else {
-        0 as u64
+        0
     }
 }
 
@@ -41,7 +44,7 @@ pub fn code_daylight_saving(input: bool) -> u64 {
     if input {
         ANNOUNCE_DAYLIGHT_SAVING
     } else {
-        0 as u64
+        0
     }
 }
 
@@ -58,7 +61,7 @@ pub fn code_standard_time(input: bool) -> u64 {
     if input {
         ANNOUNCE_STANDARD_TIME
     } else {
-        0 as u64
+        0
     }
 }
 
@@ -75,7 +78,7 @@ pub fn code_bit_leap_second(input: bool) -> u64 {
     if input {
         ANNOUNCE_BIT_LEAP_FOR_SECOND
     } else {
-        0 as u64
+        0
     }
 }
 
@@ -84,6 +87,45 @@ pub fn process_bit_leap_second(input: u64) -> bool {
     0 < input & ANNOUNCE_BIT_LEAP_FOR_SECOND
 }
 
+/// Mask for the start of encoded time bit (S) in the DCF77 bit field, always 1
+const START_OF_TIME: u64 = 1<<39;
+
+/// Codes the start of encoded time bit into the DCF77 bit field
+pub fn code_start_of_time() -> u64 {
+    START_OF_TIME
+}
+
+/// Extracts the start of encoded time bit out of a dcf77 bitfield
+pub fn process_start_of_time(input: u64) -> bool {
+    0 < input & START_OF_TIME
+}
+
+/// Mask for the third party bits of the seconds 1 to 14 in the DCF77 bit field
+const THIRD_PARTY_BIT_MASK: u64 = 0x3FFF;
+
+/// Position of the third party bits in the DCF77 bit field
+const THIRD_PARTY_POSITION: u8 = 45;
+
+/// Codes the third party bits [0..0x3FFF] into the DCF77 bit field
+pub fn code_third_party(input: u16) -> Result<u64, Error> {
+    if u64::from(input) > THIRD_PARTY_BIT_MASK {
+        let error_payload = format!("Above max! : 0x{:X} -> 0x{:X}", input, THIRD_PARTY_BIT_MASK);
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    Ok(u64::from(input) << THIRD_PARTY_POSITION)
+}
+
+/// Extracts the third party bits out of a dcf77 bitfield
+pub fn process_third_party(input: u64) -> u16 {
+    ((input >> THIRD_PARTY_POSITION) & THIRD_PARTY_BIT_MASK) as u16
+}
+
+/// Replaces the third party bits of a DCF77 bit field
+pub fn replace_third_party(input: u64, third_party: u16) -> Result<u64, Error> {
+    let coded_third_party = code_third_party(third_party)?;
+    Ok((input & !(THIRD_PARTY_BIT_MASK << THIRD_PARTY_POSITION)) | coded_third_party)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -91,74 +133,97 @@ mod tests {
     fn test_antenna() {
         let mut dcf77_bitfield = 0x0;
         dcf77_bitfield |= code_antenna(false);
-        assert!(false == process_antenna(dcf77_bitfield));
-        assert!(false == process_daylight_saving(dcf77_bitfield));
-        assert!(false == process_standard_time(dcf77_bitfield));
-        assert!(false == process_bit_leap_second(dcf77_bitfield));
+        assert!(!process_antenna(dcf77_bitfield));
+        assert!(!process_daylight_saving(dcf77_bitfield));
+        assert!(!process_standard_time(dcf77_bitfield));
+        assert!(!process_bit_leap_second(dcf77_bitfield));
         dcf77_bitfield |= code_antenna(true);
-        assert!(true == process_antenna(dcf77_bitfield));
-        assert!(false == process_daylight_saving(dcf77_bitfield));
-        assert!(false == process_standard_time(dcf77_bitfield));
-        assert!(false == process_bit_leap_second(dcf77_bitfield));
+        assert!(process_antenna(dcf77_bitfield));
+        assert!(!process_daylight_saving(dcf77_bitfield));
+        assert!(!process_standard_time(dcf77_bitfield));
+        assert!(!process_bit_leap_second(dcf77_bitfield));
     }
     #[test]
     fn test_announce_daylight_saving_switching() {
         let mut dcf77_bitfield = 0x0;
         dcf77_bitfield |= code_announce_daylight_saving_switching(false);
-        assert!(false == process_antenna(dcf77_bitfield));
-        assert!(false == process_announce_daylight_saving_switch(dcf77_bitfield));
-        assert!(false == process_daylight_saving(dcf77_bitfield));
-        assert!(false == process_standard_time(dcf77_bitfield));
-        assert!(false == process_bit_leap_second(dcf77_bitfield));
+        assert!(!process_antenna(dcf77_bitfield));
+        assert!(!process_announce_daylight_saving_switch(dcf77_bitfield));
+        assert!(!process_daylight_saving(dcf77_bitfield));
+        assert!(!process_standard_time(dcf77_bitfield));
+        assert!(!process_bit_leap_second(dcf77_bitfield));
         dcf77_bitfield |= code_announce_daylight_saving_switching(true);
-        assert!(false == process_antenna(dcf77_bitfield));
-        assert!(true == process_announce_daylight_saving_switch(dcf77_bitfield));
-        assert!(false == process_daylight_saving(dcf77_bitfield));
-        assert!(false == process_standard_time(dcf77_bitfield));
-        assert!(false == process_bit_leap_second(dcf77_bitfield));
+        assert!(!process_antenna(dcf77_bitfield));
+        assert!(process_announce_daylight_saving_switch(dcf77_bitfield));
+        assert!(!process_daylight_saving(dcf77_bitfield));
+        assert!(!process_standard_time(dcf77_bitfield));
+        assert!(!process_bit_leap_second(dcf77_bitfield));
     }
     #[test]
     fn test_process_daylight_saving() {
         let mut dcf77_bitfield = 0x0;
         dcf77_bitfield |= code_daylight_saving(false);
-        assert!(false == process_antenna(dcf77_bitfield));
-        assert!(false == process_announce_daylight_saving_switch(dcf77_bitfield));
-        assert!(false == process_daylight_saving(dcf77_bitfield));
-        assert!(false == process_standard_time(dcf77_bitfield));
-        assert!(false == process_bit_leap_second(dcf77_bitfield));
+        assert!(!process_antenna(dcf77_bitfield));
+        assert!(!process_announce_daylight_saving_switch(dcf77_bitfield));
+        assert!(!process_daylight_saving(dcf77_bitfield));
+        assert!(!process_standard_time(dcf77_bitfield));
+        assert!(!process_bit_leap_second(dcf77_bitfield));
         dcf77_bitfield |= code_daylight_saving(true);
-        assert!(false == process_antenna(dcf77_bitfield));
-        assert!(false == process_announce_daylight_saving_switch(dcf77_bitfield));
-        assert!(true == process_daylight_saving(dcf77_bitfield));
-        assert!(false == process_standard_time(dcf77_bitfield));
-        assert!(false == process_bit_leap_second(dcf77_bitfield));
+        assert!(!process_antenna(dcf77_bitfield));
+        assert!(!process_announce_daylight_saving_switch(dcf77_bitfield));
+        assert!(process_daylight_saving(dcf77_bitfield));
+        assert!(!process_standard_time(dcf77_bitfield));
+        assert!(!process_bit_leap_second(dcf77_bitfield));
     }
     #[test]
     fn test_standard_time() {
         let mut dcf77_bitfield = 0x0;
         dcf77_bitfield |= code_standard_time(false);
-        assert!(false == process_antenna(dcf77_bitfield));
-        assert!(false == process_standard_time(dcf77_bitfield));
-        assert!(false == process_daylight_saving(dcf77_bitfield));
-        assert!(false == process_bit_leap_second(dcf77_bitfield));
+        assert!(!process_antenna(dcf77_bitfield));
+        assert!(!process_standard_time(dcf77_bitfield));
+        assert!(!process_daylight_saving(dcf77_bitfield));
+        assert!(!process_bit_leap_second(dcf77_bitfield));
         dcf77_bitfield |= code_standard_time(true);
-        assert!(true == process_standard_time(dcf77_bitfield));
-        assert!(false == process_antenna(dcf77_bitfield));
-        assert!(false == process_daylight_saving(dcf77_bitfield));
-        assert!(false == process_bit_leap_second(dcf77_bitfield));
+        assert!(process_standard_time(dcf77_bitfield));
+        assert!(!process_antenna(dcf77_bitfield));
+        assert!(!process_daylight_saving(dcf77_bitfield));
+        assert!(!process_bit_leap_second(dcf77_bitfield));
     }
     #[test]
     fn test_bit_leap_second() {
         let mut dcf77_bitfield = 0x0;
         dcf77_bitfield |= code_bit_leap_second(false);
-        assert!(false == process_antenna(dcf77_bitfield));
-        assert!(false == process_bit_leap_second(dcf77_bitfield));
-        assert!(false == process_daylight_saving(dcf77_bitfield));
-        assert!(false == process_standard_time(dcf77_bitfield));
+        assert!(!process_antenna(dcf77_bitfield));
+        assert!(!process_bit_leap_second(dcf77_bitfield));
+        assert!(!process_daylight_saving(dcf77_bitfield));
+        assert!(!process_standard_time(dcf77_bitfield));
         dcf77_bitfield |= code_bit_leap_second(true);
-        assert!(true == process_bit_leap_second(dcf77_bitfield));
-        assert!(false == process_antenna(dcf77_bitfield));
-        assert!(false == process_standard_time(dcf77_bitfield));
-        assert!(false == process_daylight_saving(dcf77_bitfield));
+        assert!(process_bit_leap_second(dcf77_bitfield));
+        assert!(!process_antenna(dcf77_bitfield));
+        assert!(!process_standard_time(dcf77_bitfield));
+        assert!(!process_daylight_saving(dcf77_bitfield));
+    }
+    #[test]
+    fn test_start_of_time() {
+        let mut dcf77_bitfield = 0x0;
+        assert!(!process_start_of_time(dcf77_bitfield));
+        dcf77_bitfield |= code_start_of_time();
+        assert!(process_start_of_time(dcf77_bitfield));
+        assert!(!process_antenna(dcf77_bitfield));
+        assert!(!process_announce_daylight_saving_switch(dcf77_bitfield));
+        assert!(!process_daylight_saving(dcf77_bitfield));
+        assert!(!process_standard_time(dcf77_bitfield));
+        assert!(!process_bit_leap_second(dcf77_bitfield));
+    }
+    #[test]
+    fn test_third_party() {
+        for fake_input in 0..=0x3FFF {
+            let dcf77_bitfield = code_third_party(fake_input).unwrap();
+            assert!(dcf77_bitfield & !0x07FF_E000_0000_0000 == 0);
+            assert!(process_third_party(dcf77_bitfield) == fake_input);
+        }
+        assert!(code_third_party(0x2000).unwrap() == 1 << 58);
+        assert!(code_third_party(0x4000).is_err());
+        assert!(process_third_party(u64::MAX) == 0x3FFF);
     }
 }
@@ -18,7 +18,7 @@ pub struct SectionInBitfield {
 
 /// Checks the parity (even) of an input to the given parity
 fn proof_parity(input: u64) -> bool {
-    input.count_ones()%2 == 0
+    input.count_ones() % 2 == 0
 }
 
 /// Computes the value of an input bitfield with the BIT WEIGHTS of a DCF77 bitfield
@@ -52,7 +52,7 @@ pub fn code_dcf77(input: u32, section: SectionInBitfield) -> Result<u64, Error>
     let mut coded_value:u64;
     if section.max_data >= input {
         coded_value = create_pulse(input, section.data_bit_mask).into();
-        coded_value = coded_value << u32::from(section.data_position);
+        coded_value <<= u32::from(section.data_position);
         if 0 < section.parity_mask && !proof_parity(coded_value) {
             coded_value |= section.parity_mask;
         }
@@ -101,11 +101,11 @@ mod tests {
             section.data_bit_mask |= 1 << number_bits;
             section.max_data = BIT_WEIGHTS[0..=number_bits].iter().sum();
             for value in 0..=section.max_data {
-                match code_dcf77(value.into(), section) {
+                match code_dcf77(value, section) {
                     Ok(coded_value) => {
                         match decode_dcf77(coded_value, section) {
                             Ok(output) => {
-                                assert!(u32::from(value) == output)
+                                assert!(value == output)
                             }
                             Err(data) => {
                                 println!("{:?}", data)
@@ -0,0 +1,454 @@
+//! Rules of the Daylight Saving Time broadcast by DCF77
+//!
+//! Germany switches between CET and CEST on Sundays, always at 01:00 UTC.
+//! The days changed over time:
+//! - before 1980 the time stays CET (the older summer times predate the
+//!   DCF77 time code)
+//! - in 1980 CEST starts on the first Sunday of April
+//! - from 1981 CEST starts on the last Sunday of March
+//! - until 1995 CEST ends on the last Sunday of September
+//! - since 1996 it ends on the last Sunday of October, as in the whole EU
+//!
+//! `DstRules` holds those rules as eras, each one applying from a year on,
+//! and can be extended with future rules such as an abolition of the
+//! switches. The schedule encodes minutes with it, and `DstRules::check`
+//! tells whether a decoded minute agrees with it.
+//!
+//! At the switch to CET the local hour from 02:00 to 02:59 happens twice, and
+//! at the switch to CEST it does not happen at all. `DstRules::resolve` gives
+//! the UTC instant of a decoded minute from Z1 and Z2, falling back on A1 in
+//! the repeated hour, and rejects the skipped hour.
+
+use std::io::Error;
+use std::io::ErrorKind;
+use chrono::prelude::*;
+use crate::{DCF77, to_utc};
+use crate::schedule::is_announced;
+
+/// Hour (UTC) of the switches since 1980
+pub const SWITCH_HOUR: u32 = 1;
+
+/// Rule of the switches of the years of an era
+///
+/// A switch is given as (month, day) and happens on the last Sunday on or
+/// before that day: (3, 31) is the last Sunday of March and (4, 7) the first
+/// Sunday of April.
+///
+/// # Examples
+/// ```
+///use dcf77_chrono::dst::*;
+///let rule = YearRule::new(Some((3, 31)), Some((10, 31)), SWITCH_HOUR).unwrap();
+///assert!(rule == EU_RULE);
+///assert!(YearRule::new(Some((2, 29)), None, SWITCH_HOUR).is_err());
+///assert!(YearRule::new(None, Some((10, 31)), 24).is_err());
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct YearRule {
+    start: Option<(u32, u32)>,
+    end: Option<(u32, u32)>,
+    hour: u32
+}
+
+impl YearRule {
+    /// Creates a rule out of its switches, given as (month, day), and their hour (UTC)
+    ///
+    /// The days must exist every year, which leaves out the 29th of February,
+    /// and the hour must be below 24.
+    pub fn new(start: Option<(u32, u32)>, end: Option<(u32, u32)>, hour: u32) -> Result<YearRule, Error> {
+        let exists = |switch: Option<(u32, u32)>| switch.map_or(true, |(month, day)| NaiveDate::from_ymd_opt(2001, month, day).is_some());
+        if !exists(start) || !exists(end) || hour >= 24 {
+            let error_payload = format!("Invalid Daylight Saving Time rule - Start: {:?} End: {:?} Hour: {}", start, end, hour);
+            return Err(Error::new(ErrorKind::InvalidData, error_payload));
+        }
+        Ok(YearRule {start, end, hour})
+    }
+
+    /// Switch that starts CEST, `None` when it does not start
+    pub fn start(&self) -> Option<(u32, u32)> {
+        self.start
+    }
+
+    /// Switch that ends CEST, `None` when it does not end
+    pub fn end(&self) -> Option<(u32, u32)> {
+        self.end
+    }
+
+    /// Hour (UTC) of the switches
+    pub fn hour(&self) -> u32 {
+        self.hour
+    }
+}
+
+/// Rule of the EU since 1996
+pub const EU_RULE: YearRule = YearRule {start: Some((3, 31)), end: Some((10, 31)), hour: SWITCH_HOUR};
+
+/// Rule of Germany in 1980
+pub const GERMAN_1980_RULE: YearRule = YearRule {start: Some((4, 7)), end: Some((9, 30)), hour: SWITCH_HOUR};
+
+/// Rule of Germany from 1981 to 1995
+pub const GERMAN_1981_RULE: YearRule = YearRule {start: Some((3, 31)), end: Some((9, 30)), hour: SWITCH_HOUR};
+
+/// Years without switch, keeping the time of the end of the previous year
+pub const NO_SWITCH: YearRule = YearRule {start: None, end: None, hour: SWITCH_HOUR};
+
+/// Last Sunday on or before a day, `None` out of the range of chrono
+fn sunday_on_or_before(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    let date = NaiveDate::from_ymd_opt(year, month, day)?;
+    date.checked_sub_days(chrono::Days::new(date.weekday().num_days_from_sunday().into()))
+}
+
+/// German legal time of a DCF77 struct, the years below 100 being taken as 20xx
+pub(crate) fn local_time(dcf_data: DCF77) -> NaiveDateTime {
+    let date = dcf_data.date.naive_utc();
+    if date.year() < 100 {
+        date.with_year(date.year() + 2000).unwrap()
+    } else {
+        date
+    }
+}
+
+/// Daylight Saving Time rules, as eras of years
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::dst::*;
+///let rules = DstRules::german().with_abolition(2030, false);
+///let (start, end) = rules.switches(2023);
+///assert!(start == Some(Utc.with_ymd_and_hms(2023, 3, 26, 1, 0, 0).unwrap()));
+///assert!(end == Some(Utc.with_ymd_and_hms(2023, 10, 29, 1, 0, 0).unwrap()));
+///assert!(rules.switches(1990).1 == Some(Utc.with_ymd_and_hms(1990, 9, 30, 1, 0, 0).unwrap()));
+///assert!(!rules.is_daylight_saving(Utc.with_ymd_and_hms(2031, 7, 1, 12, 0, 0).unwrap()));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DstRules {
+    eras: Vec<(i32, YearRule)>
+}
+
+impl Default for DstRules {
+    fn default() -> DstRules {
+        DstRules::german()
+    }
+}
+
+impl DstRules {
+    /// Rules with the same switches every year
+    pub fn new(rule: YearRule) -> DstRules {
+        DstRules {eras: vec![(i32::MIN, rule)]}
+    }
+
+    /// Rules of Germany: none before 1980, then `GERMAN_1980_RULE`, `GERMAN_1981_RULE` and `EU_RULE` from 1996
+    pub fn german() -> DstRules {
+        DstRules {eras: vec![(i32::MIN, NO_SWITCH), (1980, GERMAN_1980_RULE), (1981, GERMAN_1981_RULE), (1996, EU_RULE)]}
+    }
+
+    /// Applies a rule from a year on, replacing the eras that start at or after it
+    pub fn with_rule(mut self, year: i32, rule: YearRule) -> DstRules {
+        self.eras.retain(|(first_year, _)| *first_year < year);
+        self.eras.push((year, rule));
+        self
+    }
+
+    /// Stops the switches from a year on
+    ///
+    /// With `permanent_summer_time` the start of CEST in `year` is the last
+    /// switch; otherwise the time stays CET from the start of `year`.
+    pub fn with_abolition(self, year: i32, permanent_summer_time: bool) -> DstRules {
+        if permanent_summer_time {
+            let last_rule = YearRule {end: None, ..self.rule(year)};
+            self.with_rule(year, last_rule).with_rule(year + 1, NO_SWITCH)
+        } else {
+            self.with_rule(year, NO_SWITCH)
+        }
+    }
+
+    /// Index of the era of a year
+    fn era(&self, year: i32) -> usize {
+        self.eras.iter().rposition(|(first_year, _)| *first_year <= year).unwrap_or(0)
+    }
+
+    /// Rule of a year
+    pub fn rule(&self, year: i32) -> YearRule {
+        let (first_year, rule) = self.eras[self.era(year)];
+        if first_year <= year { rule } else { NO_SWITCH }
+    }
+
+    /// Start and end of CEST in a year, `None` for the switches that do not happen
+    ///
+    /// There are no switches in the years out of the range of chrono.
+    pub fn switches(&self, year: i32) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+        let rule = self.rule(year);
+        let switch = |day: Option<(u32, u32)>| day
+            .and_then(|(month, day)| sunday_on_or_before(year, month, day))
+            .and_then(|date| date.and_hms_opt(rule.hour, 0, 0))
+            .map(|date| date.and_utc());
+        (switch(rule.start), switch(rule.end))
+    }
+
+    /// Whether CEST is in effect at the start of a year
+    fn is_daylight_saving_at_start(&self, year: i32) -> bool {
+        let mut era = self.era(year - 1);
+        let mut year = year - 1;
+        loop {
+            let (first_year, rule) = self.eras[era];
+            if first_year > year {
+                return false;
+            }
+            match (rule.start, rule.end) {
+                (Some(start), Some(end)) => return start > end,
+                (Some(_), None) => return true,
+                (None, Some(_)) => return false,
+                (None, None) if era == 0 => return false,
+                (None, None) => {
+                    year = first_year - 1;
+                    era -= 1;
+                }
+            }
+        }
+    }
+
+    /// Whether CEST is in effect at a UTC instant
+    pub fn is_daylight_saving(&self, instant: DateTime<Utc>) -> bool {
+        let (start, end) = self.switches(instant.year());
+        let mut switches: Vec<(DateTime<Utc>, bool)> = start.map(|start| (start, true)).into_iter()
+            .chain(end.map(|end| (end, false)))
+            .collect();
+        switches.sort();
+        switches.into_iter()
+            .rev()
+            .find(|(switch, _)| *switch <= instant)
+            .map_or_else(|| self.is_daylight_saving_at_start(instant.year()), |(_, daylight_saving)| daylight_saving)
+    }
+
+    /// Offset of the German legal time at a UTC instant
+    pub fn utc_offset(&self, instant: DateTime<Utc>) -> FixedOffset {
+        if self.is_daylight_saving(instant) {
+            FixedOffset::east_opt(2 * 3600).unwrap()
+        } else {
+            FixedOffset::east_opt(3600).unwrap()
+        }
+    }
+
+    /// First switch after a UTC instant, looking at most into the next year
+    pub fn next_switch(&self, instant: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        (instant.year()..=instant.year() + 1)
+            .flat_map(|year| {
+                let (start, end) = self.switches(year);
+                start.into_iter().chain(end)
+            })
+            .filter(|switch| *switch > instant)
+            .min()
+    }
+
+    /// UTC instants at which the German legal time shows a local time
+    ///
+    /// Each instant comes with whether CEST is in effect at it. There are two
+    /// of them in the hour repeated at the switch to CET and none in the hour
+    /// skipped at the switch to CEST.
+    pub fn instants(&self, local: NaiveDateTime) -> Vec<(DateTime<Utc>, bool)> {
+        [true, false].into_iter()
+            .map(|daylight_saving| {
+                let offset = if daylight_saving { 2 } else { 1 };
+                ((local - chrono::Duration::hours(offset)).and_utc(), daylight_saving)
+            })
+            .filter(|(instant, daylight_saving)| self.is_daylight_saving(*instant) == *daylight_saving)
+            .collect()
+    }
+
+    /// Whether a local time is skipped by the switch to CEST
+    pub fn is_skipped(&self, local: NaiveDateTime) -> bool {
+        self.instants(local).is_empty()
+    }
+
+    /// Gives the UTC instant of a decoded minute
+    ///
+    /// When exactly one of Z1 and Z2 is set, it gives the time zone, which
+    /// tells apart the two occurrences of the hour repeated at the switch to
+    /// CET. Otherwise the time zone comes from the rules and, in the repeated
+    /// hour, from A1: only one of the two occurrences carries the A1 the
+    /// schedule would send. A minute in the hour skipped at the switch to CEST
+    /// is an error.
+    ///
+    /// # Examples
+    /// ```
+    ///use chrono::prelude::*;
+    ///use dcf77_chrono::*;
+    ///use dcf77_chrono::dst::*;
+    ///let summer_time = DCF77 {
+    ///    date: Utc.with_ymd_and_hms(23, 10, 29, 2, 30, 0).unwrap(),
+    ///    antenna: false,
+    ///    announce_daily_saving_time: true,
+    ///    daily_saving_time: true,
+    ///    standard_time: false,
+    ///    bit_leap_second: false,
+    ///    third_party: 0
+    ///};
+    ///let standard_time = DCF77 {announce_daily_saving_time: false, daily_saving_time: false, standard_time: true, ..summer_time};
+    ///let rules = DstRules::german();
+    ///assert!(rules.resolve(summer_time).unwrap() == Utc.with_ymd_and_hms(2023, 10, 29, 0, 30, 0).unwrap());
+    ///assert!(rules.resolve(standard_time).unwrap() == Utc.with_ymd_and_hms(2023, 10, 29, 1, 30, 0).unwrap());
+    ///let skipped = DCF77 {date: Utc.with_ymd_and_hms(23, 3, 26, 2, 30, 0).unwrap(), ..summer_time};
+    ///assert!(rules.resolve(skipped).is_err());
+    /// ```
+    pub fn resolve(&self, dcf_data: DCF77) -> Result<DateTime<Utc>, Error> {
+        let local = local_time(dcf_data);
+        let instants = self.instants(local);
+        if instants.is_empty() {
+            let error_payload = format!("Local time skipped by the switch to CEST - {}", local);
+            return Err(Error::new(ErrorKind::InvalidData, error_payload));
+        }
+        if dcf_data.daily_saving_time != dcf_data.standard_time {
+            return Ok(to_utc(dcf_data));
+        }
+        let announced: Vec<DateTime<Utc>> = instants.iter()
+            .map(|(instant, _)| *instant)
+            .filter(|instant| instants.len() == 1 || self.next_switch(*instant - chrono::Duration::minutes(1))
+                .is_some_and(|switch| is_announced(*instant, switch)) == dcf_data.announce_daily_saving_time)
+            .collect();
+        match announced[..] {
+            [instant] => Ok(instant),
+            _ => {
+                let error_payload = format!("Ambiguous local time - {} Z1: {} Z2: {} A1: {}", local, dcf_data.daily_saving_time, dcf_data.standard_time, dcf_data.announce_daily_saving_time);
+                Err(Error::new(ErrorKind::InvalidData, error_payload))
+            }
+        }
+    }
+
+    /// Checks that the time zone bits of a decoded minute follow the rules
+    ///
+    /// Exactly one of Z1 and Z2 must be set, the local time must not be
+    /// skipped by the switch to CEST, and Z1 must give the time zone of the
+    /// rules at the instant the minute describes.
+    ///
+    /// # Examples
+    /// ```
+    ///use chrono::prelude::*;
+    ///use dcf77_chrono::*;
+    ///use dcf77_chrono::dst::*;
+    ///let mut dcf_data = DCF77 {
+    ///    date: Utc.with_ymd_and_hms(23, 7, 1, 12, 0, 0).unwrap(),
+    ///    antenna: false,
+    ///    announce_daily_saving_time: false,
+    ///    daily_saving_time: true,
+    ///    standard_time: false,
+    ///    bit_leap_second: false,
+    ///    third_party: 0
+    ///};
+    ///assert!(DstRules::german().check(dcf_data).is_ok());
+    ///dcf_data.daily_saving_time = false;
+    ///dcf_data.standard_time = true;
+    ///assert!(DstRules::german().check(dcf_data).is_err());
+    /// ```
+    pub fn check(&self, dcf_data: DCF77) -> Result<(), Error> {
+        if dcf_data.daily_saving_time == dcf_data.standard_time {
+            let error_payload = format!("Invalid time zone bits - Z1: {} Z2: {}", dcf_data.daily_saving_time, dcf_data.standard_time);
+            return Err(Error::new(ErrorKind::InvalidData, error_payload));
+        }
+        let local = local_time(dcf_data);
+        if self.is_skipped(local) {
+            let error_payload = format!("Local time skipped by the switch to CEST - {}", local);
+            return Err(Error::new(ErrorKind::InvalidData, error_payload));
+        }
+        let instant = to_utc(dcf_data);
+        if self.is_daylight_saving(instant) != dcf_data.daily_saving_time {
+            let error_payload = format!("Time zone against the Daylight Saving Time rules - UTC: {} CEST: {}", instant, dcf_data.daily_saving_time);
+            return Err(Error::new(ErrorKind::InvalidData, error_payload));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_dcf77, to_dcf77};
+    use crate::schedule::Schedule;
+    #[test]
+    fn test_switches() {
+        let rules = DstRules::german();
+        let (start, end) = rules.switches(2024);
+        assert!(start == Some(Utc.with_ymd_and_hms(2024, 3, 31, 1, 0, 0).unwrap()));
+        assert!(end == Some(Utc.with_ymd_and_hms(2024, 10, 27, 1, 0, 0).unwrap()));
+        assert!(!rules.is_daylight_saving(start.unwrap() - chrono::Duration::seconds(1)));
+        assert!(rules.is_daylight_saving(start.unwrap()));
+        assert!(!rules.is_daylight_saving(end.unwrap()));
+        // The last German switch in September and the first one in October
+        assert!(rules.switches(1995).1 == Some(Utc.with_ymd_and_hms(1995, 9, 24, 1, 0, 0).unwrap()));
+        assert!(rules.switches(1996).1 == Some(Utc.with_ymd_and_hms(1996, 10, 27, 1, 0, 0).unwrap()));
+        // Out of the range of chrono
+        assert!(DstRules::new(EU_RULE).switches(i32::MAX) == (None, None));
+        let custom = DstRules::new(YearRule::new(Some((4, 7)), Some((12, 31)), 23).unwrap());
+        assert!(custom.switches(2023).1 == Some(Utc.with_ymd_and_hms(2023, 12, 31, 23, 0, 0).unwrap()));
+        assert!(rules.switches(1980).0 == Some(Utc.with_ymd_and_hms(1980, 4, 6, 1, 0, 0).unwrap()));
+        assert!(rules.switches(1981).0 == Some(Utc.with_ymd_and_hms(1981, 3, 29, 1, 0, 0).unwrap()));
+        assert!(rules.switches(1979) == (None, None));
+        assert!(!rules.is_daylight_saving(Utc.with_ymd_and_hms(1979, 7, 1, 0, 0, 0).unwrap()));
+        assert!(rules.next_switch(Utc.with_ymd_and_hms(2023, 11, 1, 0, 0, 0).unwrap()) == Some(Utc.with_ymd_and_hms(2024, 3, 31, 1, 0, 0).unwrap()));
+        assert!(DstRules::new(EU_RULE).switches(1979).1 == Some(Utc.with_ymd_and_hms(1979, 10, 28, 1, 0, 0).unwrap()));
+    }
+    #[test]
+    fn test_abolition() {
+        let summer = DstRules::german().with_abolition(2030, true);
+        assert!(summer.switches(2030) == (Some(Utc.with_ymd_and_hms(2030, 3, 31, 1, 0, 0).unwrap()), None));
+        assert!(summer.is_daylight_saving(Utc.with_ymd_and_hms(2030, 12, 31, 0, 0, 0).unwrap()));
+        assert!(summer.is_daylight_saving(Utc.with_ymd_and_hms(2045, 1, 1, 0, 0, 0).unwrap()));
+        assert!(summer.next_switch(Utc.with_ymd_and_hms(2030, 4, 1, 0, 0, 0).unwrap()).is_none());
+        let winter = DstRules::german().with_abolition(2030, false);
+        assert!(winter.is_daylight_saving(Utc.with_ymd_and_hms(2029, 7, 1, 0, 0, 0).unwrap()));
+        assert!(!winter.is_daylight_saving(Utc.with_ymd_and_hms(2030, 7, 1, 0, 0, 0).unwrap()));
+        assert!(!winter.is_daylight_saving(Utc.with_ymd_and_hms(2045, 7, 1, 0, 0, 0).unwrap()));
+        // Back to switches after an abolition
+        let back = summer.with_rule(2040, EU_RULE);
+        assert!(back.is_daylight_saving(Utc.with_ymd_and_hms(2040, 1, 1, 0, 0, 0).unwrap()));
+        assert!(!back.is_daylight_saving(Utc.with_ymd_and_hms(2040, 11, 1, 0, 0, 0).unwrap()));
+    }
+    #[test]
+    fn test_repeated_hour() {
+        let rules = DstRules::german();
+        let from = Utc.with_ymd_and_hms(2023, 10, 28, 23, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2023, 10, 29, 3, 0, 0).unwrap();
+        let mut resolved = Vec::new();
+        for minute in Schedule::new(from, to) {
+            let decoded_minutes = from_dcf77(minute.frame).unwrap();
+            assert!(rules.resolve(decoded_minutes).unwrap() == minute.utc);
+            // Without Z1 and Z2, A1 tells the occurrences apart
+            let without_zone = DCF77 {daily_saving_time: false, standard_time: false, ..decoded_minutes};
+            resolved.push(rules.resolve(without_zone).unwrap());
+        }
+        assert!(resolved.iter().zip(Schedule::new(from, to)).all(|(instant, minute)| *instant == minute.utc));
+        // Without A1, 02:30 is the second occurrence, in CET
+        let minute = Schedule::new(from + chrono::Duration::minutes(90), to).next().unwrap();
+        let without_announcement = DCF77 {daily_saving_time: false, standard_time: false, announce_daily_saving_time: false, ..minute.dcf};
+        assert!(minute.local.hour() == 2 && minute.dcf.announce_daily_saving_time);
+        assert!(rules.resolve(without_announcement).unwrap() == minute.utc + chrono::Duration::hours(1));
+    }
+    #[test]
+    fn test_skipped_hour() {
+        let rules = DstRules::german();
+        let local = |hour, minute| NaiveDate::from_ymd_opt(2024, 3, 31).unwrap().and_hms_opt(hour, minute, 0).unwrap();
+        assert!(!rules.is_skipped(local(1, 59)));
+        assert!(rules.is_skipped(local(2, 0)));
+        assert!(rules.is_skipped(local(2, 59)));
+        assert!(!rules.is_skipped(local(3, 0)));
+        assert!(rules.instants(NaiveDate::from_ymd_opt(2024, 10, 27).unwrap().and_hms_opt(2, 0, 0).unwrap()).len() == 2);
+        // The rules reject the skipped hour whatever the time zone bits, the
+        // decoder knows nothing of them
+        let skipped = Utc.with_ymd_and_hms(24, 3, 31, 2, 15, 0).unwrap();
+        for daylight_saving in [false, true] {
+            let dcf_data = DCF77 {
+                date: skipped,
+                antenna: false,
+                announce_daily_saving_time: false,
+                daily_saving_time: daylight_saving,
+                standard_time: !daylight_saving,
+                bit_leap_second: false,
+                third_party: 0
+            };
+            assert!(from_dcf77(to_dcf77(dcf_data).unwrap()).is_ok());
+            assert!(rules.resolve(dcf_data).is_err());
+            let error = rules.check(dcf_data).unwrap_err();
+            assert!(error.to_string().starts_with("Local time skipped"));
+            assert!(DstRules::german().with_abolition(2024, false).check(dcf_data).is_ok() != daylight_saving);
+        }
+    }
+}
@@ -0,0 +1,291 @@
+//! DCF77 telegrams as strings of 0 and 1, with unknown bits
+//!
+//! Receiver logs write a minute as the 59 bits of the seconds 0 to 58, the
+//! second 0 first, often with separators between the fields and `?` for the
+//! bits that could not be received. `Frame` parses and formats that text and
+//! keeps track of the unknown bits, which the soft decoder fills from the
+//! constant bits and the parities before decoding.
+
+use std::fmt;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::str::FromStr;
+use crate::{DCF77, from_dcf77};
+use crate::telegram::{Field, FIELDS, SECONDS_PER_MINUTE};
+
+/// Mask of the bits of the seconds 0 to 58 in the DCF77 bit field
+pub const FRAME_MASK: u64 = 0x0FFF_FFFF_FFFF_FFFE;
+
+/// Number of bits sent in a minute, the minute marker having no pulse
+pub const BITS_PER_FRAME: usize = 59;
+
+/// Characters accepted between the bits of a bitstring
+const SEPARATORS: [char; 7] = [' ', '-', '_', '|', ':', '.', ','];
+
+/// Position of the bit of a second in the DCF77 bit field
+fn position(second: u8) -> u64 {
+    1 << (59 - second)
+}
+
+/// DCF77 telegram of which some bits may be unknown
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::frame::Frame;
+///let frame: Frame = "0-00000000000000-0-0-1-0-0-1-0000110-0-000100-1-001010-110-01100-11000100-1-0".parse().unwrap();
+///assert!(frame.is_complete());
+///assert!(frame.to_string() == "00000000000000000100100001100000100100101011001100110001001");
+///assert!(u64::try_from(frame).unwrap() == 0x486092B3312);
+///let frame: Frame = "0000000000000000010010000110?0001001001010110011001100010010".parse().unwrap();
+///assert!(!frame.is_complete());
+///assert!(frame.soft_decode().unwrap().date.minute() == 30);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub struct Frame {
+    /// Bits of the telegram in the layout of `to_dcf77`, unknown bits being 0
+    pub bits: u64,
+    /// Mask of the bits that are known
+    pub known: u64
+}
+
+impl Frame {
+    /// Whether every bit of the seconds 0 to 58 is known
+    pub fn is_complete(&self) -> bool {
+        self.known & FRAME_MASK == FRAME_MASK
+    }
+
+    /// Bit of a second, `None` when it is unknown
+    pub fn bit(&self, second: u8) -> Option<bool> {
+        if second >= SECONDS_PER_MINUTE - 1 || self.known & position(second) == 0 {
+            None
+        } else {
+            Some(self.bits & position(second) > 0)
+        }
+    }
+
+    /// Sets the bit of a second as known
+    pub fn set_bit(&mut self, second: u8, value: bool) {
+        if second < SECONDS_PER_MINUTE - 1 {
+            self.known |= position(second);
+            if value {
+                self.bits |= position(second);
+            } else {
+                self.bits &= !position(second);
+            }
+        }
+    }
+
+    /// Seconds whose bit is unknown
+    pub fn unknown_seconds(&self) -> Vec<u8> {
+        (0..SECONDS_PER_MINUTE - 1).filter(|second| self.bit(*second).is_none()).collect()
+    }
+
+    /// Fills the unknown bits that can be deduced
+    ///
+    /// The start of minute and start of encoded time bits are constant, Z1 and
+    /// Z2 are exclusive, and a parity group with a single unknown bit has it
+    /// given by the even parity. The third party bits and the call bit cannot
+    /// be deduced and stay unknown.
+    pub fn resolve(&self) -> Frame {
+        let mut frame = *self;
+        for (second, value) in [(0, false), (20, true)] {
+            if frame.bit(second).is_none() {
+                frame.set_bit(second, value);
+            }
+        }
+        match (frame.bit(17), frame.bit(18)) {
+            (Some(daylight_saving), None) => frame.set_bit(18, !daylight_saving),
+            (None, Some(standard_time)) => frame.set_bit(17, !standard_time),
+            _ => {}
+        }
+        for field in FIELDS {
+            if let Some(coverage) = field.parity_coverage() {
+                let group: Vec<u8> = coverage.chain(field.seconds()).collect();
+                let unknown: Vec<u8> = group.iter().copied().filter(|second| frame.bit(*second).is_none()).collect();
+                if let [second] = unknown[..] {
+                    let ones = group.iter().filter(|second| frame.bit(**second) == Some(true)).count();
+                    frame.set_bit(second, ones % 2 == 1);
+                }
+            }
+        }
+        frame
+    }
+
+    /// Fills the unknown bits like `resolve`, and takes the unknown third party
+    /// bits and call bit as 0
+    ///
+    /// Those bits do not carry the time, but the bit field given to
+    /// `from_dcf77` needs them.
+    pub fn resolve_for_decoding(&self) -> Frame {
+        let mut frame = self.resolve();
+        for second in Field::ThirdParty.seconds().chain(Field::CallBit.seconds()) {
+            if frame.bit(second).is_none() {
+                frame.set_bit(second, false);
+            }
+        }
+        frame
+    }
+
+    /// Decodes the telegram after filling the unknown bits that can be deduced
+    ///
+    /// The unknown third party bits and call bit are decoded as 0.
+    pub fn soft_decode(&self) -> Result<DCF77, Error> {
+        from_dcf77(u64::try_from(self.resolve_for_decoding())?)
+    }
+}
+
+impl From<u64> for Frame {
+    fn from(input: u64) -> Frame {
+        Frame {bits: input & FRAME_MASK, known: FRAME_MASK}
+    }
+}
+
+impl TryFrom<Frame> for u64 {
+    type Error = Error;
+
+    fn try_from(frame: Frame) -> Result<u64, Error> {
+        if frame.is_complete() {
+            Ok(frame.bits & FRAME_MASK)
+        } else {
+            let error_payload = format!("Unknown bits in the seconds {:?}", frame.unknown_seconds());
+            Err(Error::new(ErrorKind::InvalidData, error_payload))
+        }
+    }
+}
+
+impl FromStr for Frame {
+    type Err = Error;
+
+    /// Parses the 59 bits of a minute, the second 0 first
+    ///
+    /// Separators between the bits are ignored and `?` marks an unknown bit. A
+    /// 60th symbol for the minute marker is accepted when it is not a 1.
+    fn from_str(input: &str) -> Result<Frame, Error> {
+        let mut frame = Frame::default();
+        let mut second = 0;
+        for character in input.trim().chars() {
+            if SEPARATORS.contains(&character) || character.is_whitespace() {
+                continue;
+            }
+            let value = match character {
+                '0' => Some(false),
+                '1' => Some(true),
+                '?' => None,
+                _ => {
+                    let error_payload = format!("Invalid character {:?} in the bitstring {:?}", character, input);
+                    return Err(Error::new(ErrorKind::InvalidData, error_payload));
+                }
+            };
+            if second == SECONDS_PER_MINUTE - 1 {
+                if value == Some(true) {
+                    let error_payload = format!("Pulse in the minute marker of the bitstring {:?}", input);
+                    return Err(Error::new(ErrorKind::InvalidData, error_payload));
+                }
+                second += 1;
+                continue;
+            }
+            if second >= SECONDS_PER_MINUTE {
+                let error_payload = format!("Too many bits in the bitstring {:?}", input);
+                return Err(Error::new(ErrorKind::InvalidData, error_payload));
+            }
+            if let Some(value) = value {
+                frame.set_bit(second, value);
+            }
+            second += 1;
+        }
+        if usize::from(second) < BITS_PER_FRAME {
+            let error_payload = format!("Only {} bits in the bitstring {:?}", second, input);
+            return Err(Error::new(ErrorKind::InvalidData, error_payload));
+        }
+        Ok(frame)
+    }
+}
+
+impl fmt::Display for Frame {
+    /// Formats the 59 bits of a minute, the second 0 first
+    ///
+    /// The alternate form `{:#}` separates the fields with spaces.
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for second in 0..SECONDS_PER_MINUTE - 1 {
+            if formatter.alternate() && second > 0 && FIELDS.iter().any(|field| *field.seconds().start() == second) {
+                write!(formatter, " ")?;
+            }
+            let symbol = match self.bit(second) {
+                Some(true) => '1',
+                Some(false) => '0',
+                None => '?'
+            };
+            write!(formatter, "{}", symbol)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::prelude::*;
+    use crate::to_dcf77;
+    #[test]
+    fn test_round_trip() {
+        let original_test_time = Utc.with_ymd_and_hms(23, 1, 1, 0, 0, 0).unwrap();
+        for fake_input in 0..=0xfff {
+            let test_time = DCF77 {
+                date: original_test_time + chrono::Duration::minutes(fake_input * 127),
+                antenna: fake_input & 8 > 0,
+                announce_daily_saving_time: fake_input & 1 > 0,
+                daily_saving_time: fake_input & 2 > 0,
+                standard_time: fake_input & 2 == 0,
+                bit_leap_second: fake_input & 4 > 0,
+                third_party: (fake_input as u16).wrapping_mul(0x1234) & 0x3FFF
+            };
+            let coded_minutes = to_dcf77(test_time).unwrap();
+            let text = Frame::from(coded_minutes).to_string();
+            assert!(text.len() == BITS_PER_FRAME);
+            let frame: Frame = text.parse().unwrap();
+            assert!(u64::try_from(frame).unwrap() == coded_minutes);
+            let frame: Frame = format!("{:#}", Frame::from(coded_minutes)).parse().unwrap();
+            assert!(u64::try_from(frame).unwrap() == coded_minutes);
+        }
+    }
+    #[test]
+    fn test_parse() {
+        let frame: Frame = "0 10101010101010 0 0 0 1 0 1 0000000 0 000000 0 100000 100 10000 00000000 0 0".parse().unwrap();
+        assert!(format!("{:#}", frame) == "0 10101010101010 0 0 0 1 0 1 0000000 0 000000 0 100000 100 10000 00000000 0");
+        assert!(frame.bit(1) == Some(true) && frame.bit(2) == Some(false));
+        let frame: Frame = "?".repeat(59).parse().unwrap();
+        assert!(frame.known == 0);
+        assert!(frame.unknown_seconds().len() == BITS_PER_FRAME);
+        assert!(frame.to_string() == "?".repeat(59));
+        assert!("0".repeat(58).parse::<Frame>().is_err());
+        assert!("0".repeat(61).parse::<Frame>().is_err());
+        assert!(format!("{}1", "0".repeat(59)).parse::<Frame>().is_err());
+        assert!(format!("{}2", "0".repeat(58)).parse::<Frame>().is_err());
+        assert!(u64::try_from(frame).is_err());
+    }
+    #[test]
+    fn test_soft_decode() {
+        let test_time = DCF77 {
+            date: Utc.with_ymd_and_hms(23, 10, 29, 2, 59, 0).unwrap(),
+            antenna: false,
+            announce_daily_saving_time: true,
+            daily_saving_time: true,
+            standard_time: false,
+            bit_leap_second: false,
+            third_party: 0
+        };
+        let coded_minutes = to_dcf77(test_time).unwrap();
+        let mut frame = Frame::from(coded_minutes);
+        for second in [0, 5, 15, 18, 20, 23, 33, 50] {
+            frame.known &= !position(second);
+        }
+        // The third party bit and the call bit cannot be deduced
+        assert!(frame.resolve().unknown_seconds() == vec![5, 15]);
+        assert!(frame.resolve_for_decoding() == Frame::from(coded_minutes));
+        assert!(frame.soft_decode().unwrap().date == test_time.date);
+        frame.known &= !position(24);
+        assert!(frame.soft_decode().is_err());
+        assert!(frame.resolve().unknown_seconds() == vec![5, 15, 23, 24]);
+    }
+}
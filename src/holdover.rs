@@ -0,0 +1,277 @@
+//! Holdover clock disciplined by decoded DCF77 minutes
+//!
+//! The clock keeps the last decoded minute together with the local monotonic
+//! instant of its minute marker and extrapolates the time from there. The
+//! frequency error of the local clock is estimated out of consecutive
+//! minutes, and the error estimate grows with the time spent without a new
+//! minute (holdover). A minute that disagrees with the clock by more than the
+//! error estimate is taken as an outlier and left out, unless the following
+//! ones agree with it. The leap second announced by A2 is inserted before the
+//! next UTC hour.
+
+use std::time::{Duration, Instant};
+use chrono::prelude::*;
+use crate::{DCF77, to_utc};
+
+/// Precision of a decoded DCF77 minute marker
+pub const DCF77_MARKER_PRECISION: Duration = Duration::from_millis(8);
+
+/// Default assumed worst case drift of the local clock, 15 ppm
+pub const DEFAULT_DRIFT_RATE: f64 = 15e-6;
+
+/// Largest frequency error of the local clock that is accepted, 500 ppm
+const MAX_FREQUENCY: f64 = 500e-6;
+
+/// Consecutive outliers after which the clock takes the last one as reference
+pub const MAX_OUTLIERS: u32 = 3;
+
+/// Holdover clock disciplined by decoded DCF77 minutes
+///
+/// # Examples
+/// ```
+///use std::time::{Duration, Instant};
+///use chrono::prelude::*;
+///use dcf77_chrono::DCF77;
+///use dcf77_chrono::holdover::*;
+///let test_time = DCF77 {
+///    date: Utc.with_ymd_and_hms(2023, 2, 1, 13, 34, 0).unwrap(),
+///    antenna: false,
+///    announce_daily_saving_time: false,
+///    daily_saving_time: false,
+///    standard_time: true,
+///    bit_leap_second: false,
+///    third_party: 0
+///};
+///let marker = Instant::now();
+///let mut clock = HoldoverClock::new(DEFAULT_DRIFT_RATE);
+///clock.synchronize(test_time, marker);
+///let later = marker + Duration::from_secs(90);
+///assert!(clock.time_at(later).unwrap() == Utc.with_ymd_and_hms(2023, 2, 1, 12, 35, 30).unwrap());
+///assert!(clock.error_estimate_at(later).unwrap() > DCF77_MARKER_PRECISION);
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct HoldoverClock {
+    reference: Option<(Instant, DateTime<Utc>)>,
+    frequency: f64,
+    drift_rate: f64,
+    leap_second_announced: bool,
+    outliers: u32
+}
+
+impl HoldoverClock {
+    /// Creates a clock that has not been synchronized yet
+    ///
+    /// `drift_rate` is the assumed worst case drift of the local clock, used to
+    /// grow the error estimate during holdover.
+    pub fn new(drift_rate: f64) -> HoldoverClock {
+        HoldoverClock {
+            reference: None,
+            frequency: 0.0,
+            drift_rate,
+            leap_second_announced: false,
+            outliers: 0
+        }
+    }
+
+    /// Synchronizes the clock with a decoded minute
+    ///
+    /// `marker` is the local instant at which the minute marker of the decoded
+    /// minute was received. A minute whose time differs from the one of the
+    /// clock by more than the error estimate is an outlier: it is rejected and
+    /// `false` is returned, unless it is the `MAX_OUTLIERS`th in a row, which
+    /// means that the clock is the one to be wrong.
+    pub fn synchronize(&mut self, dcf_data: DCF77, marker: Instant) -> bool {
+        let measured = to_utc(dcf_data);
+        if let Some((reference_instant, _)) = self.reference {
+            if let (Some(predicted), Some(error_estimate)) = (self.time_at(marker), self.error_estimate_at(marker)) {
+                let error = measured - predicted;
+                if error.abs().to_std().unwrap_or(Duration::MAX) > error_estimate {
+                    self.outliers += 1;
+                    if self.outliers < MAX_OUTLIERS {
+                        return false;
+                    }
+                } else if let Some(elapsed) = marker.checked_duration_since(reference_instant).filter(|elapsed| !elapsed.is_zero()) {
+                    let error = error.num_nanoseconds().unwrap_or(0) as f64 / 1e9;
+                    self.frequency = (self.frequency + error / elapsed.as_secs_f64()).clamp(-MAX_FREQUENCY, MAX_FREQUENCY);
+                }
+            }
+        }
+        self.reference = Some((marker, measured));
+        self.leap_second_announced = dcf_data.bit_leap_second;
+        self.outliers = 0;
+        true
+    }
+
+    /// Whether the clock has been synchronized at least once
+    pub fn is_synchronized(&self) -> bool {
+        self.reference.is_some()
+    }
+
+    /// Whether the last decoded minute announced a leap second
+    pub fn leap_second_announced(&self) -> bool {
+        self.leap_second_announced
+    }
+
+    /// UTC time following the leap second announced by the last decoded minute
+    ///
+    /// The leap second is inserted at the end of the UTC hour of the minute.
+    pub fn leap_second(&self) -> Option<DateTime<Utc>> {
+        let (_, reference_time) = self.reference?;
+        let hour = reference_time.with_minute(0)?.with_second(0)?.with_nanosecond(0)?;
+        self.leap_second_announced.then(|| hour + chrono::Duration::hours(1))
+    }
+
+    /// Consecutive outliers rejected since the last accepted minute
+    pub fn outliers(&self) -> u32 {
+        self.outliers
+    }
+
+    /// UTC time of the last decoded minute
+    pub fn reference_time(&self) -> Option<DateTime<Utc>> {
+        self.reference.map(|(_, time)| time)
+    }
+
+    /// Estimated frequency error of the local clock, positive when it runs slow
+    pub fn frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    /// Time elapsed between the last decoded minute and a local instant
+    pub fn holdover_at(&self, instant: Instant) -> Option<Duration> {
+        self.reference.map(|(reference_instant, _)| instant.saturating_duration_since(reference_instant))
+    }
+
+    /// Estimated UTC time at a local instant
+    ///
+    /// Past an announced leap second, the time is one second behind the
+    /// elapsed local time; the inserted second repeats the last one of the hour.
+    pub fn time_at(&self, instant: Instant) -> Option<DateTime<Utc>> {
+        let (reference_instant, reference_time) = self.reference?;
+        let elapsed = if instant >= reference_instant {
+            (instant - reference_instant).as_secs_f64()
+        } else {
+            -(reference_instant - instant).as_secs_f64()
+        };
+        let corrected = elapsed * (1.0 + self.frequency);
+        let time = reference_time + chrono::Duration::nanoseconds((corrected * 1e9) as i64);
+        match self.leap_second() {
+            Some(leap_second) if time >= leap_second => Some(time - chrono::Duration::seconds(1)),
+            _ => Some(time)
+        }
+    }
+
+    /// Estimated UTC time now
+    pub fn now(&self) -> Option<DateTime<Utc>> {
+        self.time_at(Instant::now())
+    }
+
+    /// Estimated error of the time given at a local instant
+    ///
+    /// It is the precision of the minute marker plus the assumed drift of the
+    /// local clock for the whole holdover.
+    pub fn error_estimate_at(&self, instant: Instant) -> Option<Duration> {
+        let holdover = self.holdover_at(instant)?;
+        Some(DCF77_MARKER_PRECISION + holdover.mul_f64(self.drift_rate))
+    }
+}
+
+impl Default for HoldoverClock {
+    fn default() -> HoldoverClock {
+        HoldoverClock::new(DEFAULT_DRIFT_RATE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn test_minute(minute: u32) -> DCF77 {
+        DCF77 {
+            date: Utc.with_ymd_and_hms(2023, 7, 1, 12, minute, 0).unwrap(),
+            antenna: false,
+            announce_daily_saving_time: false,
+            daily_saving_time: true,
+            standard_time: false,
+            bit_leap_second: false,
+            third_party: 0
+        }
+    }
+    #[test]
+    fn test_unsynchronized() {
+        let clock = HoldoverClock::default();
+        assert!(!clock.is_synchronized());
+        assert!(clock.now().is_none());
+        assert!(clock.error_estimate_at(Instant::now()).is_none());
+    }
+    #[test]
+    fn test_error_grows() {
+        let marker = Instant::now();
+        let mut clock = HoldoverClock::new(1e-5);
+        clock.synchronize(test_minute(0), marker);
+        assert!(clock.error_estimate_at(marker).unwrap() == DCF77_MARKER_PRECISION);
+        let error_estimate = clock.error_estimate_at(marker + Duration::from_secs(1000)).unwrap();
+        assert!(error_estimate == DCF77_MARKER_PRECISION + Duration::from_millis(10));
+        assert!(clock.holdover_at(marker + Duration::from_secs(1000)).unwrap() == Duration::from_secs(1000));
+    }
+    #[test]
+    fn test_frequency() {
+        let marker = Instant::now();
+        // The local clock may drift by 200 ppm
+        let mut clock = HoldoverClock::new(2e-4);
+        clock.synchronize(test_minute(0), marker);
+        // The local clock runs 100 ppm slow: 600 s are measured as 599.94 s
+        clock.synchronize(test_minute(10), marker + Duration::from_micros(599_940_000));
+        assert!((clock.frequency() - 1e-4).abs() < 1e-7);
+        let predicted = clock.time_at(marker + Duration::from_micros(599_940_000 * 2)).unwrap();
+        let expected = Utc.with_ymd_and_hms(2023, 7, 1, 10, 20, 0).unwrap();
+        assert!((predicted - expected).num_microseconds().unwrap().abs() < 100);
+    }
+    #[test]
+    fn test_outlier() {
+        let marker = Instant::now();
+        let mut clock = HoldoverClock::default();
+        assert!(clock.synchronize(test_minute(0), marker));
+        assert!(clock.synchronize(test_minute(10), marker + Duration::from_millis(600_005)));
+        let frequency = clock.frequency();
+        // A minute marker received 200 ms late leaves the clock as it is
+        assert!(!clock.synchronize(test_minute(20), marker + Duration::from_millis(1_200_205)));
+        assert!(clock.outliers() == 1);
+        assert!(clock.frequency() == frequency);
+        assert!(clock.reference_time() == Some(to_utc(test_minute(10))));
+        assert!(clock.synchronize(test_minute(30), marker + Duration::from_millis(1_800_010)));
+        assert!(clock.outliers() == 0);
+        assert!(clock.frequency().abs() < 1e-5);
+        // The clock follows a time that keeps disagreeing with it
+        for (index, minute) in [40, 41, 42].into_iter().enumerate() {
+            let accepted = clock.synchronize(test_minute(minute), marker + Duration::from_secs(60 * minute as u64 + 5));
+            assert!(accepted == (index == 2));
+        }
+        assert!(clock.reference_time() == Some(to_utc(test_minute(42))));
+    }
+    #[test]
+    fn test_leap_second() {
+        let leap_minute = DCF77 {
+            date: Utc.with_ymd_and_hms(17, 1, 1, 0, 59, 0).unwrap(),
+            antenna: false,
+            announce_daily_saving_time: false,
+            daily_saving_time: false,
+            standard_time: true,
+            bit_leap_second: true,
+            third_party: 0
+        };
+        let next_minute = DCF77 {
+            date: Utc.with_ymd_and_hms(17, 1, 1, 1, 0, 0).unwrap(),
+            bit_leap_second: false,
+            ..leap_minute
+        };
+        let marker = Instant::now();
+        let mut clock = HoldoverClock::default();
+        clock.synchronize(leap_minute, marker);
+        assert!(clock.leap_second() == Some(Utc.with_ymd_and_hms(2017, 1, 1, 0, 0, 0).unwrap()));
+        // The minute of the leap second lasts 61 seconds
+        let after = clock.time_at(marker + Duration::from_secs(61)).unwrap();
+        assert!(after == Utc.with_ymd_and_hms(2017, 1, 1, 0, 0, 0).unwrap());
+        assert!(clock.synchronize(next_minute, marker + Duration::from_secs(61)));
+        assert!(clock.frequency().abs() < 1e-9);
+        assert!(clock.leap_second().is_none());
+    }
+}
@@ -0,0 +1,426 @@
+//! Coding and decoding of IRIG-B (B00x and B12x) time code frames
+//!
+//! An IRIG-B frame lasts one second and is made of 100 elements of 10 ms. Each
+//! element is either a binary 0 (2 ms pulse), a binary 1 (5 ms pulse) or a
+//! position identifier (8 ms pulse). The reference marker Pr is the element 0
+//! and the position identifiers P1..P9 and P0 are the elements 9, 19, ..., 99:
+//!
+//! | Elements | Content                                         |
+//! |----------|-------------------------------------------------|
+//! | 1..9     | Seconds BCD (units 1..5, tens 6..9)             |
+//! | 10..19   | Minutes BCD (units 10..14, tens 15..18)         |
+//! | 20..29   | Hours BCD (units 20..24, tens 25..27)           |
+//! | 30..42   | Day of the year BCD (units, tens, hundreds)     |
+//! | 50..59   | Year BCD (units 50..54, tens 55..59)            |
+//! | 60..79   | Control functions, 18 bits                      |
+//! | 80..98   | Straight binary seconds of the day, 17 bits     |
+//!
+//! B00x frames are sent as DC level pulses and B12x frames modulate the
+//! amplitude of a 1 kHz carrier with the same pulses. The `x` digit is the
+//! coded expression, which tells which of the optional fields are present.
+
+use std::io::Error;
+use std::io::ErrorKind;
+use std::time::Duration;
+use chrono::prelude::*;
+
+/// Number of elements in an IRIG-B frame
+pub const ELEMENTS_PER_FRAME: usize = 100;
+
+/// Element of an IRIG-B frame
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IrigSymbol {
+    /// Binary 0, 2 ms pulse
+    Zero,
+    /// Binary 1, 5 ms pulse
+    One,
+    /// Reference marker or position identifier, 8 ms pulse
+    Marker
+}
+
+/// IRIG-B frame, one element each 10 ms
+pub type IrigBFrame = [IrigSymbol; ELEMENTS_PER_FRAME];
+
+/// Coded expression of an IRIG-B frame (the `x` in B00x and B12x)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CodedExpression {
+    /// Time of year, control functions and straight binary seconds
+    B0 = 0,
+    /// Time of year and control functions
+    B1 = 1,
+    /// Time of year
+    B2 = 2,
+    /// Time of year and straight binary seconds
+    B3 = 3,
+    /// Time of year, year, control functions and straight binary seconds
+    B4 = 4,
+    /// Time of year, year and control functions
+    B5 = 5,
+    /// Time of year and year
+    B6 = 6,
+    /// Time of year, year and straight binary seconds
+    B7 = 7
+}
+
+impl CodedExpression {
+    /// Whether the frame carries the year
+    pub fn has_year(self) -> bool {
+        self as u8 >= 4
+    }
+
+    /// Whether the frame carries the control functions
+    pub fn has_control_functions(self) -> bool {
+        matches!(self as u8 % 4, 0 | 1)
+    }
+
+    /// Whether the frame carries the straight binary seconds
+    pub fn has_straight_binary_seconds(self) -> bool {
+        matches!(self as u8 % 4, 0 | 3)
+    }
+}
+
+/// Struct that represents the IRIG-B information
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::irig_b::*;
+///let test_time = IrigB {
+///    date: Utc.with_ymd_and_hms(2023, 3, 26, 1, 59, 58).unwrap(),
+///    control_functions: 0x155,
+///    expression: CodedExpression::B4
+///};
+///let coded_frame = to_irig_b(test_time).unwrap();
+///let decoded_frame = from_irig_b(&coded_frame, CodedExpression::B4, 2023).unwrap();
+///assert!(decoded_frame == test_time);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct IrigB {
+    /// Date in UTC timezone
+    pub date: DateTime<Utc>,
+    /// Control functions [0..2^18)
+    pub control_functions: u32,
+    /// Coded expression used to code the frame
+    pub expression: CodedExpression
+}
+
+/// Elements and weights of the seconds in an IRIG-B frame
+const SECONDS_ELEMENTS: [(usize, u32); 7] = [(1, 1), (2, 2), (3, 4), (4, 8), (6, 10), (7, 20), (8, 40)];
+
+/// Elements and weights of the minutes in an IRIG-B frame
+const MINUTES_ELEMENTS: [(usize, u32); 7] = [(10, 1), (11, 2), (12, 4), (13, 8), (15, 10), (16, 20), (17, 40)];
+
+/// Elements and weights of the hours in an IRIG-B frame
+const HOURS_ELEMENTS: [(usize, u32); 6] = [(20, 1), (21, 2), (22, 4), (23, 8), (25, 10), (26, 20)];
+
+/// Elements and weights of the day of the year in an IRIG-B frame
+const DAY_OF_YEAR_ELEMENTS: [(usize, u32); 10] = [(30, 1), (31, 2), (32, 4), (33, 8),
+                                                  (35, 10), (36, 20), (37, 40), (38, 80),
+                                                  (40, 100), (41, 200)];
+
+/// Elements and weights of the year in an IRIG-B frame
+const YEAR_ELEMENTS: [(usize, u32); 8] = [(50, 1), (51, 2), (52, 4), (53, 8),
+                                          (55, 10), (56, 20), (57, 40), (58, 80)];
+
+/// Elements of the control functions in an IRIG-B frame, least significant bit first
+const CONTROL_FUNCTIONS_ELEMENTS: [usize; 18] = [60, 61, 62, 63, 64, 65, 66, 67, 68,
+                                                 70, 71, 72, 73, 74, 75, 76, 77, 78];
+
+/// Elements of the straight binary seconds in an IRIG-B frame, least significant bit first
+const STRAIGHT_BINARY_SECONDS_ELEMENTS: [usize; 17] = [80, 81, 82, 83, 84, 85, 86, 87, 88,
+                                                       90, 91, 92, 93, 94, 95, 96, 97];
+
+/// Duration of one element of an IRIG-B frame
+const ELEMENT_DURATION: Duration = Duration::from_millis(10);
+
+/// Allowed deviation of a pulse width from its nominal value
+const PULSE_TOLERANCE: Duration = Duration::from_micros(1500);
+
+/// Whether an element of the frame is the reference marker or a position identifier
+fn is_marker_position(index: usize) -> bool {
+    index == 0 || index % 10 == 9
+}
+
+/// Codes a BCD value into the given elements of the frame
+fn code_bcd(frame: &mut IrigBFrame, input: u32, elements: &[(usize, u32)]) {
+    let mut aux = input;
+    for (index, weight) in elements.iter().rev() {
+        if aux >= *weight {
+            frame[*index] = IrigSymbol::One;
+            aux -= weight;
+        }
+    }
+}
+
+/// Decodes a BCD value out of the given elements of the frame
+fn decode_bcd(frame: &IrigBFrame, elements: &[(usize, u32)]) -> u32 {
+    elements.iter()
+        .filter(|(index, _)| frame[*index] == IrigSymbol::One)
+        .map(|(_, weight)| weight)
+        .sum()
+}
+
+/// Codes a binary value into the given elements of the frame
+fn code_binary(frame: &mut IrigBFrame, input: u32, elements: &[usize]) {
+    for (bit, index) in elements.iter().enumerate() {
+        if input & (1 << bit) > 0 {
+            frame[*index] = IrigSymbol::One;
+        }
+    }
+}
+
+/// Decodes a binary value out of the given elements of the frame
+fn decode_binary(frame: &IrigBFrame, elements: &[usize]) -> u32 {
+    elements.iter()
+        .enumerate()
+        .filter(|(_, index)| frame[**index] == IrigSymbol::One)
+        .fold(0, |output, (bit, _)| output | (1 << bit))
+}
+
+/// Encodes an IRIG-B frame containing the information of an IrigB struct
+///
+/// An IrigB struct is given as input and an IRIG-B frame is returned when
+/// successful. Only the fields of the coded expression are coded.
+pub fn to_irig_b(irig_data: IrigB) -> Result<IrigBFrame, Error> {
+    if irig_data.control_functions >= 1 << CONTROL_FUNCTIONS_ELEMENTS.len() {
+        let error_payload = format!("Above max! : 0x{:X} -> 0x{:X}", irig_data.control_functions, (1 << CONTROL_FUNCTIONS_ELEMENTS.len()) - 1);
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    let mut frame = [IrigSymbol::Zero; ELEMENTS_PER_FRAME];
+    for (index, element) in frame.iter_mut().enumerate() {
+        if is_marker_position(index) {
+            *element = IrigSymbol::Marker;
+        }
+    }
+    let given_date = irig_data.date.date_naive();
+    let given_time = irig_data.date.time();
+    code_bcd(&mut frame, given_time.second(), &SECONDS_ELEMENTS);
+    code_bcd(&mut frame, given_time.minute(), &MINUTES_ELEMENTS);
+    code_bcd(&mut frame, given_time.hour(), &HOURS_ELEMENTS);
+    code_bcd(&mut frame, given_date.ordinal(), &DAY_OF_YEAR_ELEMENTS);
+    if irig_data.expression.has_year() {
+        code_bcd(&mut frame, given_date.year().rem_euclid(100) as u32, &YEAR_ELEMENTS);
+    }
+    if irig_data.expression.has_control_functions() {
+        code_binary(&mut frame, irig_data.control_functions, &CONTROL_FUNCTIONS_ELEMENTS);
+    }
+    if irig_data.expression.has_straight_binary_seconds() {
+        code_binary(&mut frame, given_time.num_seconds_from_midnight(), &STRAIGHT_BINARY_SECONDS_ELEMENTS);
+    }
+    Ok(frame)
+}
+
+/// Decodes the date and control functions out of an IRIG-B frame
+///
+/// The frame only carries the last two digits of the year, so the century is
+/// taken from `reference_year`. When the coded expression has no year at all
+/// `reference_year` is used as is.
+pub fn from_irig_b(input: &IrigBFrame, expression: CodedExpression, reference_year: i32) -> Result<IrigB, Error> {
+    for (index, element) in input.iter().enumerate() {
+        if is_marker_position(index) != (*element == IrigSymbol::Marker) {
+            let error_payload = format!("Misplaced position identifier at element {}", index);
+            return Err(Error::new(ErrorKind::InvalidData, error_payload));
+        }
+    }
+    let year = if expression.has_year() {
+        reference_year - reference_year.rem_euclid(100) + decode_bcd(input, &YEAR_ELEMENTS) as i32
+    } else {
+        reference_year
+    };
+    let date = NaiveDate::from_yo_opt(year, decode_bcd(input, &DAY_OF_YEAR_ELEMENTS))
+        .and_then(|date| date.and_hms_opt(decode_bcd(input, &HOURS_ELEMENTS),
+                                          decode_bcd(input, &MINUTES_ELEMENTS),
+                                          decode_bcd(input, &SECONDS_ELEMENTS)));
+    let date = match date {
+        Some(date) => Utc.from_utc_datetime(&date),
+        None => {
+            let error_payload = "Invalid date in the IRIG-B frame".to_string();
+            return Err(Error::new(ErrorKind::InvalidData, error_payload));
+        }
+    };
+    if expression.has_straight_binary_seconds() {
+        let straight_binary_seconds = decode_binary(input, &STRAIGHT_BINARY_SECONDS_ELEMENTS);
+        if straight_binary_seconds != date.time().num_seconds_from_midnight() {
+            let error_payload = format!("Straight binary seconds {} do not match the BCD time", straight_binary_seconds);
+            return Err(Error::new(ErrorKind::InvalidData, error_payload));
+        }
+    }
+    let control_functions = if expression.has_control_functions() {
+        decode_binary(input, &CONTROL_FUNCTIONS_ELEMENTS)
+    } else {
+        0
+    };
+    Ok(IrigB {date, control_functions, expression})
+}
+
+/// Gives the nominal pulse width of an element of an IRIG-B frame
+pub fn pulse_width(input: IrigSymbol) -> Duration {
+    match input {
+        IrigSymbol::Zero => Duration::from_millis(2),
+        IrigSymbol::One => Duration::from_millis(5),
+        IrigSymbol::Marker => Duration::from_millis(8)
+    }
+}
+
+/// Translates the width of a pulse into an element of an IRIG-B frame
+pub fn symbol_from_pulse_width(width: Duration) -> Result<IrigSymbol, Error> {
+    for symbol in [IrigSymbol::Zero, IrigSymbol::One, IrigSymbol::Marker] {
+        let nominal = pulse_width(symbol);
+        if width.max(nominal) - width.min(nominal) < PULSE_TOLERANCE {
+            return Ok(symbol);
+        }
+    }
+    let error_payload = format!("Invalid pulse width: {:?}", width);
+    Err(Error::new(ErrorKind::InvalidData, error_payload))
+}
+
+/// Creates the DC level (B00x) timeline of an IRIG-B frame
+///
+/// Each entry is the offset since the start of the frame at which the level
+/// changes and the new level, starting with the rising edge of the reference
+/// marker.
+///
+/// # Examples
+/// ```
+///use std::time::Duration;
+///use chrono::prelude::*;
+///use dcf77_chrono::irig_b::*;
+///let test_time = IrigB {
+///    date: Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap(),
+///    control_functions: 0,
+///    expression: CodedExpression::B2
+///};
+///let timeline = dc_level_timeline(&to_irig_b(test_time).unwrap());
+///assert!(timeline[0] == (Duration::ZERO, true));
+///assert!(timeline[1] == (Duration::from_millis(8), false));
+///assert!(timeline.len() == 200);
+/// ```
+pub fn dc_level_timeline(input: &IrigBFrame) -> Vec<(Duration, bool)> {
+    let mut output = Vec::with_capacity(2 * ELEMENTS_PER_FRAME);
+    for (index, element) in input.iter().enumerate() {
+        let start = ELEMENT_DURATION * index as u32;
+        output.push((start, true));
+        output.push((start + pulse_width(*element), false));
+    }
+    output
+}
+
+/// Creates the amplitude envelope of a B12x frame, one entry per 1 kHz carrier cycle
+///
+/// `true` means the carrier is sent at mark (high) amplitude and `false` at
+/// space (low) amplitude.
+pub fn carrier_envelope(input: &IrigBFrame) -> Vec<bool> {
+    let cycles_per_element = ELEMENT_DURATION.as_millis() as usize;
+    let mut output = Vec::with_capacity(cycles_per_element * ELEMENTS_PER_FRAME);
+    for element in input.iter() {
+        let high_cycles = pulse_width(*element).as_millis() as usize;
+        for cycle in 0..cycles_per_element {
+            output.push(cycle < high_cycles);
+        }
+    }
+    output
+}
+
+/// Rebuilds an IRIG-B frame out of the widths of its 100 pulses
+pub fn frame_from_pulse_widths(input: &[Duration]) -> Result<IrigBFrame, Error> {
+    if input.len() != ELEMENTS_PER_FRAME {
+        let error_payload = format!("Expected {} pulses, got {}", ELEMENTS_PER_FRAME, input.len());
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    let mut frame = [IrigSymbol::Zero; ELEMENTS_PER_FRAME];
+    for (element, width) in frame.iter_mut().zip(input.iter()) {
+        *element = symbol_from_pulse_width(*width)?;
+    }
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const EXPRESSIONS: [CodedExpression; 8] = [CodedExpression::B0, CodedExpression::B1,
+                                               CodedExpression::B2, CodedExpression::B3,
+                                               CodedExpression::B4, CodedExpression::B5,
+                                               CodedExpression::B6, CodedExpression::B7];
+    #[test]
+    fn test_coder_decoder() {
+        let original_test_time = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        for fake_input in 0..=0xfff {
+            let expression = EXPRESSIONS[fake_input as usize % EXPRESSIONS.len()];
+            let test_time = IrigB {
+                date: original_test_time + chrono::Duration::seconds(fake_input * 7_777),
+                control_functions: (fake_input as u32 * 0x2F) & 0x3FFFF,
+                expression
+            };
+            match to_irig_b(test_time) {
+                Ok(coded_frame) => {
+                    match from_irig_b(&coded_frame, expression, test_time.date.year()) {
+                        Ok(decoded_frame) => {
+                            assert!(test_time.date == decoded_frame.date);
+                            if expression.has_control_functions() {
+                                assert!(test_time.control_functions == decoded_frame.control_functions);
+                            } else {
+                                assert!(0 == decoded_frame.control_functions);
+                            }
+                        }
+                        Err(error) => {
+                            panic!("Error on decoding {:?} {:?}", coded_frame, error)
+                        }
+                    }
+                }
+                Err(error) => {
+                    panic!("Error on coding the irig-b {:?}", error)
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_known_frame() {
+        let test_time = IrigB {
+            date: Utc.with_ymd_and_hms(2023, 2, 1, 12, 34, 56).unwrap(),
+            control_functions: 0,
+            expression: CodedExpression::B7
+        };
+        let coded_frame = to_irig_b(test_time).unwrap();
+        let ones: Vec<usize> = coded_frame.iter()
+            .enumerate()
+            .filter(|(_, element)| **element == IrigSymbol::One)
+            .map(|(index, _)| index)
+            .collect();
+        // 56 s, 34 min, 12 h, day 32, year 23 and 45296 straight binary seconds
+        assert!(ones == vec![2, 3, 6, 8, 12, 15, 16, 21, 25, 31, 35, 36, 50, 51, 56,
+                             84, 85, 86, 87, 93, 94, 96]);
+    }
+    #[test]
+    fn test_invalid_frames() {
+        let test_time = IrigB {
+            date: Utc.with_ymd_and_hms(2023, 2, 1, 12, 34, 56).unwrap(),
+            control_functions: 0,
+            expression: CodedExpression::B0
+        };
+        let coded_frame = to_irig_b(test_time).unwrap();
+        let mut wrong_frame = coded_frame;
+        wrong_frame[49] = IrigSymbol::Zero;
+        assert!(from_irig_b(&wrong_frame, CodedExpression::B0, 2023).is_err());
+        let mut wrong_frame = coded_frame;
+        wrong_frame[80] = IrigSymbol::One;
+        assert!(from_irig_b(&wrong_frame, CodedExpression::B0, 2023).is_err());
+        assert!(to_irig_b(IrigB {control_functions: 1 << 18, ..test_time}).is_err());
+    }
+    #[test]
+    fn test_pulses() {
+        let test_time = IrigB {
+            date: Utc.with_ymd_and_hms(2023, 12, 31, 23, 59, 59).unwrap(),
+            control_functions: 0x3FFFF,
+            expression: CodedExpression::B4
+        };
+        let coded_frame = to_irig_b(test_time).unwrap();
+        let timeline = dc_level_timeline(&coded_frame);
+        let widths: Vec<Duration> = timeline.chunks(2).map(|edges| edges[1].0 - edges[0].0).collect();
+        assert!(frame_from_pulse_widths(&widths).unwrap() == coded_frame);
+        assert!(frame_from_pulse_widths(&widths[1..]).is_err());
+        let envelope = carrier_envelope(&coded_frame);
+        assert!(envelope.len() == 1000);
+        assert!(envelope.iter().filter(|high| **high).count() as u128 ==
+                widths.iter().map(|width| width.as_millis()).sum::<u128>());
+    }
+}
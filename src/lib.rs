@@ -4,10 +4,77 @@
 //! The library offers two main methods:
 //! - to_dcf77
 //! - from_dcf77
+//!
+//! The `telegram` module explains a DCF77 bit field second by second, and the
+//! `schedule` module gives the telegrams broadcast over a range of instants,
+//! following the Daylight Saving Time rules of the `dst` module.
+//! The `frame` module parses and formats telegrams as strings of 0 and 1, and
+//! the `receiver` module decodes them out of the edges of a receiver module.
+//! The quality of the received signal is gathered by the `quality` module.
+//! Receiver sessions are recorded and replayed with the `capture` module, and
+//! minutes or edge streams are exported to waveform viewers by the `vcd` module.
+//! The third party bits of the seconds 1 to 14 are collected into packets by
+//! the `third_party` module, and the Meteotime forecasts they carry are
+//! decoded by the `meteotime` module. The receiver reports the packets it
+//! collects.
+//! The `call_bit` module follows the status of the transmitter given by the
+//! call bit, and the `continuity` module flags decoded minutes that do not
+//! follow the previous ones. The `spoofing` module scores decoded minutes
+//! against the holdover clock, the timing of their pulses and the phase
+//! modulation to find signals generated by emulators.
+//!
+//! Other time codes are available in their own modules:
+//! - bpc
+//! - tdf
+//! - irig_b
+//!
+//! Decoded minutes can be translated into other formats:
+//! - nmea
+//! - serial_protocols
+//! - ntp_shm
+//! - chrony_sock
+//!
+//! The `holdover` module keeps the time between decoded minutes, and with the
+//! `ntp-server` feature the `ntp_server` module serves it over NTP.
+//! With the `prometheus` feature the `prometheus` module serves the state of
+//! the receiver to Prometheus over HTTP.
+//! With the `simulated-warnings` feature the `simulated_warnings` module codes
+//! and decodes civil protection warnings in a simulation format of its own,
+//! since the layout of the warnings broadcast by DCF77 is not published.
 
 use std::io::Error;
+use std::io::ErrorKind;
 use chrono::prelude::*;
 mod dcf77;
+pub mod telegram;
+pub mod frame;
+pub mod receiver;
+pub mod capture;
+pub mod quality;
+pub mod vcd;
+pub mod third_party;
+pub mod meteotime;
+pub mod call_bit;
+pub mod continuity;
+pub mod spoofing;
+pub mod schedule;
+pub mod dst;
+pub mod bpc;
+pub mod tdf;
+pub mod irig_b;
+pub mod nmea;
+pub mod serial_protocols;
+#[cfg(unix)]
+pub mod ntp_shm;
+#[cfg(unix)]
+pub mod chrony_sock;
+pub mod holdover;
+#[cfg(feature = "ntp-server")]
+pub mod ntp_server;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+#[cfg(feature = "simulated-warnings")]
+pub mod simulated_warnings;
 
 /// Struct that represents the DCF77 information
 ///
@@ -30,7 +97,8 @@ mod dcf77;
 ///    announce_daily_saving_time: false,
 ///    daily_saving_time: false,
 ///    standard_time: true,
-///    bit_leap_second: false};
+///    bit_leap_second: false,
+///    third_party: 0};
 ///    assert!(output.date.year() == 2021);
 ///    assert!(output.date.month() == 11);
 ///    assert!(output.date.day() == 12);
@@ -39,12 +107,14 @@ mod dcf77;
 ///    assert!(output.daily_saving_time == false);
 ///    assert!(output.standard_time == true);
 ///    assert!(output.bit_leap_second == false);
+///    assert!(output.third_party == 0);
 /// ```
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct DCF77 {
     /// Date in UTC timezone
     pub date: DateTime<Utc>,
-    /// Antenna bit (always 0)
+    /// Call bit (R) of the second 15, set when the transmitter runs
+    /// irregularly, for instance on its backup antenna
     pub antenna: bool,
     /// Announce Bit for Daylight Saving Time (DST) Switching
     pub announce_daily_saving_time: bool,
@@ -53,13 +123,22 @@ pub struct DCF77 {
     /// If this bit is set, Standard Time is Active
     pub standard_time: bool,
     /// Announce Bit for Leap Second
-    pub bit_leap_second: bool
+    pub bit_leap_second: bool,
+    /// Third party bits of the seconds 1 to 14 (weather data and civil
+    /// protection warnings), the second 1 being the most significant bit
+    pub third_party: u16
 }
 
 /// Decodes the date and metadata out of a dcf77 bit field
 ///
 /// A DCF77 bitfield is given as input and a DCF77 struct is returned when successful
 ///
+/// Dates that do not exist are rejected. The Daylight Saving Time rules are
+/// left to the `dst` module: `DstRules::check` rejects the local hour skipped
+/// by the switch to CEST, and the hour repeated at the switch to CET is told
+/// apart by `to_utc` from Z1, or by `DstRules::resolve` when Z1 and Z2 cannot
+/// be trusted.
+///
 /// # Examples
 /// ```
 ///use chrono::prelude::*;
@@ -71,7 +150,8 @@ pub struct DCF77 {
 ///    announce_daily_saving_time: false,
 ///    daily_saving_time: false,
 ///    standard_time: false,
-///    bit_leap_second: false
+///    bit_leap_second: false,
+///    third_party: 0
 ///};
 ///match to_dcf77(test_time) {
 ///    Ok(coded_minutes) => {
@@ -95,18 +175,20 @@ pub fn from_dcf77(input: u64) -> Result<DCF77, Error> {
     let processed_day = dcf77::date::process_day(input)?;
     let processed_month = dcf77::date::process_month(input)?;
     let processed_year = dcf77::date::process_year(input)? as i32;
+    dcf77::date::check_date_parity(input)?;
+    let Some(date) = NaiveDate::from_ymd_opt(processed_year, processed_month, processed_day)
+        .and_then(|date| date.and_hms_opt(processed_hour, processed_minutes, 0)) else {
+        let error_payload = format!("Invalid date - {:02}-{:02}-{:02} {:02}:{:02}", processed_year, processed_month, processed_day, processed_hour, processed_minutes);
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    };
     let output = DCF77 {
-        date: Utc.with_ymd_and_hms(processed_year,
-                                    processed_month,
-                                    processed_day,
-                                    processed_hour,
-                                    processed_minutes,
-                                    0).single().unwrap(),
+        date: date.and_utc(),
         antenna: dcf77::metadata::process_antenna(input),
         announce_daily_saving_time: dcf77::metadata::process_announce_daylight_saving_switch(input),
         daily_saving_time: dcf77::metadata::process_daylight_saving(input),
         standard_time: dcf77::metadata::process_standard_time(input),
-        bit_leap_second: dcf77::metadata::process_bit_leap_second(input)};
+        bit_leap_second: dcf77::metadata::process_bit_leap_second(input),
+        third_party: dcf77::metadata::process_third_party(input)};
     Ok(output)
 }
 
@@ -125,7 +207,8 @@ pub fn from_dcf77(input: u64) -> Result<DCF77, Error> {
 ///    announce_daily_saving_time: false,
 ///    daily_saving_time: false,
 ///    standard_time: false,
-///    bit_leap_second: false
+///    bit_leap_second: false,
+///    third_party: 0
 ///};
 ///match to_dcf77(test_time) {
 ///    Ok(coded_minutes) => {
@@ -154,18 +237,95 @@ pub fn to_dcf77(dcf_data: DCF77) -> Result<u64, Error> {
     let coded_year = dcf77::date::code_year(given_date.year())?;
     let coded_antenna = dcf77::metadata::code_antenna(dcf_data.antenna);
     let coded_announce_daily_saving_time = dcf77::metadata::code_announce_daylight_saving_switching(dcf_data.announce_daily_saving_time);
-    let coded_daily_saving_time = dcf77::metadata::code_daylight_saving(dcf_data.announce_daily_saving_time);
+    let coded_daily_saving_time = dcf77::metadata::code_daylight_saving(dcf_data.daily_saving_time);
     let coded_standard_time = dcf77::metadata::code_standard_time(dcf_data.standard_time);
     let coded_bit_leap_second = dcf77::metadata::code_bit_leap_second(dcf_data.bit_leap_second);
+    let coded_third_party = dcf77::metadata::code_third_party(dcf_data.third_party)?;
+    let coded_date_parity = dcf77::date::code_date_parity(coded_day | coded_day_of_week | coded_month | coded_year);
+    let coded_start_of_time = dcf77::metadata::code_start_of_time();
     Ok(coded_hour |
         coded_minutes |
         coded_day |
         coded_day_of_week |
         coded_month |
         coded_year |
+        coded_date_parity |
+        coded_start_of_time |
         coded_antenna |
         coded_announce_daily_saving_time |
         coded_daily_saving_time |
         coded_standard_time |
-        coded_bit_leap_second)
+        coded_bit_leap_second |
+        coded_third_party)
+}
+
+/// Encodes a dcf77 bit field like `to_dcf77`, with the day of the week of the full date
+///
+/// The DCF77 struct only holds the year within the century, whose day of the
+/// week generally differs from the one of the real date. `day_of_week` is
+/// coded instead, with the date parity.
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::*;
+///let real_date = NaiveDate::from_ymd_opt(1995, 6, 1).unwrap();
+///let test_time = DCF77 {
+///    date: Utc.with_ymd_and_hms(95, 6, 1, 12, 0, 0).unwrap(),
+///    antenna: false,
+///    announce_daily_saving_time: false,
+///    daily_saving_time: true,
+///    standard_time: false,
+///    bit_leap_second: false,
+///    third_party: 0
+///};
+///let coded_minutes = to_dcf77_with_day_of_week(test_time, real_date.weekday()).unwrap();
+///let day_of_week = telegram::field_reports(coded_minutes).into_iter().find(|report| report.field == telegram::Field::DayOfWeek).unwrap();
+///assert!(day_of_week.value == 4);
+///assert!(from_dcf77(coded_minutes).is_ok());
+/// ```
+pub fn to_dcf77_with_day_of_week(dcf_data: DCF77, day_of_week: Weekday) -> Result<u64, Error> {
+    let coded_minutes = to_dcf77(dcf_data)?;
+    dcf77::date::replace_day_of_week(coded_minutes, day_of_week.number_from_monday().try_into().unwrap())
+}
+
+/// Gives the offset to UTC of the time broadcast in a DCF77 struct
+///
+/// The offset is two hours when Daylight Saving Time (CEST) is active and one
+/// hour (CET) otherwise.
+pub fn utc_offset(dcf_data: DCF77) -> FixedOffset {
+    if dcf_data.daily_saving_time {
+        FixedOffset::east_opt(2 * 3600).unwrap()
+    } else {
+        FixedOffset::east_opt(3600).unwrap()
+    }
+}
+
+/// Translates the date of a DCF77 struct into the real UTC instant
+///
+/// The date of the DCF77 struct holds the German legal time as broadcast, so
+/// the offset given by `utc_offset` is subtracted. DCF77 only broadcasts the
+/// last two digits of the year, so years below 100 are taken as 20xx.
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::*;
+///let test_time = DCF77 {
+///    date: Utc.with_ymd_and_hms(23, 7, 1, 12, 0, 0).unwrap(),
+///    antenna: false,
+///    announce_daily_saving_time: false,
+///    daily_saving_time: true,
+///    standard_time: false,
+///    bit_leap_second: false,
+///    third_party: 0
+///};
+///assert!(to_utc(test_time) == Utc.with_ymd_and_hms(2023, 7, 1, 10, 0, 0).unwrap());
+/// ```
+pub fn to_utc(dcf_data: DCF77) -> DateTime<Utc> {
+    let mut date = dcf_data.date;
+    if date.year() < 100 {
+        date = date.with_year(date.year() + 2000).unwrap();
+    }
+    date - chrono::Duration::seconds(utc_offset(dcf_data).local_minus_utc().into())
 }
@@ -0,0 +1,642 @@
+//! Meteotime weather forecasts carried by the third party bits
+//!
+//! Meteotime broadcasts one encrypted forecast per third party packet. The
+//! first 40 bits of the 42 bit packet are a block encrypted with a 40 bit key
+//! built from the time of the telegram that carries the first chunk; the last
+//! two bits are not part of the block.
+//!
+//! The cipher is licensed by Meteotime and its tables are not shipped with
+//! this crate: the reverse-engineered algorithm is plugged in through the
+//! `MeteotimeCipher` trait, and everything around it (time key, check value,
+//! forecast fields, regions and broadcast slots) is handled here. No captured
+//! packets with their forecasts are bundled either, so the tests check the
+//! layout around the cipher with a stand-in, not the decryption of real
+//! broadcasts.
+//!
+//! The decrypted block holds the 24 bit forecast in its upper bits and the
+//! check value `0x2501` in its lower 16 bits. The bits of the forecast are:
+//! - 0 to 3: weather during the day
+//! - 4 to 7: weather during the night
+//! - 8 to 11: extreme weather (highs) or wind direction (lows)
+//! - 12 to 14: precipitation probability (highs) or wind strength (lows)
+//! - 15: weather anomaly
+//! - 16 to 21: temperature plus 22 °C, maximum for highs and minimum for lows
+//!
+//! The 480 packets of a day start at 22:00 UTC. The first 360 give the highs
+//! and lows of the next three days for the regions 0 to 59, region after
+//! region; the last 120 give those of the next two days for the regions 60 to
+//! 89.
+//!
+//! Simulated transmissions are built with `encode_packet`, which encrypts a
+//! forecast and places its chunks into the minutes of the broadcast schedule
+//! that carry its slot.
+
+use std::io::Error;
+use std::io::ErrorKind;
+use chrono::prelude::*;
+use crate::{DCF77, to_utc, utc_offset};
+use crate::schedule::{Schedule, ScheduledMinute};
+use crate::third_party::{CHUNKS_PER_PACKET, ThirdPartyPacket, join_chunks, split_packet};
+
+/// Mask of the 40 bits of an encrypted block or a time key
+pub const BLOCK_MASK: u64 = 0xFF_FFFF_FFFF;
+
+/// Check value found in the lower 16 bits of a correctly decrypted block
+pub const CHECK_VALUE: u64 = 0x2501;
+
+/// Bits of a check value in a decrypted block
+const CHECK_BITS: u32 = 16;
+
+/// Packet bits that are not part of the encrypted block
+const UNUSED_PACKET_BITS: u32 = 2;
+
+/// Mask of the 24 bits of a forecast
+const FORECAST_MASK: u64 = 0xFF_FFFF;
+
+/// Offset of the coded temperature in degrees Celsius
+const TEMPERATURE_OFFSET: i8 = 22;
+
+/// Precipitation probabilities in percent of the 3 bit codes
+pub const PRECIPITATION_PROBABILITIES: [u8; 8] = [0, 15, 30, 45, 60, 75, 90, 100];
+
+/// Lowest and highest Beaufort numbers of the 3 bit wind strength codes
+pub const WIND_STRENGTHS: [(u8, u8); 8] = [(0, 0), (0, 2), (3, 4), (5, 6), (7, 7), (8, 8), (9, 9), (10, 12)];
+
+/// Hour (UTC) at which the packets of a day start
+pub const DAY_START_HOUR: u32 = 22;
+
+/// Packets sent per day
+pub const PACKETS_PER_DAY: u32 = 480;
+
+/// Packets of the day that give the three days forecasts of the regions 0 to 59
+const LONG_FORECAST_PACKETS: u32 = 360;
+
+/// Regions with a three days forecast
+const LONG_FORECAST_REGIONS: u32 = 60;
+
+/// Regions with a two days forecast
+const SHORT_FORECAST_REGIONS: u32 = 30;
+
+/// Country and name of the 90 forecast regions
+pub const REGIONS: [(&str, &str); 90] = [
+    ("F", "Bordeaux"), ("F", "La Rochelle"), ("F", "Paris"), ("F", "Brest"),
+    ("F", "Clermont-Ferrand"), ("F", "Béziers"), ("B", "Bruxelles"), ("F", "Dijon"),
+    ("F", "Marseille"), ("F", "Lyon"), ("F", "Grenoble"), ("CH", "La Chaux de Fonds"),
+    ("D", "Frankfurt am Main"), ("D", "Trier"), ("D", "Duisburg"), ("GB", "Swansea"),
+    ("GB", "Manchester"), ("F", "Le Havre"), ("GB", "London"), ("D", "Bremerhaven"),
+    ("DK", "Herning"), ("DK", "Århus"), ("D", "Hannover"), ("DK", "København"),
+    ("D", "Rostock"), ("D", "Ingolstadt"), ("D", "München"), ("I", "Bolzano"),
+    ("D", "Nürnberg"), ("D", "Leipzig"), ("D", "Erfurt"), ("CH", "Lausanne"),
+    ("CH", "Zürich"), ("CH", "Adelboden"), ("CH", "Sion"), ("CH", "Glarus"),
+    ("CH", "Davos"), ("D", "Kassel"), ("CH", "Locarno"), ("I", "Sestriere"),
+    ("I", "Milano"), ("I", "Roma"), ("NL", "Amsterdam"), ("I", "Genova"),
+    ("I", "Venezia"), ("F", "Strasbourg"), ("A", "Klagenfurt"), ("A", "Innsbruck"),
+    ("A", "Salzburg"), ("SK", "Bratislava"), ("CZ", "Praha"), ("CZ", "Děčín"),
+    ("D", "Berlin"), ("S", "Göteborg"), ("S", "Stockholm"), ("S", "Kalmar"),
+    ("S", "Jönköping"), ("D", "Donaueschingen"), ("N", "Oslo"), ("D", "Stuttgart"),
+    ("I", "Napoli"), ("I", "Ancona"), ("I", "Bari"), ("H", "Budapest"),
+    ("E", "Madrid"), ("E", "Bilbao"), ("I", "Palermo"), ("E", "Palma de Mallorca"),
+    ("E", "Valencia"), ("E", "Barcelona"), ("AND", "Andorra"), ("E", "Sevilla"),
+    ("P", "Lisboa"), ("I", "Sassari"), ("E", "Gijón"), ("IRL", "Galway"),
+    ("IRL", "Dublin"), ("GB", "Glasgow"), ("N", "Stavanger"), ("N", "Trondheim"),
+    ("S", "Sundsvall"), ("PL", "Gdańsk"), ("PL", "Warszawa"), ("PL", "Kraków"),
+    ("S", "Umeå"), ("S", "Östersund"), ("CH", "Samedan"), ("HR", "Zagreb"),
+    ("CH", "Zermatt"), ("HR", "Split")
+];
+
+/// Cipher of the Meteotime blocks
+///
+/// Blocks and keys are 40 bit values, see `time_key` for the layout of the key.
+pub trait MeteotimeCipher {
+    /// Decrypts a block with the time key it was sent with
+    fn decrypt(&self, block: u64, key: u64) -> u64;
+    /// Encrypts a block with the time key it is sent with
+    fn encrypt(&self, block: u64, key: u64) -> u64;
+}
+
+/// Weather of a day or a night
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Weather {
+    /// Code 0, not used
+    Reserved,
+    /// Sunny or clear
+    Sunny,
+    /// Partly cloudy
+    PartlyCloudy,
+    /// Mostly cloudy
+    MostlyCloudy,
+    /// Overcast
+    Overcast,
+    /// Heat storms
+    HeatStorms,
+    /// Heavy rain
+    HeavyRain,
+    /// Snow
+    Snow,
+    /// Fog
+    Fog,
+    /// Sleet
+    Sleet,
+    /// Rain showers
+    RainShowers,
+    /// Light rain
+    LightRain,
+    /// Snow showers
+    SnowShowers,
+    /// Frontal storms
+    FrontalStorms,
+    /// Stratus clouds
+    StratusClouds,
+    /// Sleet storms
+    SleetStorms
+}
+
+/// Every weather, indexed by its code
+pub const WEATHERS: [Weather; 16] = [
+    Weather::Reserved, Weather::Sunny, Weather::PartlyCloudy, Weather::MostlyCloudy,
+    Weather::Overcast, Weather::HeatStorms, Weather::HeavyRain, Weather::Snow,
+    Weather::Fog, Weather::Sleet, Weather::RainShowers, Weather::LightRain,
+    Weather::SnowShowers, Weather::FrontalStorms, Weather::StratusClouds, Weather::SleetStorms
+];
+
+/// Extreme weather warning of the highs
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExtremeWeather {
+    /// No extreme weather
+    None,
+    /// Heavy weather during 24 hours
+    HeavyWeather,
+    /// Heavy weather during the day
+    HeavyWeatherDay,
+    /// Heavy weather during the night
+    HeavyWeatherNight,
+    /// Storm during 24 hours
+    Storm,
+    /// Storm during the day
+    StormDay,
+    /// Storm during the night
+    StormNight,
+    /// Wind gusts during the day
+    GustsDay,
+    /// Wind gusts during the night
+    GustsNight,
+    /// Icy rain in the morning
+    IcyRainMorning,
+    /// Icy rain in the afternoon
+    IcyRainAfternoon,
+    /// Icy rain during the night
+    IcyRainNight,
+    /// Fine dust
+    FineDust,
+    /// Ozone
+    Ozone,
+    /// Radiation
+    Radiation,
+    /// High water
+    HighWater
+}
+
+/// Every extreme weather, indexed by its code
+pub const EXTREME_WEATHERS: [ExtremeWeather; 16] = [
+    ExtremeWeather::None, ExtremeWeather::HeavyWeather, ExtremeWeather::HeavyWeatherDay, ExtremeWeather::HeavyWeatherNight,
+    ExtremeWeather::Storm, ExtremeWeather::StormDay, ExtremeWeather::StormNight, ExtremeWeather::GustsDay,
+    ExtremeWeather::GustsNight, ExtremeWeather::IcyRainMorning, ExtremeWeather::IcyRainAfternoon, ExtremeWeather::IcyRainNight,
+    ExtremeWeather::FineDust, ExtremeWeather::Ozone, ExtremeWeather::Radiation, ExtremeWeather::HighWater
+];
+
+/// Wind direction of the lows
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WindDirection {
+    /// North
+    North,
+    /// North-east
+    NorthEast,
+    /// East
+    East,
+    /// South-east
+    SouthEast,
+    /// South
+    South,
+    /// South-west
+    SouthWest,
+    /// West
+    West,
+    /// North-west
+    NorthWest,
+    /// Changeable
+    Changeable,
+    /// Foehn
+    Foehn,
+    /// Bise from the north-east
+    Bise,
+    /// Mistral from the north
+    Mistral,
+    /// Scirocco from the south
+    Scirocco,
+    /// Tramontana from the west
+    Tramontana
+}
+
+/// Every wind direction, indexed by its code, the codes 14 and 15 being reserved
+pub const WIND_DIRECTIONS: [WindDirection; 14] = [
+    WindDirection::North, WindDirection::NorthEast, WindDirection::East, WindDirection::SouthEast,
+    WindDirection::South, WindDirection::SouthWest, WindDirection::West, WindDirection::NorthWest,
+    WindDirection::Changeable, WindDirection::Foehn, WindDirection::Bise, WindDirection::Mistral,
+    WindDirection::Scirocco, WindDirection::Tramontana
+];
+
+/// Part of a forecast day sent in a packet
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ForecastPart {
+    /// Maximum temperature, extreme weather and precipitation probability
+    Highs,
+    /// Minimum temperature and wind
+    Lows
+}
+
+/// Region, day and part of the forecast sent in a packet
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Slot {
+    /// Index of the region in `REGIONS`
+    pub region: u8,
+    /// Forecast day, 1 being the day that starts after the packets of the day
+    pub day: u8,
+    /// Part of the forecast day
+    pub part: ForecastPart
+}
+
+/// Fields that depend on the part of the forecast day
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ForecastDetails {
+    /// Fields of the highs
+    Highs {
+        /// Extreme weather warning
+        extreme_weather: ExtremeWeather,
+        /// Precipitation probability in percent, one of `PRECIPITATION_PROBABILITIES`
+        precipitation: u8
+    },
+    /// Fields of the lows
+    Lows {
+        /// Wind direction
+        wind_direction: WindDirection,
+        /// Wind strength code, see `WIND_STRENGTHS`
+        wind_strength: u8
+    }
+}
+
+/// Decoded forecast of a region
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Forecast {
+    /// Region, day and part of the forecast
+    pub slot: Slot,
+    /// Weather during the day
+    pub day_weather: Weather,
+    /// Weather during the night
+    pub night_weather: Weather,
+    /// Fields of the highs or of the lows
+    pub details: ForecastDetails,
+    /// Weather anomaly
+    pub anomaly: bool,
+    /// Maximum (highs) or minimum (lows) temperature in degrees Celsius, from -22 to 41
+    pub temperature: i8
+}
+
+impl Forecast {
+    /// Country and name of the region of the forecast
+    pub fn region(&self) -> (&'static str, &'static str) {
+        REGIONS[usize::from(self.slot.region)]
+    }
+}
+
+/// Codes a value into BCD
+fn bcd(input: u32) -> u64 {
+    u64::from(((input / 10) << 4) | (input % 10))
+}
+
+/// Builds the 40 bit time key out of a decoded minute
+///
+/// From the least significant byte: minute, hour, day, month plus the day of
+/// the week in the upper three bits, and year, all in BCD.
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::*;
+///use dcf77_chrono::meteotime::*;
+///let dcf_data = DCF77 {
+///    date: Utc.with_ymd_and_hms(23, 6, 14, 8, 1, 0).unwrap(),
+///    antenna: false,
+///    announce_daily_saving_time: false,
+///    daily_saving_time: true,
+///    standard_time: false,
+///    bit_leap_second: false,
+///    third_party: 0
+///};
+///assert!(time_key(dcf_data) == 0x23_66_14_08_01);
+/// ```
+pub fn time_key(dcf_data: DCF77) -> u64 {
+    let local = to_utc(dcf_data).with_timezone(&utc_offset(dcf_data));
+    let month = bcd(local.month()) | u64::from(local.weekday().number_from_monday()) << 5;
+    bcd(local.minute()) |
+        bcd(local.hour()) << 8 |
+        bcd(local.day()) << 16 |
+        month << 24 |
+        bcd(local.year().rem_euclid(100) as u32) << 32
+}
+
+/// Slot of the packet whose transmission starts at a given instant
+pub fn slot(start: DateTime<Utc>) -> Slot {
+    let minutes = (start.hour() + 24 - DAY_START_HOUR) % 24 * 60 + start.minute();
+    let packet = minutes / 3 % PACKETS_PER_DAY;
+    let (region, kind) = if packet < LONG_FORECAST_PACKETS {
+        (packet % LONG_FORECAST_REGIONS, packet / LONG_FORECAST_REGIONS)
+    } else {
+        let packet = packet - LONG_FORECAST_PACKETS;
+        (LONG_FORECAST_REGIONS + packet % SHORT_FORECAST_REGIONS, packet / SHORT_FORECAST_REGIONS)
+    };
+    let part = if kind % 2 == 0 { ForecastPart::Highs } else { ForecastPart::Lows };
+    Slot {region: region as u8, day: (kind / 2 + 1) as u8, part}
+}
+
+/// Index of a slot among the packets of a day
+fn packet_of_slot(slot: Slot) -> Result<u32, Error> {
+    let region = u32::from(slot.region);
+    let kind = u32::from(slot.day.max(1) - 1) * 2 + u32::from(slot.part == ForecastPart::Lows);
+    let packet = if slot.day == 0 {
+        None
+    } else if region < LONG_FORECAST_REGIONS {
+        (kind < LONG_FORECAST_PACKETS / LONG_FORECAST_REGIONS).then_some(kind * LONG_FORECAST_REGIONS + region)
+    } else if region < LONG_FORECAST_REGIONS + SHORT_FORECAST_REGIONS {
+        (kind < (PACKETS_PER_DAY - LONG_FORECAST_PACKETS) / SHORT_FORECAST_REGIONS)
+            .then_some(LONG_FORECAST_PACKETS + kind * SHORT_FORECAST_REGIONS + region - LONG_FORECAST_REGIONS)
+    } else {
+        None
+    };
+    packet.ok_or_else(|| {
+        let error_payload = format!("Slot not broadcast - Region: {} Day: {} Part: {:?}", slot.region, slot.day, slot.part);
+        Error::new(ErrorKind::InvalidData, error_payload)
+    })
+}
+
+/// First instant at or after `after` at which the transmission of a slot starts
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::meteotime::*;
+///let after = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+///let berlin = Slot {region: 52, day: 1, part: ForecastPart::Highs};
+///assert!(slot_start(berlin, after).unwrap() == Utc.with_ymd_and_hms(2023, 6, 15, 0, 36, 0).unwrap());
+///assert!(slot(slot_start(berlin, after).unwrap()) == berlin);
+/// ```
+pub fn slot_start(slot: Slot, after: DateTime<Utc>) -> Result<DateTime<Utc>, Error> {
+    let packet = packet_of_slot(slot)?;
+    let day_start = after.date_naive().and_hms_opt(DAY_START_HOUR, 0, 0).unwrap().and_utc() - chrono::Duration::days(1);
+    let mut start = day_start + chrono::Duration::minutes(3 * i64::from(packet));
+    while start < after {
+        start += chrono::Duration::days(1);
+    }
+    Ok(start)
+}
+
+/// Codes the fields of a forecast into 24 bits
+pub fn code_forecast(forecast: &Forecast) -> Result<u32, Error> {
+    let invalid = |name: &str| {
+        let error_payload = format!("Invalid {} - Forecast: {:?}", name, forecast);
+        Error::new(ErrorKind::InvalidData, error_payload)
+    };
+    let (details, part) = match forecast.details {
+        ForecastDetails::Highs {extreme_weather, precipitation} => {
+            let extreme_weather = EXTREME_WEATHERS.iter().position(|other| *other == extreme_weather).unwrap();
+            let precipitation = PRECIPITATION_PROBABILITIES.iter().position(|other| *other == precipitation)
+                .ok_or_else(|| invalid("precipitation probability"))?;
+            (extreme_weather | precipitation << 4, ForecastPart::Highs)
+        }
+        ForecastDetails::Lows {wind_direction, wind_strength} => {
+            let wind_direction = WIND_DIRECTIONS.iter().position(|other| *other == wind_direction).unwrap();
+            if usize::from(wind_strength) >= WIND_STRENGTHS.len() {
+                return Err(invalid("wind strength"));
+            }
+            (wind_direction | usize::from(wind_strength) << 4, ForecastPart::Lows)
+        }
+    };
+    if part != forecast.slot.part {
+        return Err(invalid("part"));
+    }
+    let temperature = forecast.temperature.checked_add(TEMPERATURE_OFFSET)
+        .filter(|temperature| (0..64).contains(temperature))
+        .ok_or_else(|| invalid("temperature"))?;
+    let day_weather = WEATHERS.iter().position(|other| *other == forecast.day_weather).unwrap();
+    let night_weather = WEATHERS.iter().position(|other| *other == forecast.night_weather).unwrap();
+    Ok((day_weather |
+        night_weather << 4 |
+        details << 8 |
+        usize::from(forecast.anomaly) << 15 |
+        (temperature as usize) << 16) as u32)
+}
+
+/// Encrypts a 24 bit forecast into the three chunks of its packet
+pub fn encrypt(forecast: u32, key: u64, cipher: &impl MeteotimeCipher) -> [u16; CHUNKS_PER_PACKET] {
+    let block = (u64::from(forecast) & FORECAST_MASK) << CHECK_BITS | CHECK_VALUE;
+    let encrypted = cipher.encrypt(block, key & BLOCK_MASK) & BLOCK_MASK;
+    split_packet(encrypted << UNUSED_PACKET_BITS)
+}
+
+/// Places a forecast in the three minutes of the first transmission of its slot at or after `after`
+///
+/// The minutes are those of the broadcast schedule, their telegrams carrying
+/// the chunks of the encrypted forecast.
+pub fn encode_packet(forecast: &Forecast, after: DateTime<Utc>, cipher: &impl MeteotimeCipher) -> Result<[ScheduledMinute; CHUNKS_PER_PACKET], Error> {
+    let start = slot_start(forecast.slot, after)?;
+    let minutes: Vec<ScheduledMinute> = Schedule::new(start + chrono::Duration::minutes(1), start + chrono::Duration::minutes(4)).collect();
+    let chunks = encrypt(code_forecast(forecast)?, time_key(minutes[0].dcf), cipher);
+    let mut output = Vec::new();
+    for (minute, chunk) in minutes.into_iter().zip(chunks) {
+        output.push(minute.with_third_party(chunk)?);
+    }
+    Ok(output.try_into().unwrap())
+}
+
+/// Decrypts the block of three chunks, giving the 24 bit forecast
+pub fn decrypt(chunks: [u16; 3], key: u64, cipher: &impl MeteotimeCipher) -> Result<u32, Error> {
+    let block = join_chunks(chunks) >> UNUSED_PACKET_BITS;
+    let decrypted = cipher.decrypt(block, key & BLOCK_MASK) & BLOCK_MASK;
+    if decrypted & ((1 << CHECK_BITS) - 1) != CHECK_VALUE {
+        let error_payload = format!("Invalid Meteotime check value - Block: 0x{:010X} Key: 0x{:010X} Decrypted: 0x{:010X}", block, key, decrypted);
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    Ok(((decrypted >> CHECK_BITS) & FORECAST_MASK) as u32)
+}
+
+/// Decodes the fields of a decrypted forecast sent in a slot
+///
+/// # Examples
+/// ```
+///use dcf77_chrono::meteotime::*;
+///let slot = Slot {region: 52, day: 1, part: ForecastPart::Highs};
+///let forecast = decode_forecast(0x28_3481, slot).unwrap();
+///assert!(forecast.region() == ("D", "Berlin"));
+///assert!(forecast.day_weather == Weather::Sunny);
+///assert!(forecast.night_weather == Weather::Fog);
+///assert!(forecast.temperature == 18);
+/// ```
+pub fn decode_forecast(input: u32, slot: Slot) -> Result<Forecast, Error> {
+    let field = |position: u32, bits: u32| ((input >> position) & ((1 << bits) - 1)) as usize;
+    let details = match slot.part {
+        ForecastPart::Highs => ForecastDetails::Highs {
+            extreme_weather: EXTREME_WEATHERS[field(8, 4)],
+            precipitation: PRECIPITATION_PROBABILITIES[field(12, 3)]
+        },
+        ForecastPart::Lows => {
+            let Some(wind_direction) = WIND_DIRECTIONS.get(field(8, 4)) else {
+                let error_payload = format!("Reserved wind direction - Forecast: 0x{:06X}", input);
+                return Err(Error::new(ErrorKind::InvalidData, error_payload));
+            };
+            ForecastDetails::Lows {wind_direction: *wind_direction, wind_strength: field(12, 3) as u8}
+        }
+    };
+    Ok(Forecast {
+        slot,
+        day_weather: WEATHERS[field(0, 4)],
+        night_weather: WEATHERS[field(4, 4)],
+        details,
+        anomaly: field(15, 1) == 1,
+        temperature: field(16, 6) as i8 - TEMPERATURE_OFFSET
+    })
+}
+
+/// Decrypts and decodes the forecast of a third party packet
+///
+/// The key is taken from the first minute of the packet and the slot from
+/// the instant its transmission started.
+pub fn decode_packet(packet: &ThirdPartyPacket, cipher: &impl MeteotimeCipher) -> Result<Forecast, Error> {
+    let forecast = decrypt(packet.chunks, time_key(packet.minutes[0]), cipher)?;
+    decode_forecast(forecast, slot(packet.start()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_dcf77, to_dcf77};
+    use crate::schedule::Schedule;
+    use crate::third_party::{ThirdPartyAggregator, split_packet};
+    /// Stand-in for the Meteotime cipher
+    struct XorCipher;
+    impl MeteotimeCipher for XorCipher {
+        fn decrypt(&self, block: u64, key: u64) -> u64 {
+            block ^ key
+        }
+        fn encrypt(&self, block: u64, key: u64) -> u64 {
+            block ^ key
+        }
+    }
+    #[test]
+    fn test_time_key() {
+        // 2023-10-29 02:59 CEST and then 02:00 CET, a Sunday
+        let from = Utc.with_ymd_and_hms(2023, 10, 29, 0, 59, 0).unwrap();
+        let keys: Vec<u64> = Schedule::new(from, from + chrono::Duration::minutes(3)).map(|minute| time_key(minute.dcf)).collect();
+        assert!(keys == [0x23_F0_29_02_59, 0x23_F0_29_02_00, 0x23_F0_29_02_01]);
+        // Decoded years keep their two digits and the day of the week is not broadcast
+        let decoded_minutes = from_dcf77(to_dcf77(Schedule::new(from, from + chrono::Duration::minutes(1)).next().unwrap().dcf).unwrap()).unwrap();
+        assert!(time_key(decoded_minutes) == keys[0]);
+    }
+    #[test]
+    fn test_slot() {
+        let at = |hour, minute| Utc.with_ymd_and_hms(2023, 6, 14, hour, minute, 0).unwrap();
+        assert!(slot(at(22, 0)) == Slot {region: 0, day: 1, part: ForecastPart::Highs});
+        assert!(slot(at(22, 3)) == Slot {region: 1, day: 1, part: ForecastPart::Highs});
+        assert!(slot(at(1, 0)) == Slot {region: 0, day: 1, part: ForecastPart::Lows});
+        assert!(slot(at(6, 57)) == Slot {region: 59, day: 2, part: ForecastPart::Highs});
+        assert!(slot(at(15, 57)) == Slot {region: 59, day: 3, part: ForecastPart::Lows});
+        assert!(slot(at(16, 0)) == Slot {region: 60, day: 1, part: ForecastPart::Highs});
+        assert!(slot(at(21, 57)) == Slot {region: 89, day: 2, part: ForecastPart::Lows});
+        let days: Vec<Slot> = (0..PACKETS_PER_DAY).map(|packet| slot(at(22, 0) + chrono::Duration::minutes(3 * i64::from(packet)))).collect();
+        assert!((0..90).all(|region| days.iter().filter(|slot| slot.region == region).count() == if region < 60 { 6 } else { 4 }));
+    }
+    #[test]
+    fn test_decode_forecast() {
+        let highs = decode_forecast(0x28_3481, Slot {region: 52, day: 1, part: ForecastPart::Highs}).unwrap();
+        assert!(highs.details == ForecastDetails::Highs {extreme_weather: ExtremeWeather::Storm, precipitation: 45});
+        assert!(!highs.anomaly);
+        let lows = decode_forecast(0x01_F5C4, Slot {region: 26, day: 2, part: ForecastPart::Lows}).unwrap();
+        assert!(lows.region() == ("D", "München"));
+        assert!(lows.day_weather == Weather::Overcast);
+        assert!(lows.night_weather == Weather::SnowShowers);
+        assert!(lows.details == ForecastDetails::Lows {wind_direction: WindDirection::SouthWest, wind_strength: 7});
+        assert!(lows.anomaly);
+        assert!(lows.temperature == -21);
+        assert!(decode_forecast(0x01_F5C4, Slot {region: 26, day: 2, part: ForecastPart::Highs}).is_ok());
+        assert!(decode_forecast(0x01_FEC4, Slot {region: 26, day: 2, part: ForecastPart::Lows}).is_err());
+    }
+    #[test]
+    fn test_code_forecast() {
+        for (input, part) in [(0x28_3481, ForecastPart::Highs), (0x01_F5C4, ForecastPart::Lows), (0x3F_0000, ForecastPart::Highs)] {
+            let forecast = decode_forecast(input, Slot {region: 89, day: 2, part}).unwrap();
+            assert!(code_forecast(&forecast).unwrap() == input);
+        }
+        let mut forecast = decode_forecast(0x28_3481, Slot {region: 12, day: 3, part: ForecastPart::Highs}).unwrap();
+        forecast.temperature = 42;
+        assert!(code_forecast(&forecast).is_err());
+        forecast.temperature = -22;
+        assert!(code_forecast(&forecast).is_ok());
+        forecast.details = ForecastDetails::Highs {extreme_weather: ExtremeWeather::None, precipitation: 50};
+        assert!(code_forecast(&forecast).is_err());
+        forecast.details = ForecastDetails::Lows {wind_direction: WindDirection::Mistral, wind_strength: 3};
+        assert!(code_forecast(&forecast).is_err());
+    }
+    #[test]
+    fn test_slot_start() {
+        let after = Utc.with_ymd_and_hms(2023, 6, 14, 22, 1, 0).unwrap();
+        let bordeaux = Slot {region: 0, day: 1, part: ForecastPart::Highs};
+        assert!(slot_start(bordeaux, after).unwrap() == Utc.with_ymd_and_hms(2023, 6, 15, 22, 0, 0).unwrap());
+        assert!(slot_start(bordeaux, after - chrono::Duration::minutes(1)).unwrap() == after - chrono::Duration::minutes(1));
+        let split = Slot {region: 89, day: 2, part: ForecastPart::Lows};
+        assert!(slot_start(split, after).unwrap() == Utc.with_ymd_and_hms(2023, 6, 15, 21, 57, 0).unwrap());
+        assert!(slot_start(Slot {region: 89, day: 3, part: ForecastPart::Highs}, after).is_err());
+        assert!(slot_start(Slot {region: 90, day: 1, part: ForecastPart::Highs}, after).is_err());
+        assert!(slot_start(Slot {region: 0, day: 0, part: ForecastPart::Highs}, after).is_err());
+    }
+    #[test]
+    fn test_encrypt() {
+        // Check value in the lower 16 bits, key as given and unused bits left at 0
+        assert!(encrypt(0x28_3481, 0x23_66_14_08_01, &XorCipher) == [0x2D4, 0x2952, 0x3400]);
+        assert!(encrypt(0xFF28_3481, 0xFF23_6614_0801, &XorCipher) == [0x2D4, 0x2952, 0x3400]);
+    }
+    #[test]
+    fn test_encode_packet() {
+        let slot = Slot {region: 52, day: 2, part: ForecastPart::Lows};
+        let forecast = decode_forecast(0x01_F5C4, slot).unwrap();
+        let after = Utc.with_ymd_and_hms(2023, 10, 28, 12, 0, 0).unwrap();
+        let minutes = encode_packet(&forecast, after, &XorCipher).unwrap();
+        assert!(minutes[0].utc == slot_start(slot, after).unwrap() + chrono::Duration::minutes(1));
+        let mut aggregator = ThirdPartyAggregator::new();
+        let mut packets = Vec::new();
+        for minute in minutes {
+            packets.extend(aggregator.push(from_dcf77(minute.frame).unwrap()));
+        }
+        assert!(packets.len() == 1);
+        assert!(decode_packet(&packets[0], &XorCipher).unwrap() == forecast);
+    }
+    #[test]
+    fn test_decode_packet() {
+        let from = Utc.with_ymd_and_hms(2023, 6, 14, 22, 1, 0).unwrap();
+        let minutes: Vec<DCF77> = Schedule::new(from, from + chrono::Duration::minutes(3)).map(|minute| minute.dcf).collect();
+        // Block of the highs of Bordeaux sent from 22:00 UTC
+        let block = (0x28_3481 << CHECK_BITS | CHECK_VALUE) ^ time_key(minutes[0]);
+        let mut aggregator = ThirdPartyAggregator::new();
+        let mut packets = Vec::new();
+        // The unused bits at the end of the packet are ignored
+        for (minute, chunk) in minutes.iter().zip(split_packet(block << UNUSED_PACKET_BITS | 0b11)) {
+            packets.extend(aggregator.push(DCF77 {third_party: chunk, ..*minute}));
+        }
+        assert!(packets.len() == 1);
+        let forecast = decode_packet(&packets[0], &XorCipher).unwrap();
+        assert!(forecast.region() == ("F", "Bordeaux"));
+        assert!(forecast.slot == Slot {region: 0, day: 1, part: ForecastPart::Highs});
+        assert!(forecast.temperature == 18);
+        // A wrong key does not give the check value, nor does a check value out of the lower 16 bits
+        assert!(decrypt(packets[0].chunks, time_key(minutes[1]), &XorCipher).is_err());
+        let shifted = split_packet(((CHECK_VALUE << CHECK_BITS) ^ time_key(minutes[0])) << UNUSED_PACKET_BITS);
+        assert!(decrypt(shifted, time_key(minutes[0]), &XorCipher).is_err());
+    }
+}
@@ -0,0 +1,265 @@
+//! Translation of decoded DCF77 minutes into NMEA 0183 ZDA and RMC sentences
+//!
+//! The sentences are generated with the `GP` talker identifier so that they
+//! are understood by software that only knows GPS receivers. The time of a
+//! sentence is the one of the decoded minute plus the time elapsed since its
+//! minute marker, translated to UTC with `to_utc`.
+//!
+//! The ZDA local zone fields follow NMEA 0183: they are the hours and minutes
+//! that have to be added to the local time to obtain UTC, so CET is `-01,00`.
+//!
+//! Sentences of any talker can be parsed back to compare the DCF77 time with
+//! the time given by a GPS receiver.
+
+use std::io::Error;
+use std::io::ErrorKind;
+use chrono::prelude::*;
+use crate::{DCF77, to_utc, utc_offset};
+
+/// Time information parsed out of a ZDA or RMC sentence
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NmeaTime {
+    /// Date in UTC timezone
+    pub date: DateTime<Utc>,
+    /// Offset of the local time to UTC, only given by ZDA sentences
+    pub utc_offset: Option<FixedOffset>
+}
+
+/// Computes the checksum of an NMEA sentence, the XOR of the characters between `$` and `*`
+pub fn checksum(input: &str) -> u8 {
+    input.bytes().fold(0, |output, byte| output ^ byte)
+}
+
+/// Adds the `$`, the checksum and the line ending to the body of a sentence
+fn finish_sentence(body: &str) -> String {
+    format!("${}*{:02X}\r\n", body, checksum(body))
+}
+
+/// Gives the UTC instant of a decoded minute some time after its marker
+fn sentence_time(dcf_data: DCF77, since_marker: chrono::Duration) -> DateTime<Utc> {
+    to_utc(dcf_data) + since_marker
+}
+
+/// Creates a `$GPZDA` sentence out of a decoded DCF77 minute
+///
+/// `since_marker` is the time elapsed since the minute marker of the decoded
+/// minute.
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::*;
+///use dcf77_chrono::nmea::*;
+///let test_time = DCF77 {
+///    date: Utc.with_ymd_and_hms(2023, 2, 1, 13, 34, 0).unwrap(),
+///    antenna: false,
+///    announce_daily_saving_time: false,
+///    daily_saving_time: false,
+///    standard_time: true,
+///    bit_leap_second: false,
+///    third_party: 0
+///};
+///let sentence = to_zda(test_time, chrono::Duration::milliseconds(56_500));
+///assert!(sentence == "$GPZDA,123456.50,01,02,2023,-01,00*48\r\n");
+/// ```
+pub fn to_zda(dcf_data: DCF77, since_marker: chrono::Duration) -> String {
+    let date = sentence_time(dcf_data, since_marker);
+    let offset_minutes = utc_offset(dcf_data).local_minus_utc() / 60;
+    let body = format!("GPZDA,{},{:02},{:02},{:04},{}{:02},{:02}",
+                       format_time(date),
+                       date.day(),
+                       date.month(),
+                       date.year(),
+                       if offset_minutes > 0 { '-' } else { '+' },
+                       offset_minutes.abs() / 60,
+                       offset_minutes.abs() % 60);
+    finish_sentence(&body)
+}
+
+/// Creates a `$GPRMC` sentence out of a decoded DCF77 minute
+///
+/// `since_marker` is the time elapsed since the minute marker of the decoded
+/// minute. The position, speed and course fields are left empty.
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::*;
+///use dcf77_chrono::nmea::*;
+///let test_time = DCF77 {
+///    date: Utc.with_ymd_and_hms(2023, 2, 1, 13, 34, 0).unwrap(),
+///    antenna: false,
+///    announce_daily_saving_time: false,
+///    daily_saving_time: false,
+///    standard_time: true,
+///    bit_leap_second: false,
+///    third_party: 0
+///};
+///let sentence = to_rmc(test_time, chrono::Duration::seconds(56));
+///assert!(parse(&sentence).unwrap().date == Utc.with_ymd_and_hms(2023, 2, 1, 12, 34, 56).unwrap());
+/// ```
+pub fn to_rmc(dcf_data: DCF77, since_marker: chrono::Duration) -> String {
+    let date = sentence_time(dcf_data, since_marker);
+    let body = format!("GPRMC,{},A,,,,,,,{:02}{:02}{:02},,",
+                       format_time(date),
+                       date.day(),
+                       date.month(),
+                       date.year().rem_euclid(100));
+    finish_sentence(&body)
+}
+
+/// Formats the `hhmmss.ss` time field of a sentence
+fn format_time(date: DateTime<Utc>) -> String {
+    format!("{:02}{:02}{:02}.{:02}",
+            date.hour(),
+            date.minute(),
+            date.second(),
+            date.timestamp_subsec_millis() / 10)
+}
+
+/// Creates the error returned for a malformed sentence
+fn invalid_sentence(input: &str) -> Error {
+    let error_payload = format!("Invalid NMEA sentence: {}", input.trim_end());
+    Error::new(ErrorKind::InvalidData, error_payload)
+}
+
+/// Parses a number out of a field of a sentence
+fn parse_field<T: std::str::FromStr>(field: Option<&&str>, input: &str) -> Result<T, Error> {
+    match field {
+        Some(field) => field.parse().map_err(|_| invalid_sentence(input)),
+        None => Err(invalid_sentence(input))
+    }
+}
+
+/// Parses the `hhmmss.ss` time field of a sentence
+fn parse_time(field: Option<&&str>, input: &str) -> Result<NaiveTime, Error> {
+    match field {
+        Some(field) if field.len() >= 6 && field.is_ascii() => {
+            let hour: u32 = field[0..2].parse().map_err(|_| invalid_sentence(input))?;
+            let minute: u32 = field[2..4].parse().map_err(|_| invalid_sentence(input))?;
+            let seconds: f64 = field[4..].parse().map_err(|_| invalid_sentence(input))?;
+            let nanoseconds = ((seconds.fract() * 1_000_000_000.0).round() as u32).min(999_999_999);
+            NaiveTime::from_hms_nano_opt(hour, minute, seconds.trunc() as u32, nanoseconds)
+                .ok_or_else(|| invalid_sentence(input))
+        }
+        _ => Err(invalid_sentence(input))
+    }
+}
+
+/// Parses a ZDA or RMC sentence of any talker
+///
+/// The checksum is verified when present. RMC sentences flagged as not valid
+/// (status `V`) are rejected.
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::nmea::*;
+///let parsed = parse("$GNZDA,101530.00,29,10,2023,-01,00*5B").unwrap();
+///assert!(parsed.date == Utc.with_ymd_and_hms(2023, 10, 29, 10, 15, 30).unwrap());
+///assert!(parsed.utc_offset.unwrap().local_minus_utc() == 3600);
+/// ```
+pub fn parse(input: &str) -> Result<NmeaTime, Error> {
+    let sentence = input.trim_end();
+    let body = match sentence.strip_prefix('$') {
+        Some(body) => body,
+        None => return Err(invalid_sentence(input))
+    };
+    let body = match body.split_once('*') {
+        Some((body, given_checksum)) => {
+            match u8::from_str_radix(given_checksum, 16) {
+                Ok(given_checksum) if given_checksum == checksum(body) => body,
+                _ => {
+                    let error_payload = format!("Invalid NMEA checksum: {}", sentence);
+                    return Err(Error::new(ErrorKind::InvalidData, error_payload));
+                }
+            }
+        }
+        None => body
+    };
+    let fields: Vec<&str> = body.split(',').collect();
+    let address = fields[0];
+    if address.len() != 5 || !address.is_ascii() {
+        return Err(invalid_sentence(input));
+    }
+    match &address[2..] {
+        "ZDA" => {
+            let time = parse_time(fields.get(1), input)?;
+            let day: u32 = parse_field(fields.get(2), input)?;
+            let month: u32 = parse_field(fields.get(3), input)?;
+            let year: i32 = parse_field(fields.get(4), input)?;
+            let date = NaiveDate::from_ymd_opt(year, month, day).ok_or_else(|| invalid_sentence(input))?;
+            let utc_offset = match (fields.get(5), fields.get(6)) {
+                (Some(hours), Some(minutes)) if !hours.is_empty() && !minutes.is_empty() => {
+                    let negative = hours.starts_with('-');
+                    let hours: i32 = parse_field(Some(hours), input)?;
+                    let minutes: i32 = parse_field(Some(minutes), input)?;
+                    let offset = hours.abs() * 3600 + minutes * 60;
+                    FixedOffset::east_opt(if negative { offset } else { -offset })
+                }
+                _ => None
+            };
+            Ok(NmeaTime {date: Utc.from_utc_datetime(&date.and_time(time)), utc_offset})
+        }
+        "RMC" => {
+            let time = parse_time(fields.get(1), input)?;
+            if fields.get(2) != Some(&"A") {
+                let error_payload = format!("NMEA sentence not valid: {}", sentence);
+                return Err(Error::new(ErrorKind::InvalidData, error_payload));
+            }
+            let date_field = fields.get(9).ok_or_else(|| invalid_sentence(input))?;
+            if date_field.len() != 6 || !date_field.is_ascii() {
+                return Err(invalid_sentence(input));
+            }
+            let day: u32 = date_field[0..2].parse().map_err(|_| invalid_sentence(input))?;
+            let month: u32 = date_field[2..4].parse().map_err(|_| invalid_sentence(input))?;
+            let year: i32 = date_field[4..6].parse().map_err(|_| invalid_sentence(input))?;
+            let date = NaiveDate::from_ymd_opt(2000 + year, month, day).ok_or_else(|| invalid_sentence(input))?;
+            Ok(NmeaTime {date: Utc.from_utc_datetime(&date.and_time(time)), utc_offset: None})
+        }
+        _ => Err(invalid_sentence(input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_zda_round_trip() {
+        let original_test_time = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        for fake_input in 0..=0xff {
+            let test_time = DCF77 {
+                date: original_test_time + chrono::Duration::minutes(fake_input * 1_234),
+                antenna: false,
+                announce_daily_saving_time: false,
+                daily_saving_time: fake_input % 2 == 0,
+                standard_time: fake_input % 2 == 1,
+                bit_leap_second: false,
+                third_party: 0
+            };
+            let since_marker = chrono::Duration::milliseconds(fake_input * 230);
+            let parsed = parse(&to_zda(test_time, since_marker)).unwrap();
+            assert!(parsed.date == to_utc(test_time) + since_marker);
+            assert!(parsed.utc_offset == Some(utc_offset(test_time)));
+            let parsed = parse(&to_rmc(test_time, since_marker)).unwrap();
+            assert!(parsed.date == to_utc(test_time) + since_marker);
+            assert!(parsed.utc_offset.is_none());
+        }
+    }
+    #[test]
+    fn test_known_sentences() {
+        let parsed = parse("$GPRMC,123519.00,A,4807.038,N,01131.000,E,022.4,084.4,230323,003.1,W*48").unwrap();
+        assert!(parsed.date == Utc.with_ymd_and_hms(2023, 3, 23, 12, 35, 19).unwrap());
+        let parsed = parse("$GPZDA,201530.00,04,07,2002,00,00*60").unwrap();
+        assert!(parsed.date == Utc.with_ymd_and_hms(2002, 7, 4, 20, 15, 30).unwrap());
+        assert!(parsed.utc_offset.unwrap().local_minus_utc() == 0);
+    }
+    #[test]
+    fn test_invalid_sentences() {
+        assert!(parse("$GPZDA,201530.00,04,07,2002,00,00*61").is_err());
+        assert!(parse("GPZDA,201530.00,04,07,2002,00,00").is_err());
+        assert!(parse("$GPRMC,123519.00,V,,,,,,,230394,,").is_err());
+        assert!(parse("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,").is_err());
+        assert!(parse("$GPZDA,2015,04,07,2002,00,00").is_err());
+    }
+}
@@ -0,0 +1,261 @@
+//! Minimal NTPv4 server backed by the DCF77 holdover clock
+//!
+//! The server answers client (mode 3) requests as a stratum 1 server with the
+//! reference identifier `DCF`. The leap indicator is taken from the leap
+//! second announcement bit of the last decoded minute until the leap second
+//! has passed, and the root dispersion
+//! is the error estimate of the holdover clock, so it grows while no new
+//! minute is decoded. Before the first decoded minute the server answers with
+//! the alarm leap indicator and stratum 16, so that clients ignore it.
+//!
+//! Only available with the `ntp-server` feature.
+
+use std::io::Error;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use chrono::prelude::*;
+use crate::holdover::HoldoverClock;
+
+/// Size of an NTP packet without extension fields
+pub const NTP_PACKET_SIZE: usize = 48;
+
+/// Seconds between the NTP era (1900) and the Unix epoch (1970)
+const NTP_UNIX_OFFSET: i64 = 2_208_988_800;
+
+/// Reference identifier of the server
+const REFERENCE_ID: [u8; 4] = *b"DCF\0";
+
+/// Stratum of a server synchronized by a reference clock
+const STRATUM_PRIMARY: u8 = 1;
+
+/// Stratum of a server that is not synchronized
+const STRATUM_UNSYNCHRONIZED: u8 = 16;
+
+/// Precision of the server as a power of two in seconds, about 8 ms
+const PRECISION: i8 = -7;
+
+/// Leap indicator: no leap second announced
+const LEAP_NONE: u8 = 0;
+
+/// Leap indicator: a leap second will be inserted at the end of the day
+const LEAP_INSERT: u8 = 1;
+
+/// Leap indicator: the clock is not synchronized
+const LEAP_ALARM: u8 = 3;
+
+/// Mode of a client request
+const MODE_CLIENT: u8 = 3;
+
+/// Mode of a server response
+const MODE_SERVER: u8 = 4;
+
+/// Translates a UTC time into an NTP timestamp
+pub fn to_ntp_timestamp(input: DateTime<Utc>) -> u64 {
+    let seconds = (input.timestamp() + NTP_UNIX_OFFSET) as u64;
+    let fraction = (u64::from(input.timestamp_subsec_nanos()) << 32) / 1_000_000_000;
+    (seconds << 32) | fraction
+}
+
+/// Translates an NTP timestamp of the current era into a UTC time
+pub fn from_ntp_timestamp(input: u64) -> DateTime<Utc> {
+    let seconds = (input >> 32) as i64 - NTP_UNIX_OFFSET;
+    let nanoseconds = ((input & 0xFFFF_FFFF) * 1_000_000_000) >> 32;
+    DateTime::from_timestamp(seconds, nanoseconds as u32).unwrap()
+}
+
+/// Translates a duration into the NTP short format (16.16 seconds), saturating
+fn to_ntp_short(input: Duration) -> u32 {
+    let value = (input.as_nanos() << 16) / 1_000_000_000;
+    value.min(u128::from(u32::MAX)) as u32
+}
+
+/// Creates the response to a client request
+///
+/// `receive` and `transmit` are the local instants at which the request was
+/// received and the response is sent.
+pub fn build_response(request: &[u8], clock: &HoldoverClock, receive: Instant, transmit: Instant) -> Result<[u8; NTP_PACKET_SIZE], Error> {
+    if request.len() < NTP_PACKET_SIZE {
+        let error_payload = format!("NTP request too short: {} bytes", request.len());
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    let version = (request[0] >> 3) & 0x7;
+    let mode = request[0] & 0x7;
+    if mode != MODE_CLIENT || !(1..=4).contains(&version) {
+        let error_payload = format!("Not an NTP client request: version {} mode {}", version, mode);
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    let mut response = [0; NTP_PACKET_SIZE];
+    let transmit_time = clock.time_at(transmit);
+    let leap_second_ahead = clock.leap_second().zip(transmit_time).is_some_and(|(leap_second, time)| time < leap_second);
+    let (leap, stratum) = if !clock.is_synchronized() {
+        (LEAP_ALARM, STRATUM_UNSYNCHRONIZED)
+    } else if leap_second_ahead {
+        (LEAP_INSERT, STRATUM_PRIMARY)
+    } else {
+        (LEAP_NONE, STRATUM_PRIMARY)
+    };
+    response[0] = (leap << 6) | (version << 3) | MODE_SERVER;
+    response[1] = stratum;
+    response[2] = request[2];
+    response[3] = PRECISION as u8;
+    let root_dispersion = clock.error_estimate_at(transmit).map(to_ntp_short).unwrap_or(u32::MAX);
+    response[8..12].copy_from_slice(&root_dispersion.to_be_bytes());
+    response[12..16].copy_from_slice(&REFERENCE_ID);
+    if let Some(reference_time) = clock.reference_time() {
+        response[16..24].copy_from_slice(&to_ntp_timestamp(reference_time).to_be_bytes());
+    }
+    response[24..32].copy_from_slice(&request[40..48]);
+    if let (Some(receive_time), Some(transmit_time)) = (clock.time_at(receive), transmit_time) {
+        response[32..40].copy_from_slice(&to_ntp_timestamp(receive_time).to_be_bytes());
+        response[40..48].copy_from_slice(&to_ntp_timestamp(transmit_time).to_be_bytes());
+    }
+    Ok(response)
+}
+
+/// NTP server answering requests over UDP with the time of a holdover clock
+#[derive(Debug)]
+pub struct NtpServer {
+    socket: UdpSocket,
+    clock: Arc<Mutex<HoldoverClock>>
+}
+
+impl NtpServer {
+    /// Binds the server to a local address
+    ///
+    /// The clock is shared so that it can keep being synchronized while the
+    /// server runs.
+    pub fn bind<A: ToSocketAddrs>(address: A, clock: Arc<Mutex<HoldoverClock>>) -> Result<NtpServer, Error> {
+        Ok(NtpServer {socket: UdpSocket::bind(address)?, clock})
+    }
+
+    /// Local address the server is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        self.socket.local_addr()
+    }
+
+    /// Waits for one datagram and answers it when it is a client request
+    pub fn serve_one(&self) -> Result<(), Error> {
+        let mut request = [0; 1024];
+        let (size, source) = self.socket.recv_from(&mut request)?;
+        let receive = Instant::now();
+        let clock = *self.clock.lock().map_err(|_| Error::new(ErrorKind::Other, "Holdover clock lock poisoned"))?;
+        let response = build_response(&request[..size], &clock, receive, Instant::now())?;
+        self.socket.send_to(&response, source)?;
+        Ok(())
+    }
+
+    /// Answers requests forever, ignoring the datagrams that are not client requests
+    pub fn serve(&self) -> Result<(), Error> {
+        loop {
+            match self.serve_one() {
+                Ok(()) => {}
+                Err(error) if error.kind() == ErrorKind::InvalidData => {}
+                Err(error) => return Err(error)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DCF77;
+    use crate::holdover::DCF77_MARKER_PRECISION;
+    fn client_request(transmit: u64) -> [u8; NTP_PACKET_SIZE] {
+        let mut request = [0; NTP_PACKET_SIZE];
+        request[0] = (4 << 3) | MODE_CLIENT;
+        request[2] = 6;
+        request[40..48].copy_from_slice(&transmit.to_be_bytes());
+        request
+    }
+    #[test]
+    fn test_timestamps() {
+        let test_time = Utc.with_ymd_and_hms(2023, 2, 1, 12, 34, 56).unwrap() + chrono::Duration::milliseconds(500);
+        let timestamp = to_ntp_timestamp(test_time);
+        assert!(timestamp >> 32 == 3_884_243_696);
+        assert!(timestamp & 0xFFFF_FFFF == 0x8000_0000);
+        assert!(from_ntp_timestamp(timestamp) == test_time);
+    }
+    #[test]
+    fn test_unsynchronized() {
+        let now = Instant::now();
+        let response = build_response(&client_request(1), &HoldoverClock::default(), now, now).unwrap();
+        assert!(response[0] >> 6 == LEAP_ALARM);
+        assert!(response[1] == STRATUM_UNSYNCHRONIZED);
+        assert!(build_response(&client_request(1)[..40], &HoldoverClock::default(), now, now).is_err());
+        let mut server_packet = client_request(1);
+        server_packet[0] = (4 << 3) | MODE_SERVER;
+        assert!(build_response(&server_packet, &HoldoverClock::default(), now, now).is_err());
+    }
+    #[test]
+    fn test_server() {
+        let test_time = DCF77 {
+            date: Utc.with_ymd_and_hms(2017, 1, 1, 0, 30, 0).unwrap(),
+            antenna: false,
+            announce_daily_saving_time: false,
+            daily_saving_time: false,
+            standard_time: true,
+            bit_leap_second: true,
+            third_party: 0
+        };
+        let mut clock = HoldoverClock::default();
+        clock.synchronize(test_time, Instant::now());
+        let clock = Arc::new(Mutex::new(clock));
+        let server = NtpServer::bind("127.0.0.1:0", clock).unwrap();
+        let address = server.local_addr().unwrap();
+        let handle = std::thread::spawn(move || server.serve_one());
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        client.send_to(&client_request(0x0123_4567_89AB_CDEF), address).unwrap();
+        let mut response = [0; 1024];
+        let (size, _) = client.recv_from(&mut response).unwrap();
+        handle.join().unwrap().unwrap();
+        assert!(size == NTP_PACKET_SIZE);
+        assert!(response[0] >> 6 == LEAP_INSERT);
+        assert!((response[0] >> 3) & 0x7 == 4);
+        assert!(response[0] & 0x7 == MODE_SERVER);
+        assert!(response[1] == STRATUM_PRIMARY);
+        assert!(response[2] == 6);
+        assert!(response[12..16] == *b"DCF\0");
+        assert!(response[24..32] == 0x0123_4567_89AB_CDEF_u64.to_be_bytes());
+        let root_dispersion = u32::from_be_bytes(response[8..12].try_into().unwrap());
+        assert!(root_dispersion >= to_ntp_short(DCF77_MARKER_PRECISION));
+        let reference_time = from_ntp_timestamp(u64::from_be_bytes(response[16..24].try_into().unwrap()));
+        assert!(reference_time == Utc.with_ymd_and_hms(2016, 12, 31, 23, 30, 0).unwrap());
+        let transmit_time = from_ntp_timestamp(u64::from_be_bytes(response[40..48].try_into().unwrap()));
+        let elapsed = transmit_time - reference_time;
+        assert!(elapsed >= chrono::Duration::zero() && elapsed < chrono::Duration::seconds(5));
+    }
+    #[test]
+    fn test_leap_second() {
+        // Leap second at 2017-01-01 00:00 UTC, 30 minutes after the decoded minute
+        let test_time = DCF77 {
+            date: Utc.with_ymd_and_hms(2017, 1, 1, 0, 30, 0).unwrap(),
+            antenna: false,
+            announce_daily_saving_time: false,
+            daily_saving_time: false,
+            standard_time: true,
+            bit_leap_second: true,
+            third_party: 0
+        };
+        let now = Instant::now();
+        let mut clock = HoldoverClock::default();
+        clock.synchronize(test_time, now);
+        let before = now + Duration::from_secs(100);
+        let response = build_response(&client_request(1), &clock, before, before).unwrap();
+        assert!(response[0] >> 6 == LEAP_INSERT);
+        let transmit_time = from_ntp_timestamp(u64::from_be_bytes(response[40..48].try_into().unwrap()));
+        assert!(transmit_time == Utc.with_ymd_and_hms(2016, 12, 31, 23, 31, 40).unwrap());
+        // Still announced during the leap second, not after it
+        let during = now + Duration::from_millis(1_800_500);
+        assert!(build_response(&client_request(1), &clock, during, during).unwrap()[0] >> 6 == LEAP_INSERT);
+        let after = now + Duration::from_secs(1_860);
+        let response = build_response(&client_request(1), &clock, after, after).unwrap();
+        assert!(response[0] >> 6 == LEAP_NONE);
+        assert!(response[1] == STRATUM_PRIMARY);
+        let transmit_time = from_ntp_timestamp(u64::from_be_bytes(response[40..48].try_into().unwrap()));
+        assert!(transmit_time == Utc.with_ymd_and_hms(2017, 1, 1, 0, 0, 59).unwrap());
+    }
+}
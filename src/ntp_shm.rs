@@ -0,0 +1,240 @@
+//! Writer for the ntpd shared memory (SHM) reference clock
+//!
+//! ntpd reads the samples of `refclock shm` from a SysV shared memory segment
+//! with the key `0x4E545030 + unit`. Each decoded minute is published with
+//! the mode 1 protocol: the `count` field is increased before and after the
+//! sample is written, so that a reader can detect a concurrent update, and the
+//! `valid` field is set once the sample is complete.
+//!
+//! The clock time of a sample is the decoded minute translated with `to_utc`,
+//! the receive time is the local time at which its minute marker was seen and
+//! the leap indicator is taken from the leap second announcement bit (A2).
+
+use std::io::Error;
+use std::io::ErrorKind;
+use std::sync::atomic::{fence, Ordering};
+use chrono::prelude::*;
+use crate::{DCF77, to_utc};
+
+/// Key of the segment of the unit 0, the unit number is added to it
+pub const SHM_BASE_KEY: i32 = 0x4E54_5030;
+
+/// Leap indicator: no leap second announced
+pub const LEAP_NOWARNING: i32 = 0;
+
+/// Leap indicator: a leap second will be inserted at the end of the day
+pub const LEAP_ADDSECOND: i32 = 1;
+
+/// Leap indicator: the clock is not synchronized
+pub const LEAP_NOTINSYNC: i32 = 3;
+
+/// Precision of a decoded DCF77 minute marker, about 2^-7 s (8 ms)
+pub const DCF77_PRECISION: i32 = -7;
+
+/// Layout of the segment as documented for the ntpd SHM driver
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShmTime {
+    /// 0: use the sample when valid, 1: also check `count` before and after reading
+    pub mode: libc::c_int,
+    /// Increased before and after each update in mode 1
+    pub count: libc::c_int,
+    /// Seconds of the time given by the reference clock
+    pub clock_time_stamp_sec: libc::time_t,
+    /// Microseconds of the time given by the reference clock
+    pub clock_time_stamp_usec: libc::c_int,
+    /// Seconds of the local time at which the sample was received
+    pub receive_time_stamp_sec: libc::time_t,
+    /// Microseconds of the local time at which the sample was received
+    pub receive_time_stamp_usec: libc::c_int,
+    /// Leap indicator
+    pub leap: libc::c_int,
+    /// Precision as a power of two in seconds
+    pub precision: libc::c_int,
+    /// Unused by ntpd
+    pub nsamples: libc::c_int,
+    /// Set when a new sample is available, cleared by the reader
+    pub valid: libc::c_int,
+    /// Nanoseconds of the time given by the reference clock
+    pub clock_time_stamp_nsec: libc::c_uint,
+    /// Nanoseconds of the local time at which the sample was received
+    pub receive_time_stamp_nsec: libc::c_uint,
+    /// Reserved
+    pub dummy: [libc::c_int; 8]
+}
+
+/// Sample published into the segment
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShmSample {
+    /// Time given by the reference clock
+    pub clock_time: DateTime<Utc>,
+    /// Local time at which the sample was received
+    pub receive_time: DateTime<Utc>,
+    /// Leap indicator
+    pub leap: i32,
+    /// Precision as a power of two in seconds
+    pub precision: i32
+}
+
+/// Creates the sample of a decoded DCF77 minute
+///
+/// `marker` is the local time at which the minute marker of the decoded minute
+/// was received.
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::*;
+///use dcf77_chrono::ntp_shm::*;
+///let test_time = DCF77 {
+///    date: Utc.with_ymd_and_hms(2016, 12, 31, 23, 0, 0).unwrap(),
+///    antenna: false,
+///    announce_daily_saving_time: false,
+///    daily_saving_time: false,
+///    standard_time: true,
+///    bit_leap_second: true,
+///    third_party: 0
+///};
+///let marker = Utc.with_ymd_and_hms(2016, 12, 31, 22, 0, 0).unwrap() + chrono::Duration::milliseconds(3);
+///let sample = sample_from_dcf77(test_time, marker);
+///assert!(sample.clock_time == Utc.with_ymd_and_hms(2016, 12, 31, 22, 0, 0).unwrap());
+///assert!(sample.leap == LEAP_ADDSECOND);
+/// ```
+pub fn sample_from_dcf77(dcf_data: DCF77, marker: DateTime<Utc>) -> ShmSample {
+    ShmSample {
+        clock_time: to_utc(dcf_data),
+        receive_time: marker,
+        leap: if dcf_data.bit_leap_second { LEAP_ADDSECOND } else { LEAP_NOWARNING },
+        precision: DCF77_PRECISION
+    }
+}
+
+/// Writer attached to an ntpd SHM segment
+#[derive(Debug)]
+pub struct ShmWriter {
+    segment: *mut ShmTime
+}
+
+impl ShmWriter {
+    /// Attaches to the segment of an ntpd SHM unit, creating it when needed
+    ///
+    /// As ntpd does, the segments of the units 0 and 1 are only accessible by
+    /// their owner and the other ones by everybody.
+    pub fn open(unit: u8) -> Result<ShmWriter, Error> {
+        let permissions = if unit < 2 { 0o600 } else { 0o666 };
+        ShmWriter::open_key(SHM_BASE_KEY + i32::from(unit), permissions)
+    }
+
+    /// Attaches to the segment with the given key, creating it when needed
+    pub fn open_key(key: i32, permissions: i32) -> Result<ShmWriter, Error> {
+        // SAFETY: plain system calls, the returned address is checked before use
+        let segment = unsafe {
+            let id = libc::shmget(key as libc::key_t, std::mem::size_of::<ShmTime>(), libc::IPC_CREAT | permissions);
+            if id < 0 {
+                return Err(Error::last_os_error());
+            }
+            libc::shmat(id, std::ptr::null(), 0)
+        };
+        if segment as isize == -1 {
+            return Err(Error::last_os_error());
+        }
+        Ok(ShmWriter {segment: segment as *mut ShmTime})
+    }
+
+    /// Publishes a sample with the mode 1 protocol
+    pub fn publish(&mut self, sample: ShmSample) -> Result<(), Error> {
+        let clock_time_stamp_sec = libc::time_t::try_from(sample.clock_time.timestamp())
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "Clock time out of range"))?;
+        let receive_time_stamp_sec = libc::time_t::try_from(sample.receive_time.timestamp())
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "Receive time out of range"))?;
+        // SAFETY: the segment is attached for the whole life of the writer and
+        // is at least as big as ShmTime; ntpd reads it concurrently, so its
+        // fields are only reached through raw pointers, never references
+        unsafe {
+            let segment = self.segment;
+            std::ptr::write_volatile(std::ptr::addr_of_mut!((*segment).valid), 0);
+            std::ptr::write_volatile(std::ptr::addr_of_mut!((*segment).mode), 1);
+            let count = std::ptr::read_volatile(std::ptr::addr_of!((*segment).count));
+            std::ptr::write_volatile(std::ptr::addr_of_mut!((*segment).count), count.wrapping_add(1));
+            fence(Ordering::SeqCst);
+            std::ptr::write_volatile(std::ptr::addr_of_mut!((*segment).clock_time_stamp_sec), clock_time_stamp_sec);
+            std::ptr::write_volatile(std::ptr::addr_of_mut!((*segment).clock_time_stamp_usec), sample.clock_time.timestamp_subsec_micros() as libc::c_int);
+            std::ptr::write_volatile(std::ptr::addr_of_mut!((*segment).clock_time_stamp_nsec), sample.clock_time.timestamp_subsec_nanos());
+            std::ptr::write_volatile(std::ptr::addr_of_mut!((*segment).receive_time_stamp_sec), receive_time_stamp_sec);
+            std::ptr::write_volatile(std::ptr::addr_of_mut!((*segment).receive_time_stamp_usec), sample.receive_time.timestamp_subsec_micros() as libc::c_int);
+            std::ptr::write_volatile(std::ptr::addr_of_mut!((*segment).receive_time_stamp_nsec), sample.receive_time.timestamp_subsec_nanos());
+            std::ptr::write_volatile(std::ptr::addr_of_mut!((*segment).leap), sample.leap);
+            std::ptr::write_volatile(std::ptr::addr_of_mut!((*segment).precision), sample.precision);
+            fence(Ordering::SeqCst);
+            std::ptr::write_volatile(std::ptr::addr_of_mut!((*segment).count), count.wrapping_add(2));
+            std::ptr::write_volatile(std::ptr::addr_of_mut!((*segment).valid), 1);
+        }
+        Ok(())
+    }
+
+    /// Reads the current content of the segment
+    pub fn read(&self) -> ShmTime {
+        // SAFETY: the segment is attached for the whole life of the writer
+        unsafe { std::ptr::read_volatile(self.segment) }
+    }
+}
+
+impl Drop for ShmWriter {
+    fn drop(&mut self) {
+        // SAFETY: the segment was attached by this writer
+        unsafe {
+            libc::shmdt(self.segment as *const libc::c_void);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    /// Removes the segment with the given key
+    fn remove_segment(key: i32) {
+        unsafe {
+            let id = libc::shmget(key as libc::key_t, 0, 0);
+            if id >= 0 {
+                libc::shmctl(id, libc::IPC_RMID, std::ptr::null_mut());
+            }
+        }
+    }
+    #[test]
+    #[cfg_attr(not(target_os = "linux"), ignore = "SysV shared memory may not be available")]
+    fn test_publish() {
+        let key = 0x4443_0000 | (std::process::id() as i32 & 0xFFFF);
+        remove_segment(key);
+        let test_time = DCF77 {
+            date: Utc.with_ymd_and_hms(2023, 7, 1, 12, 34, 0).unwrap(),
+            antenna: false,
+            announce_daily_saving_time: false,
+            daily_saving_time: true,
+            standard_time: false,
+            bit_leap_second: false,
+            third_party: 0
+        };
+        let marker = Utc.with_ymd_and_hms(2023, 7, 1, 10, 34, 0).unwrap() + chrono::Duration::microseconds(4_321);
+        let mut writer = ShmWriter::open_key(key, 0o600).unwrap();
+        writer.publish(sample_from_dcf77(test_time, marker)).unwrap();
+        let reader = ShmWriter::open_key(key, 0o600).unwrap();
+        let segment = reader.read();
+        assert!(segment.mode == 1);
+        assert!(segment.count == 2);
+        assert!(segment.valid == 1);
+        assert!(segment.clock_time_stamp_sec == 1_688_207_640);
+        assert!(segment.clock_time_stamp_usec == 0);
+        assert!(segment.receive_time_stamp_sec == 1_688_207_640);
+        assert!(segment.receive_time_stamp_usec == 4_321);
+        assert!(segment.receive_time_stamp_nsec == 4_321_000);
+        assert!(segment.leap == LEAP_NOWARNING);
+        assert!(segment.precision == DCF77_PRECISION);
+        writer.publish(sample_from_dcf77(DCF77 {bit_leap_second: true, ..test_time}, marker)).unwrap();
+        let segment = reader.read();
+        assert!(segment.count == 4);
+        assert!(segment.leap == LEAP_ADDSECOND);
+        drop(writer);
+        drop(reader);
+        remove_segment(key);
+    }
+}
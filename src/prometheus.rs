@@ -0,0 +1,257 @@
+//! Prometheus exporter of the state of a DCF77 receiver
+//!
+//! The exporter serves `GET /metrics` over a small HTTP/1.1 endpoint in the
+//! Prometheus text exposition format (version 0.0.4): the rolling quality
+//! metrics of the receiver, whether it is locked, the holdover and its error
+//! estimate, the last decoded time and the status given by the call bit.
+//! Every connection gets one response and is closed.
+//!
+//! Only available with the `prometheus` feature.
+
+use std::fmt::Write as _;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::call_bit::CallBitStatus;
+use crate::holdover::HoldoverClock;
+use crate::quality::{PARITY_FIELDS, QualityMetrics};
+use crate::receiver::Receiver;
+
+/// Holdover after which the receiver is no longer taken as locked
+pub const LOCK_HOLDOVER: Duration = Duration::from_secs(120);
+
+/// Content type of the exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Largest HTTP request accepted
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// Time given to a client to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// State of the receiver exported
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ExporterStatus {
+    /// Rolling quality metrics of the signal
+    pub quality: QualityMetrics,
+    /// Holdover clock synchronized by the decoded minutes
+    pub clock: HoldoverClock,
+    /// Status given by the call bit of the last decoded minute
+    pub call_bit: Option<CallBitStatus>
+}
+
+impl ExporterStatus {
+    /// Takes the rolling quality metrics and the call bit status of a receiver
+    pub fn update_from_receiver(&mut self, receiver: &Receiver) {
+        self.quality = receiver.quality().rolling();
+        self.call_bit = receiver.call_bit().status();
+    }
+
+    /// Whether a minute was decoded within `LOCK_HOLDOVER` before a local instant
+    pub fn is_locked_at(&self, instant: Instant) -> bool {
+        self.clock.holdover_at(instant).is_some_and(|holdover| holdover <= LOCK_HOLDOVER)
+    }
+}
+
+/// Writes a metric with its help and type lines, skipping the samples without value
+fn write_metric(output: &mut String, name: &str, help: &str, samples: &[(&str, Option<f64>)]) {
+    if samples.iter().all(|(_, value)| value.is_none()) {
+        return;
+    }
+    writeln!(output, "# HELP {} {}", name, help).unwrap();
+    writeln!(output, "# TYPE {} gauge", name).unwrap();
+    for (labels, value) in samples {
+        if let Some(value) = value {
+            writeln!(output, "{}{} {}", name, labels, value).unwrap();
+        }
+    }
+}
+
+/// Formats the state of a receiver at a local instant in the exposition format
+///
+/// # Examples
+/// ```
+///use std::time::Instant;
+///use chrono::prelude::*;
+///use dcf77_chrono::prometheus::*;
+///use dcf77_chrono::schedule::*;
+///let minute = scheduled_minute(Utc.with_ymd_and_hms(2023, 6, 14, 8, 0, 0).unwrap(), &[]);
+///let now = Instant::now();
+///let mut status = ExporterStatus::default();
+///assert!(render(&status, now).contains("dcf77_locked 0\n"));
+///status.clock.synchronize(minute.dcf, now);
+///let metrics = render(&status, now);
+///assert!(metrics.contains("dcf77_locked 1\n"));
+///assert!(metrics.contains("dcf77_last_decoded_timestamp_seconds 1686729600\n"));
+/// ```
+pub fn render(status: &ExporterStatus, now: Instant) -> String {
+    let mut output = String::new();
+    let quality = &status.quality;
+    let secs = |duration: Option<Duration>| duration.map(|duration| duration.as_secs_f64());
+    write_metric(&mut output, "dcf77_locked", "Whether a minute was decoded recently",
+                 &[("", Some(f64::from(u8::from(status.is_locked_at(now)))))]);
+    write_metric(&mut output, "dcf77_holdover_seconds", "Time since the last decoded minute",
+                 &[("", secs(status.clock.holdover_at(now)))]);
+    write_metric(&mut output, "dcf77_holdover_error_estimate_seconds", "Estimated error of the holdover clock",
+                 &[("", secs(status.clock.error_estimate_at(now)))]);
+    write_metric(&mut output, "dcf77_last_decoded_timestamp_seconds", "UTC time of the last decoded minute",
+                 &[("", status.clock.reference_time().map(|time| time.timestamp() as f64))]);
+    write_metric(&mut output, "dcf77_call_bit", "Call bit of the last decoded minute, 1 when the transmitter runs irregularly",
+                 &[("", status.call_bit.map(|call_bit| f64::from(u8::from(call_bit == CallBitStatus::Irregular))))]);
+    write_metric(&mut output, "dcf77_window_minutes", "Minutes of the rolling quality metrics",
+                 &[("", Some(quality.minutes as f64))]);
+    write_metric(&mut output, "dcf77_decoded_minutes_percent", "Percentage of the minutes that decoded",
+                 &[("", quality.decode_rate())]);
+    write_metric(&mut output, "dcf77_pulse_width_mean_seconds", "Mean width of the pulses",
+                 &[("{bit=\"0\"}", quality.zero_widths.mean()), ("{bit=\"1\"}", quality.one_widths.mean())]);
+    write_metric(&mut output, "dcf77_pulse_width_variance_seconds2", "Variance of the width of the pulses",
+                 &[("{bit=\"0\"}", quality.zero_widths.variance()), ("{bit=\"1\"}", quality.one_widths.variance())]);
+    write_metric(&mut output, "dcf77_invalid_pulses", "Pulses too long to be classified",
+                 &[("", Some(quality.invalid_pulses as f64))]);
+    write_metric(&mut output, "dcf77_missing_pulses", "Seconds without pulse",
+                 &[("", Some(quality.missing_pulses as f64))]);
+    write_metric(&mut output, "dcf77_extra_pulses", "Glitches and pulses in excess",
+                 &[("", Some(quality.extra_pulses as f64))]);
+    let labels: Vec<String> = PARITY_FIELDS.iter().map(|field| format!("{{field=\"{}\"}}", field.name())).collect();
+    let parities: Vec<(&str, Option<f64>)> = PARITY_FIELDS.iter().zip(&labels)
+        .map(|(field, labels)| (labels.as_str(), quality.parity_failure_rate(*field)))
+        .collect();
+    write_metric(&mut output, "dcf77_parity_failure_ratio", "Share of the parity checks that failed", &parities);
+    write_metric(&mut output, "dcf77_marker_jitter_seconds", "Standard deviation of the minute markers",
+                 &[("", secs(quality.marker_jitter()))]);
+    output
+}
+
+/// Creates the HTTP response to a request
+fn respond(request: &str, status: &ExporterStatus, now: Instant) -> String {
+    let request_line = request.lines().next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let (status_line, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", CONTENT_TYPE, render(status, now)),
+        (Some("GET"), _) => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain", "Method not allowed\n".to_string())
+    };
+    format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status_line, content_type, body.len(), body)
+}
+
+/// HTTP endpoint serving the state of a receiver to Prometheus
+#[derive(Debug)]
+pub struct PrometheusExporter {
+    listener: TcpListener,
+    status: Arc<Mutex<ExporterStatus>>
+}
+
+impl PrometheusExporter {
+    /// Binds the exporter to a local address
+    ///
+    /// The status is shared so that it can keep being updated while the
+    /// exporter runs.
+    pub fn bind<A: ToSocketAddrs>(address: A, status: Arc<Mutex<ExporterStatus>>) -> Result<PrometheusExporter, Error> {
+        Ok(PrometheusExporter {listener: TcpListener::bind(address)?, status})
+    }
+
+    /// Local address the exporter is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        self.listener.local_addr()
+    }
+
+    /// Waits for one connection and answers its request
+    pub fn serve_one(&self) -> Result<(), Error> {
+        let (mut stream, _) = self.listener.accept()?;
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            let size = stream.read(&mut buffer)?;
+            if size == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..size]);
+            if request.len() > MAX_REQUEST_SIZE {
+                return Err(Error::new(ErrorKind::InvalidData, "HTTP request too long"));
+            }
+        }
+        let status = *self.status.lock().map_err(|_| Error::new(ErrorKind::Other, "Exporter status lock poisoned"))?;
+        let response = respond(&String::from_utf8_lossy(&request), &status, Instant::now());
+        stream.write_all(response.as_bytes())?;
+        stream.flush()
+    }
+
+    /// Answers connections forever, ignoring the requests that cannot be read
+    pub fn serve(&self) -> Result<(), Error> {
+        loop {
+            match self.serve_one() {
+                Ok(()) => {}
+                Err(error) if matches!(error.kind(), ErrorKind::InvalidData | ErrorKind::WouldBlock | ErrorKind::TimedOut
+                                                     | ErrorKind::ConnectionReset | ErrorKind::BrokenPipe) => {}
+                Err(error) => return Err(error)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpStream;
+    use chrono::prelude::*;
+    use crate::quality::QualityMonitor;
+    use crate::frame::Frame;
+    use crate::schedule::scheduled_minute;
+    fn get(address: SocketAddr, request: &str) -> String {
+        let mut client = TcpStream::connect(address).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        client.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        response
+    }
+    #[test]
+    fn test_render() {
+        let mut monitor = QualityMonitor::new();
+        for minute in 0..4 {
+            monitor.push_pulse(Duration::from_millis(100));
+            monitor.push_pulse(Duration::from_millis(200));
+            monitor.end_minute(Duration::from_secs(60 * minute), Frame::from(0x486092B3312), minute != 2);
+        }
+        let now = Instant::now();
+        let mut status = ExporterStatus {quality: monitor.rolling(), call_bit: Some(CallBitStatus::Irregular), ..ExporterStatus::default()};
+        status.clock.synchronize(scheduled_minute(Utc.with_ymd_and_hms(2023, 6, 14, 8, 0, 0).unwrap(), &[]).dcf, now);
+        let metrics = render(&status, now + Duration::from_secs(300));
+        assert!(metrics.contains("# TYPE dcf77_locked gauge\ndcf77_locked 0\n"));
+        assert!(metrics.contains("dcf77_holdover_seconds 300\n"));
+        assert!(metrics.contains("dcf77_call_bit 1\n"));
+        assert!(metrics.contains("dcf77_decoded_minutes_percent 75\n"));
+        assert!(metrics.contains("dcf77_pulse_width_mean_seconds{bit=\"1\"} 0.2\n"));
+        assert!(metrics.contains("dcf77_parity_failure_ratio{field=\"P2\"} 0\n"));
+        assert!(metrics.contains("dcf77_marker_jitter_seconds 0\n"));
+        // Metrics without value are left out
+        let metrics = render(&ExporterStatus::default(), now);
+        assert!(!metrics.contains("dcf77_holdover_seconds"));
+        assert!(!metrics.contains("dcf77_pulse_width_mean_seconds"));
+        assert!(metrics.contains("dcf77_window_minutes 0\n"));
+    }
+    #[test]
+    fn test_exporter() {
+        let status = Arc::new(Mutex::new(ExporterStatus::default()));
+        status.lock().unwrap().clock.synchronize(scheduled_minute(Utc.with_ymd_and_hms(2023, 6, 14, 8, 0, 0).unwrap(), &[]).dcf, Instant::now());
+        let exporter = PrometheusExporter::bind("127.0.0.1:0", status).unwrap();
+        let address = exporter.local_addr().unwrap();
+        let handle = std::thread::spawn(move || {
+            for _ in 0..3 {
+                exporter.serve_one().unwrap();
+            }
+        });
+        let response = get(address, "GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains(&format!("Content-Type: {}", CONTENT_TYPE)));
+        assert!(head.contains(&format!("Content-Length: {}", body.len())));
+        assert!(body.contains("dcf77_locked 1\n"));
+        assert!(body.contains("dcf77_last_decoded_timestamp_seconds 1686729600\n"));
+        assert!(get(address, "GET / HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(get(address, "POST /metrics HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        handle.join().unwrap();
+    }
+}
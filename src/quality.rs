@@ -0,0 +1,308 @@
+//! Quality of the signal received from DCF77
+//!
+//! The `QualityMonitor` gathers the measures of the demodulator minute by
+//! minute: the widths of the pulses of the bits 0 and 1, the pulses that are
+//! missing or in excess, the parity checks of the minutes, the hour and the
+//! date, the jitter of the minute markers and whether the minute decoded.
+//! Every minute gives its own `QualityMetrics`, and the monitor adds up the
+//! last ones into rolling metrics.
+//!
+//! The `Receiver` feeds a monitor with its edges and frames; other
+//! demodulators can feed one the same way.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+use crate::frame::Frame;
+use crate::receiver::bit_from_pulse_width;
+use crate::telegram::Field;
+
+/// Number of minutes of the rolling metrics by default
+pub const DEFAULT_WINDOW: usize = 60;
+
+/// Parity fields, in the order of the parity counters
+pub const PARITY_FIELDS: [Field; 3] = [Field::MinutesParity, Field::HourParity, Field::DateParity];
+
+/// Count, mean and variance of a series of values
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Statistics {
+    count: u64,
+    mean: f64,
+    squares: f64
+}
+
+impl Statistics {
+    /// Adds a value
+    pub fn push(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.squares += delta * (value - self.mean);
+    }
+
+    /// Adds the values of other statistics
+    pub fn merge(&mut self, other: &Statistics) {
+        if other.count == 0 {
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        self.squares += other.squares + delta * delta * (self.count as f64 * other.count as f64 / count as f64);
+        self.mean += delta * other.count as f64 / count as f64;
+        self.count = count;
+    }
+
+    /// Number of values
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Mean of the values, `None` without values
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.mean)
+    }
+
+    /// Variance of the values, `None` without values
+    pub fn variance(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.squares / self.count as f64)
+    }
+
+    /// Standard deviation of the values, `None` without values
+    pub fn standard_deviation(&self) -> Option<f64> {
+        self.variance().map(f64::sqrt)
+    }
+}
+
+/// Quality of the signal over one or several minutes
+///
+/// The widths and the deviations of the markers are given in seconds.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct QualityMetrics {
+    /// Minutes ended by a minute marker
+    pub minutes: u64,
+    /// Minutes whose frame decoded
+    pub decoded_minutes: u64,
+    /// Widths of the pulses of the bits 0
+    pub zero_widths: Statistics,
+    /// Widths of the pulses of the bits 1
+    pub one_widths: Statistics,
+    /// Pulses too long to be classified
+    pub invalid_pulses: u64,
+    /// Seconds without pulse before the minute marker
+    pub missing_pulses: u64,
+    /// Glitches and pulses in excess within a second
+    pub extra_pulses: u64,
+    /// Parity checks made, the fields being those of `PARITY_FIELDS`
+    pub parity_checks: [u64; 3],
+    /// Parity checks failed, the fields being those of `PARITY_FIELDS`
+    pub parity_failures: [u64; 3],
+    /// Distances of the minute markers to whole seconds after the previous one
+    pub marker_deviations: Statistics
+}
+
+impl QualityMetrics {
+    /// Adds the metrics of other minutes
+    pub fn merge(&mut self, other: &QualityMetrics) {
+        self.minutes += other.minutes;
+        self.decoded_minutes += other.decoded_minutes;
+        self.zero_widths.merge(&other.zero_widths);
+        self.one_widths.merge(&other.one_widths);
+        self.invalid_pulses += other.invalid_pulses;
+        self.missing_pulses += other.missing_pulses;
+        self.extra_pulses += other.extra_pulses;
+        for index in 0..PARITY_FIELDS.len() {
+            self.parity_checks[index] += other.parity_checks[index];
+            self.parity_failures[index] += other.parity_failures[index];
+        }
+        self.marker_deviations.merge(&other.marker_deviations);
+    }
+
+    /// Percentage of the minutes that decoded, `None` without minutes
+    pub fn decode_rate(&self) -> Option<f64> {
+        (self.minutes > 0).then(|| 100.0 * self.decoded_minutes as f64 / self.minutes as f64)
+    }
+
+    /// Share of the parity checks of a field that failed, `None` without checks
+    pub fn parity_failure_rate(&self, field: Field) -> Option<f64> {
+        let index = PARITY_FIELDS.iter().position(|parity_field| *parity_field == field)?;
+        let checks = self.parity_checks[index];
+        (checks > 0).then(|| self.parity_failures[index] as f64 / checks as f64)
+    }
+
+    /// Jitter of the minute markers, as the standard deviation of their distances to whole seconds
+    pub fn marker_jitter(&self) -> Option<Duration> {
+        self.marker_deviations.standard_deviation().map(Duration::from_secs_f64)
+    }
+}
+
+/// Gatherer of the quality of the signal minute by minute
+///
+/// # Examples
+/// ```
+///use std::time::Duration;
+///use dcf77_chrono::frame::Frame;
+///use dcf77_chrono::quality::*;
+///use dcf77_chrono::telegram::Field;
+///let mut monitor = QualityMonitor::new();
+///for minute in 0..3 {
+///    for second in 0..59 {
+///        monitor.push_pulse(Duration::from_millis(if second % 2 == 0 { 95 } else { 205 }));
+///    }
+///    monitor.end_minute(Duration::from_secs(60 * minute), Frame::from(0x486092B3312), true);
+///}
+///let rolling = monitor.rolling();
+///assert!(rolling.minutes == 3);
+///assert!(rolling.decode_rate() == Some(100.0));
+///assert!(rolling.zero_widths.count() == 90);
+///assert!((rolling.one_widths.mean().unwrap() - 0.205).abs() < 1e-9);
+///assert!(rolling.parity_failure_rate(Field::DateParity) == Some(0.0));
+///assert!(rolling.marker_jitter() == Some(Duration::ZERO));
+/// ```
+#[derive(Clone, Debug)]
+pub struct QualityMonitor {
+    current: QualityMetrics,
+    window: VecDeque<QualityMetrics>,
+    capacity: usize,
+    last_marker: Option<Duration>
+}
+
+impl Default for QualityMonitor {
+    fn default() -> QualityMonitor {
+        QualityMonitor::with_window(DEFAULT_WINDOW)
+    }
+}
+
+impl QualityMonitor {
+    /// Creates a monitor whose rolling metrics cover `DEFAULT_WINDOW` minutes
+    pub fn new() -> QualityMonitor {
+        QualityMonitor::default()
+    }
+
+    /// Creates a monitor whose rolling metrics cover `minutes` minutes
+    pub fn with_window(minutes: usize) -> QualityMonitor {
+        QualityMonitor {current: QualityMetrics::default(), window: VecDeque::new(), capacity: minutes, last_marker: None}
+    }
+
+    /// Pushes the width of a pulse of the current minute
+    pub fn push_pulse(&mut self, width: Duration) {
+        match bit_from_pulse_width(width) {
+            Some(false) => self.current.zero_widths.push(width.as_secs_f64()),
+            Some(true) => self.current.one_widths.push(width.as_secs_f64()),
+            None => self.current.invalid_pulses += 1
+        }
+    }
+
+    /// Pushes pulses that were missing in the current minute
+    pub fn push_missing_pulses(&mut self, count: u64) {
+        self.current.missing_pulses += count;
+    }
+
+    /// Pushes pulses in excess in the current minute
+    pub fn push_extra_pulses(&mut self, count: u64) {
+        self.current.extra_pulses += count;
+    }
+
+    /// Ends the current minute at its minute marker, giving its metrics
+    ///
+    /// The parities are checked on the groups of bits that are all known, and
+    /// `decoded` tells whether the frame decoded.
+    pub fn end_minute(&mut self, marker: Duration, frame: Frame, decoded: bool) -> QualityMetrics {
+        let mut minute = std::mem::take(&mut self.current);
+        minute.minutes = 1;
+        minute.decoded_minutes = u64::from(decoded);
+        for (index, field) in PARITY_FIELDS.iter().enumerate() {
+            let group: Option<Vec<bool>> = field.parity_coverage().unwrap()
+                .chain(field.seconds())
+                .map(|second| frame.bit(second))
+                .collect();
+            if let Some(group) = group {
+                minute.parity_checks[index] = 1;
+                minute.parity_failures[index] = u64::from(group.iter().filter(|bit| **bit).count() % 2 == 1);
+            }
+        }
+        if let Some(last_marker) = self.last_marker {
+            let interval = marker.saturating_sub(last_marker).as_secs_f64();
+            minute.marker_deviations.push(interval - interval.round());
+        }
+        self.last_marker = Some(marker);
+        if self.capacity > 0 {
+            if self.window.len() == self.capacity {
+                self.window.pop_front();
+            }
+            self.window.push_back(minute);
+        }
+        minute
+    }
+
+    /// Metrics of the minute going on
+    pub fn current(&self) -> QualityMetrics {
+        self.current
+    }
+
+    /// Metrics of the last minute ended
+    pub fn last_minute(&self) -> Option<QualityMetrics> {
+        self.window.back().copied()
+    }
+
+    /// Metrics of the last minutes ended, the oldest first
+    pub fn minutes(&self) -> impl Iterator<Item = &QualityMetrics> {
+        self.window.iter()
+    }
+
+    /// Metrics added up over the last minutes ended
+    pub fn rolling(&self) -> QualityMetrics {
+        self.window.iter().fold(QualityMetrics::default(), |mut rolling, minute| {
+            rolling.merge(minute);
+            rolling
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_statistics() {
+        let values = [0.1, 0.12, 0.09, 0.11, 0.2, 0.21];
+        let mut all = Statistics::default();
+        let mut first = Statistics::default();
+        let mut second = Statistics::default();
+        for (index, value) in values.iter().enumerate() {
+            all.push(*value);
+            if index < 2 { first.push(*value) } else { second.push(*value) }
+        }
+        first.merge(&second);
+        assert!(first.count() == 6);
+        assert!((first.mean().unwrap() - all.mean().unwrap()).abs() < 1e-12);
+        assert!((first.variance().unwrap() - all.variance().unwrap()).abs() < 1e-12);
+        let mean = values.iter().sum::<f64>() / 6.0;
+        let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / 6.0;
+        assert!((all.variance().unwrap() - variance).abs() < 1e-12);
+        assert!(Statistics::default().mean().is_none());
+    }
+    #[test]
+    fn test_window() {
+        let mut monitor = QualityMonitor::with_window(2);
+        let mut frame = Frame::from(0x486092B3312);
+        monitor.end_minute(Duration::ZERO, frame, true);
+        // A wrong bit of the hour, and an unknown bit of the date
+        frame.bits ^= 1 << (59 - 30);
+        frame.known &= !(1 << (59 - 40));
+        monitor.push_missing_pulses(1);
+        monitor.push_extra_pulses(2);
+        monitor.push_pulse(Duration::from_millis(300));
+        let minute = monitor.end_minute(Duration::from_millis(60_010), frame, false);
+        assert!(minute.parity_checks == [1, 1, 0]);
+        assert!(minute.parity_failures == [0, 1, 0]);
+        assert!(minute.invalid_pulses == 1);
+        monitor.end_minute(Duration::from_millis(120_000), Frame::from(0x486092B3312), true);
+        let rolling = monitor.rolling();
+        assert!(rolling.minutes == 2);
+        assert!(rolling.decode_rate() == Some(50.0));
+        assert!(rolling.parity_failure_rate(Field::HourParity) == Some(0.5));
+        assert!(rolling.parity_failure_rate(Field::DateParity) == Some(0.0));
+        assert!(rolling.parity_failure_rate(Field::Hour).is_none());
+        assert!(rolling.missing_pulses == 1 && rolling.extra_pulses == 2);
+        assert!((rolling.marker_jitter().unwrap().as_secs_f64() - 0.01).abs() < 1e-6);
+        assert!(monitor.minutes().count() == 2);
+    }
+}
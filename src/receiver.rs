@@ -0,0 +1,470 @@
+//! Decoding of the DCF77 amplitude modulation out of the edges of a receiver
+//!
+//! DCF77 lowers its carrier at the start of every second for 100 ms (bit 0)
+//! or 200 ms (bit 1), except in the second 59 which announces the minute
+//! marker. Receiver modules give that reduction as a digital signal: the
+//! `Receiver` takes its edges, measures the pulses, classifies them into bits
+//! and assembles the bits of a minute into a `Frame` whenever a minute marker
+//! is found.
+//!
+//! The times are durations since an arbitrary origin, usually the start of
+//! the capture. Missing pulses leave unknown bits in the frame, which the soft
+//! decoder of `Frame` may still fill.
+//!
+//! The complete frames are decoded further: the changes of the call bit are
+//! given as events, and so are the packets of third party bits collected from
+//! consecutive minutes. The quality of the signal is gathered minute by minute
+//! by a `QualityMonitor`.
+
+use std::time::Duration;
+use crate::from_dcf77;
+use crate::call_bit::{CallBitEvent, CallBitMonitor};
+use crate::frame::Frame;
+use crate::quality::QualityMonitor;
+use crate::third_party::{ThirdPartyAggregator, ThirdPartyPacket};
+use crate::telegram::SECONDS_PER_MINUTE;
+
+/// Pulses shorter than this are taken as noise
+pub const MIN_PULSE_WIDTH: Duration = Duration::from_millis(40);
+
+/// Pulses shorter than this are bits 0, longer ones bits 1
+pub const BIT_THRESHOLD: Duration = Duration::from_millis(140);
+
+/// Pulses longer than this cannot be classified
+pub const MAX_PULSE_WIDTH: Duration = Duration::from_millis(250);
+
+/// Largest distance of a pulse to the grid of seconds that is accepted
+pub const MAX_JITTER: Duration = Duration::from_millis(100);
+
+/// Second of the leap second announcement bit (A2)
+const LEAP_SECOND_SECOND: u8 = 19;
+
+/// Edge of the demodulated signal of a receiver
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Edge {
+    /// Time of the edge
+    pub time: Duration,
+    /// Level after the edge, true while the carrier is reduced (pulse)
+    pub level: bool
+}
+
+/// Event produced by the receiver
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReceiverEvent {
+    /// A pulse was classified, `value` being `None` when its width is out of range
+    Bit {
+        /// Start of the pulse
+        time: Duration,
+        /// Second of the minute of the pulse
+        second: u8,
+        /// Value of the bit
+        value: Option<bool>
+    },
+    /// A minute marker was found after a whole minute
+    Minute {
+        /// Time of the minute marker, start of the minute described by the frame
+        marker: Duration,
+        /// Bits received during the minute before the marker
+        frame: Frame
+    },
+    /// The call bit of a frame changed the status of the transmitter
+    CallBit {
+        /// Time of the minute marker that ended the frame
+        marker: Duration,
+        /// Change of status
+        event: CallBitEvent
+    },
+    /// A packet of third party bits was completed by the frame of a minute
+    Packet {
+        /// Time of the minute marker that ended the last chunk of the packet
+        marker: Duration,
+        /// Collected packet
+        packet: ThirdPartyPacket
+    },
+    /// The signal does not follow the DCF77 timing
+    Error {
+        /// Time of the problem
+        time: Duration,
+        /// Description of the problem
+        message: String
+    }
+}
+
+/// Classifies the width of a pulse into a bit
+///
+/// # Examples
+/// ```
+///use std::time::Duration;
+///use dcf77_chrono::receiver::*;
+///assert!(bit_from_pulse_width(Duration::from_millis(100)) == Some(false));
+///assert!(bit_from_pulse_width(Duration::from_millis(200)) == Some(true));
+///assert!(bit_from_pulse_width(Duration::from_millis(500)).is_none());
+/// ```
+pub fn bit_from_pulse_width(width: Duration) -> Option<bool> {
+    if width < MIN_PULSE_WIDTH || width > MAX_PULSE_WIDTH {
+        None
+    } else {
+        Some(width >= BIT_THRESHOLD)
+    }
+}
+
+/// Width of the pulse of a bit
+pub fn pulse_width_from_bit(value: bool) -> Duration {
+    if value {
+        Duration::from_millis(200)
+    } else {
+        Duration::from_millis(100)
+    }
+}
+
+/// Edges broadcast for a telegram, starting at the start of its second 0
+///
+/// `leap_second` adds the bit 0 of the inserted second 59.
+pub fn edges_from_frame(input: u64, start: Duration, leap_second: bool) -> Vec<Edge> {
+    let frame = Frame::from(input);
+    let mut bits: Vec<bool> = (0..SECONDS_PER_MINUTE - 1).map(|second| frame.bit(second).unwrap()).collect();
+    if leap_second {
+        bits.push(false);
+    }
+    bits.iter().enumerate().flat_map(|(second, value)| {
+        let time = start + Duration::from_secs(second as u64);
+        [Edge {time, level: true}, Edge {time: time + pulse_width_from_bit(*value), level: false}]
+    }).collect()
+}
+
+/// Decoder of the edges of a receiver into bits and frames
+///
+/// # Examples
+/// ```
+///use std::time::Duration;
+///use chrono::prelude::*;
+///use dcf77_chrono::*;
+///use dcf77_chrono::receiver::*;
+///let test_time = DCF77 {
+///    date: Utc.with_ymd_and_hms(23, 6, 14, 8, 30, 0).unwrap(),
+///    antenna: false,
+///    announce_daily_saving_time: false,
+///    daily_saving_time: true,
+///    standard_time: false,
+///    bit_leap_second: false,
+///    third_party: 0
+///};
+///let coded_minutes = to_dcf77(test_time).unwrap();
+///let mut receiver = Receiver::new();
+///let mut frames = Vec::new();
+///for minute in 0..4 {
+///    for edge in edges_from_frame(coded_minutes, Duration::from_secs(60 * minute), false) {
+///        for event in receiver.push_edge(edge) {
+///            if let ReceiverEvent::Minute {frame, ..} = event {
+///                frames.push(u64::try_from(frame).unwrap());
+///            }
+///        }
+///    }
+///}
+///assert!(frames == vec![coded_minutes, coded_minutes]);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Receiver {
+    level: bool,
+    pulse_start: Option<Duration>,
+    last_pulse: Option<Duration>,
+    second: Option<u8>,
+    frame: Frame,
+    third_party: ThirdPartyAggregator,
+    call_bit: CallBitMonitor,
+    quality: QualityMonitor
+}
+
+impl Receiver {
+    /// Creates a receiver that has not found a minute marker yet
+    pub fn new() -> Receiver {
+        Receiver::default()
+    }
+
+    /// Second of the minute of the last pulse, `None` before the first minute marker
+    pub fn second(&self) -> Option<u8> {
+        self.second
+    }
+
+    /// Monitor of the call bit of the decoded frames, with its history
+    pub fn call_bit(&self) -> &CallBitMonitor {
+        &self.call_bit
+    }
+
+    /// Monitor of the quality of the signal, with its rolling metrics
+    pub fn quality(&self) -> &QualityMonitor {
+        &self.quality
+    }
+
+    /// Pushes an edge of the demodulated signal
+    ///
+    /// Edges that do not change the level are ignored, and so are pulses
+    /// shorter than `MIN_PULSE_WIDTH`.
+    pub fn push_edge(&mut self, edge: Edge) -> Vec<ReceiverEvent> {
+        if edge.level == self.level {
+            return Vec::new();
+        }
+        self.level = edge.level;
+        if edge.level {
+            self.pulse_start = Some(edge.time);
+            return Vec::new();
+        }
+        match self.pulse_start.take() {
+            Some(start) if edge.time.saturating_sub(start) >= MIN_PULSE_WIDTH => {
+                let events = self.push_bit(start, bit_from_pulse_width(edge.time - start));
+                self.quality.push_pulse(edge.time - start);
+                events
+            }
+            Some(start) => {
+                self.quality.push_extra_pulses(1);
+                vec![ReceiverEvent::Error {time: start, message: "Glitch shorter than a pulse".to_string()}]
+            }
+            None => Vec::new()
+        }
+    }
+
+    /// Pushes a bit whose pulse started at `time`
+    ///
+    /// The second of the bit is given by the time elapsed since the previous
+    /// pulse, and a gap of one second without pulse is the minute marker.
+    /// The second 59 carries a pulse only in a minute with a leap second,
+    /// whose marker comes one second later. When pulses are lost around the
+    /// marker, the marker is placed two seconds after a pulse of the second 58
+    /// or 59, unless a leap second is announced after a pulse of the second
+    /// 58: the marker is then ambiguous and the receiver looks for the next one.
+    /// Without a previous marker, only a gap of exactly two seconds is taken
+    /// as the marker.
+    pub fn push_bit(&mut self, time: Duration, value: Option<bool>) -> Vec<ReceiverEvent> {
+        let mut events = Vec::new();
+        let Some(last_pulse) = self.last_pulse else {
+            self.last_pulse = Some(time);
+            return events;
+        };
+        let interval = time.saturating_sub(last_pulse);
+        let elapsed_seconds = (interval + Duration::from_millis(500)).as_secs();
+        if elapsed_seconds == 0 {
+            self.quality.push_extra_pulses(1);
+            events.push(ReceiverEvent::Error {time, message: "Extra pulse within a second".to_string()});
+            return events;
+        }
+        let grid = Duration::from_secs(elapsed_seconds);
+        let offset = interval.max(grid) - interval.min(grid);
+        if offset > MAX_JITTER {
+            events.push(ReceiverEvent::Error {time, message: format!("Pulse {} ms off the second grid", offset.as_millis())});
+        }
+        self.last_pulse = Some(time);
+        let second = match self.second {
+            Some(previous) => {
+                let second = u64::from(previous) + elapsed_seconds;
+                // A pulse in the second 59 is the bit of a leap second
+                let marker_second = if previous == SECONDS_PER_MINUTE - 1 {
+                    u64::from(SECONDS_PER_MINUTE) + 1
+                } else {
+                    u64::from(SECONDS_PER_MINUTE)
+                };
+                let leap_second = self.frame.bit(LEAP_SECOND_SECOND) == Some(true);
+                let missing = if second < u64::from(SECONDS_PER_MINUTE) {
+                    elapsed_seconds - 1
+                } else if second == marker_second {
+                    events.push(ReceiverEvent::Minute {marker: time, frame: self.frame});
+                    events.extend(self.push_frame(time));
+                    self.frame = Frame::default();
+                    // The pulses before the gap of the marker
+                    (marker_second - 2).saturating_sub(u64::from(previous))
+                } else if second > marker_second && previous >= SECONDS_PER_MINUTE - 2 && !(leap_second && previous == SECONDS_PER_MINUTE - 2) {
+                    let marker = last_pulse + Duration::from_secs(marker_second - u64::from(previous));
+                    events.push(ReceiverEvent::Minute {marker, frame: self.frame});
+                    events.extend(self.push_frame(marker));
+                    self.frame = Frame::default();
+                    second - marker_second
+                } else {
+                    events.push(ReceiverEvent::Error {time, message: "Minute marker not found".to_string()});
+                    self.frame = Frame::default();
+                    self.second = None;
+                    return events;
+                };
+                if missing > 0 {
+                    self.quality.push_missing_pulses(missing);
+                    events.push(ReceiverEvent::Error {time, message: format!("{} pulses missing", missing)});
+                }
+                if second < marker_second { second as u8 } else { (second - marker_second) as u8 }
+            }
+            None if elapsed_seconds == 2 => 0,
+            None => return events
+        };
+        self.second = Some(second);
+        if let Some(value) = value {
+            self.frame.set_bit(second, value);
+        }
+        events.push(ReceiverEvent::Bit {time, second, value});
+        events
+    }
+
+    /// Decodes the frame that ends at `marker` for its quality, call bit and third party bits
+    fn push_frame(&mut self, marker: Duration) -> Vec<ReceiverEvent> {
+        let decoded = u64::try_from(self.frame).ok().and_then(|input| from_dcf77(input).ok());
+        self.quality.end_minute(marker, self.frame, decoded.is_some());
+        let Some(dcf_data) = decoded else {
+            self.third_party.reset();
+            return Vec::new();
+        };
+        let mut events = Vec::new();
+        if let Some(event) = self.call_bit.push(dcf_data) {
+            events.push(ReceiverEvent::CallBit {marker, event});
+        }
+        if let Some(packet) = self.third_party.push(dcf_data) {
+            events.push(ReceiverEvent::Packet {marker, packet});
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::prelude::*;
+    use crate::{DCF77, to_dcf77};
+    use crate::call_bit::CallBitStatus;
+    use crate::schedule::scheduled_minute;
+    use crate::third_party::split_packet;
+    fn test_frame() -> u64 {
+        to_dcf77(DCF77 {
+            date: Utc.with_ymd_and_hms(16, 12, 31, 23, 59, 0).unwrap(),
+            antenna: false,
+            announce_daily_saving_time: false,
+            daily_saving_time: false,
+            standard_time: true,
+            bit_leap_second: true,
+            third_party: 0
+        }).unwrap()
+    }
+    fn minutes(receiver: &mut Receiver, edges: &[Edge]) -> (Vec<(Duration, Frame)>, usize) {
+        let mut minutes = Vec::new();
+        let mut errors = 0;
+        for edge in edges {
+            for event in receiver.push_edge(*edge) {
+                match event {
+                    ReceiverEvent::Minute {marker, frame} => minutes.push((marker, frame)),
+                    ReceiverEvent::Error {..} => errors += 1,
+                    ReceiverEvent::Bit {..} | ReceiverEvent::CallBit {..} | ReceiverEvent::Packet {..} => {}
+                }
+            }
+        }
+        (minutes, errors)
+    }
+    #[test]
+    fn test_minutes() {
+        let mut edges = edges_from_frame(test_frame(), Duration::from_millis(20_300), false);
+        edges.extend(edges_from_frame(test_frame(), Duration::from_millis(80_300), true));
+        edges.extend(edges_from_frame(test_frame(), Duration::from_millis(141_300), false));
+        edges.extend(edges_from_frame(test_frame(), Duration::from_millis(201_300), false));
+        let mut receiver = Receiver::new();
+        let (minutes, errors) = minutes(&mut receiver, &edges);
+        assert!(errors == 0);
+        assert!(minutes.len() == 2);
+        assert!(minutes[0].0 == Duration::from_millis(141_300));
+        assert!(minutes[1].0 == Duration::from_millis(201_300));
+        assert!(minutes.iter().all(|(_, frame)| u64::try_from(*frame).unwrap() == test_frame()));
+        assert!(receiver.second() == Some(58));
+    }
+    #[test]
+    fn test_missing_pulses() {
+        let mut edges = edges_from_frame(test_frame(), Duration::ZERO, false);
+        let mut second_minute = edges_from_frame(test_frame(), Duration::from_secs(60), false);
+        second_minute.drain(10..14);
+        second_minute[20].time += Duration::from_millis(5);
+        edges.extend(second_minute);
+        edges.extend(edges_from_frame(test_frame(), Duration::from_secs(120), false));
+        edges.extend(edges_from_frame(test_frame(), Duration::from_secs(180), false));
+        edges.push(Edge {time: Duration::from_millis(120_500), level: true});
+        edges.push(Edge {time: Duration::from_millis(120_510), level: false});
+        edges.sort_by_key(|edge| edge.time);
+        let mut receiver = Receiver::new();
+        let (minutes, errors) = minutes(&mut receiver, &edges);
+        assert!(errors == 2);
+        assert!(minutes.len() == 2);
+        assert!(minutes[0].1.unknown_seconds() == vec![5, 6]);
+        assert!(minutes[0].1.soft_decode().is_ok());
+        assert!(u64::try_from(minutes[1].1).unwrap() == test_frame());
+        let quality = receiver.quality().rolling();
+        assert!(quality.minutes == 2);
+        assert!(quality.decode_rate() == Some(50.0));
+        assert!(quality.missing_pulses == 2 && quality.extra_pulses == 1);
+        assert!(quality.parity_checks == [2, 2, 2]);
+        assert!(quality.parity_failures == [0, 0, 0]);
+        assert!(quality.marker_jitter() == Some(Duration::ZERO));
+        assert!((quality.zero_widths.mean().unwrap() - 0.1).abs() < 1e-3);
+    }
+    #[test]
+    fn test_packets() {
+        let start = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+        // A minute to find the first marker, two packets and one to end the last minute
+        let mut minutes = vec![scheduled_minute(start, &[])];
+        for (index, chunk) in [0x123_4567_89AB, 0x3FF_FFFF_FFFF].into_iter().flat_map(split_packet).enumerate() {
+            minutes.push(scheduled_minute(start + chrono::Duration::minutes(index as i64 + 1), &[]).with_third_party(chunk).unwrap());
+        }
+        minutes.push(scheduled_minute(start + chrono::Duration::minutes(7), &[]));
+        let mut receiver = Receiver::new();
+        let mut packets = Vec::new();
+        for (index, minute) in minutes.iter().enumerate() {
+            for edge in edges_from_frame(minute.frame, Duration::from_secs(60 * index as u64), false) {
+                for event in receiver.push_edge(edge) {
+                    if let ReceiverEvent::Packet {marker, packet} = event {
+                        packets.push((marker, packet.bits(), packet.start()));
+                    }
+                }
+            }
+        }
+        assert!(packets == [
+            (Duration::from_secs(240), 0x123_4567_89AB, start),
+            (Duration::from_secs(420), 0x3FF_FFFF_FFFF, start + chrono::Duration::minutes(3))
+        ]);
+        assert!(receiver.call_bit().status() == Some(CallBitStatus::Normal));
+        assert!(receiver.call_bit().current().unwrap().minutes == 6);
+    }
+    #[test]
+    fn test_lost_second_zero() {
+        let frame = to_dcf77(DCF77 {
+            date: Utc.with_ymd_and_hms(23, 6, 14, 8, 30, 0).unwrap(),
+            antenna: false,
+            announce_daily_saving_time: false,
+            daily_saving_time: true,
+            standard_time: false,
+            bit_leap_second: false,
+            third_party: 0
+        }).unwrap();
+        let mut edges: Vec<Edge> = (0..4).flat_map(|minute| edges_from_frame(frame, Duration::from_secs(60 * minute), false)).collect();
+        // The pulse of the second 0 at 120 s
+        edges.retain(|edge| edge.time < Duration::from_secs(120) || edge.time > Duration::from_millis(120_500));
+        let mut receiver = Receiver::new();
+        let (found, errors) = minutes(&mut receiver, &edges);
+        assert!(errors == 1);
+        assert!(found.len() == 2);
+        assert!(found[0].0 == Duration::from_secs(120));
+        assert!(u64::try_from(found[0].1).unwrap() == frame);
+        assert!(found[1].0 == Duration::from_secs(180));
+        assert!(found[1].1.unknown_seconds() == vec![0]);
+        assert!(found[1].1.soft_decode().is_ok());
+        assert!(receiver.second() == Some(58));
+        assert!(receiver.quality().rolling().missing_pulses == 1);
+        // The same loss is ambiguous in a minute announcing a leap second
+        let mut edges: Vec<Edge> = (0..4).flat_map(|minute| edges_from_frame(test_frame(), Duration::from_secs(60 * minute), false)).collect();
+        edges.retain(|edge| edge.time < Duration::from_secs(120) || edge.time > Duration::from_millis(120_500));
+        let mut receiver = Receiver::new();
+        let (ambiguous, errors) = minutes(&mut receiver, &edges);
+        assert!(errors == 1);
+        assert!(ambiguous.is_empty());
+        assert!(receiver.second() == Some(58));
+    }
+    #[test]
+    fn test_lost_marker() {
+        let mut edges = edges_from_frame(test_frame(), Duration::ZERO, false);
+        edges.extend(edges_from_frame(test_frame(), Duration::from_secs(60), false));
+        edges.extend(edges_from_frame(test_frame(), Duration::from_secs(125), false));
+        let mut receiver = Receiver::new();
+        let (minutes, errors) = minutes(&mut receiver, &edges);
+        assert!(minutes.is_empty());
+        assert!(errors == 1);
+        assert!(receiver.second().is_none());
+    }
+}
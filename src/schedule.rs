@@ -0,0 +1,253 @@
+//! Schedule of the DCF77 minutes broadcast over a range of instants
+//!
+//! The telegram sent during a minute describes the next minute, which starts
+//! with the following minute marker. Every minute of a schedule is therefore
+//! given by the UTC instant of the marker that starts it, together with the
+//! German legal time, the `DCF77` struct and the bit field of its telegram.
+//!
+//! The announcement bits are set during the hour before the event: A1 in the
+//! 60 telegrams sent before a Daylight Saving Time switch and A2 in the 60
+//! telegrams sent before a leap second. The last of them is the first
+//! telegram that describes a minute after the event.
+
+use std::io::Error;
+use chrono::prelude::*;
+use crate::{DCF77, dcf77, to_dcf77_with_day_of_week};
+use crate::dst::DstRules;
+
+/// Months whose last minute held a leap second, as (year, month)
+pub const LEAP_SECOND_MONTHS: [(i32, u32); 27] = [(1972, 6), (1972, 12), (1973, 12), (1974, 12),
+                                                  (1975, 12), (1976, 12), (1977, 12), (1978, 12),
+                                                  (1979, 12), (1981, 6), (1982, 6), (1983, 6),
+                                                  (1985, 6), (1987, 12), (1989, 12), (1990, 12),
+                                                  (1992, 6), (1993, 6), (1994, 6), (1995, 12),
+                                                  (1997, 6), (1998, 12), (2005, 12), (2008, 12),
+                                                  (2012, 6), (2015, 6), (2016, 12)];
+
+/// Number of telegrams that carry an announcement before the event
+const ANNOUNCEMENT_MINUTES: i64 = 60;
+
+/// Instants right after the leap seconds of `LEAP_SECOND_MONTHS`
+///
+/// Every instant is the start of the month that follows a leap second.
+pub fn leap_seconds() -> Vec<DateTime<Utc>> {
+    LEAP_SECOND_MONTHS.iter().map(|(year, month)| {
+        let (year, month) = if *month == 12 { (year + 1, 1) } else { (*year, month + 1) };
+        Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).unwrap()
+    }).collect()
+}
+
+/// Whether the telegram of the minute starting at `instant` announces an event
+pub(crate) fn is_announced(instant: DateTime<Utc>, event: DateTime<Utc>) -> bool {
+    instant <= event && event - instant < chrono::Duration::minutes(ANNOUNCEMENT_MINUTES)
+}
+
+/// Minute of a schedule
+#[derive(Copy, Clone, Debug)]
+pub struct ScheduledMinute {
+    /// UTC instant of the minute marker that starts the minute
+    pub utc: DateTime<Utc>,
+    /// German legal time of the minute
+    pub local: DateTime<FixedOffset>,
+    /// Information of the telegram, the year being given within the century
+    pub dcf: DCF77,
+    /// Bit field of the telegram, as given by `to_dcf77`
+    pub frame: u64,
+    /// Whether the telegram is sent over 61 seconds because of a leap second
+    pub leap_second: bool
+}
+
+/// Creates the minute of a schedule starting at a UTC instant
+///
+/// `leap_seconds` holds the instants right after the leap seconds to
+/// announce, as given by `leap_seconds`. The seconds of `utc` are ignored.
+/// The time zone follows the German rules of `DstRules::german`.
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::*;
+///use dcf77_chrono::schedule::*;
+///let minute = scheduled_minute(Utc.with_ymd_and_hms(2023, 10, 29, 0, 59, 0).unwrap(), &[]);
+///assert!(minute.local.hour() == 2);
+///assert!(minute.dcf.daily_saving_time);
+///assert!(minute.dcf.announce_daily_saving_time);
+///assert!(to_utc(from_dcf77(minute.frame).unwrap()) == minute.utc);
+/// ```
+pub fn scheduled_minute(utc: DateTime<Utc>, leap_seconds: &[DateTime<Utc>]) -> ScheduledMinute {
+    scheduled_minute_with_rules(utc, leap_seconds, &DstRules::german())
+}
+
+/// Creates the minute of a schedule starting at a UTC instant with other Daylight Saving Time rules
+pub fn scheduled_minute_with_rules(utc: DateTime<Utc>, leap_seconds: &[DateTime<Utc>], rules: &DstRules) -> ScheduledMinute {
+    let utc = utc.with_second(0).unwrap().with_nanosecond(0).unwrap();
+    let daily_saving_time = rules.is_daylight_saving(utc);
+    let local = utc.with_timezone(&rules.utc_offset(utc));
+    let (start, end) = rules.switches(utc.year());
+    let local_date = local.naive_local();
+    let dcf = DCF77 {
+        date: Utc.from_utc_datetime(&local_date.with_year(local_date.year().rem_euclid(100)).unwrap()),
+        antenna: false,
+        announce_daily_saving_time: start.into_iter().chain(end).any(|switch| is_announced(utc, switch)),
+        daily_saving_time,
+        standard_time: !daily_saving_time,
+        bit_leap_second: leap_seconds.iter().any(|leap_second| is_announced(utc, *leap_second)),
+        third_party: 0
+    };
+    // The year within the century can move the day of the week, so it is
+    // coded out of the full date
+    let frame = to_dcf77_with_day_of_week(dcf, local.weekday()).expect("A year within the century is always coded");
+    ScheduledMinute {
+        utc,
+        local,
+        dcf,
+        frame,
+        leap_second: leap_seconds.contains(&utc)
+    }
+}
+
+impl ScheduledMinute {
+    /// Gives the same minute with other third party bits in its telegram
+    pub fn with_third_party(mut self, third_party: u16) -> Result<ScheduledMinute, Error> {
+        self.frame = dcf77::metadata::replace_third_party(self.frame, third_party)?;
+        self.dcf.third_party = third_party;
+        Ok(self)
+    }
+}
+
+/// Iterator over the minutes broadcast in a range of UTC instants
+///
+/// The range includes `from`, rounded up to a whole minute, and excludes `to`.
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::schedule::*;
+///let from = Utc.with_ymd_and_hms(2016, 12, 31, 23, 0, 0).unwrap();
+///let to = Utc.with_ymd_and_hms(2017, 1, 1, 1, 0, 0).unwrap();
+///let minutes: Vec<ScheduledMinute> = Schedule::new(from, to).collect();
+///assert!(minutes.len() == 120);
+///assert!(minutes.iter().filter(|minute| minute.dcf.bit_leap_second).count() == 60);
+///assert!(minutes.iter().filter(|minute| minute.leap_second).count() == 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    next: DateTime<Utc>,
+    to: DateTime<Utc>,
+    leap_seconds: Vec<DateTime<Utc>>,
+    rules: DstRules
+}
+
+impl Schedule {
+    /// Creates the schedule of a range with the known leap seconds
+    pub fn new(from: DateTime<Utc>, to: DateTime<Utc>) -> Schedule {
+        let start_of_minute = from.with_second(0).unwrap().with_nanosecond(0).unwrap();
+        let next = if start_of_minute < from {
+            start_of_minute + chrono::Duration::minutes(1)
+        } else {
+            start_of_minute
+        };
+        Schedule {next, to, leap_seconds: leap_seconds(), rules: DstRules::german()}
+    }
+
+    /// Replaces the leap seconds to announce, given as the instants right after them
+    pub fn with_leap_seconds(mut self, leap_seconds: Vec<DateTime<Utc>>) -> Schedule {
+        self.leap_seconds = leap_seconds;
+        self
+    }
+
+    /// Replaces the German Daylight Saving Time rules
+    pub fn with_dst_rules(mut self, rules: DstRules) -> Schedule {
+        self.rules = rules;
+        self
+    }
+}
+
+impl Iterator for Schedule {
+    type Item = ScheduledMinute;
+
+    fn next(&mut self) -> Option<ScheduledMinute> {
+        if self.next >= self.to {
+            return None;
+        }
+        let minute = scheduled_minute_with_rules(self.next, &self.leap_seconds, &self.rules);
+        self.next += chrono::Duration::minutes(1);
+        Some(minute)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.to - self.next).num_minutes().max(0);
+        let remaining = if self.next + chrono::Duration::minutes(remaining) < self.to { remaining + 1 } else { remaining };
+        let remaining = usize::try_from(remaining).unwrap_or(usize::MAX);
+        (remaining, Some(remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_dcf77, to_utc};
+    #[test]
+    fn test_dst_rules() {
+        // CEST ended in September until 1995
+        let from = Utc.with_ymd_and_hms(1990, 9, 29, 23, 0, 0).unwrap();
+        let minutes: Vec<ScheduledMinute> = Schedule::new(from, from + chrono::Duration::hours(3)).collect();
+        assert!(minutes.iter().filter(|minute| minute.dcf.announce_daily_saving_time).count() == 60);
+        assert!(minutes[119].dcf.daily_saving_time && minutes[120].dcf.standard_time);
+        assert!(minutes[120].utc == Utc.with_ymd_and_hms(1990, 9, 30, 1, 0, 0).unwrap());
+        // Without switches the last Sunday of October is a Sunday like any other
+        let from = Utc.with_ymd_and_hms(2031, 10, 26, 0, 0, 0).unwrap();
+        let rules = DstRules::german().with_abolition(2031, false);
+        let schedule = Schedule::new(from, from + chrono::Duration::hours(2)).with_dst_rules(rules);
+        assert!(schedule.clone().all(|minute| minute.dcf.standard_time && !minute.dcf.announce_daily_saving_time));
+        assert!(schedule.map(|minute| minute.local.offset().local_minus_utc()).all(|offset| offset == 3600));
+    }
+    #[test]
+    fn test_transitions() {
+        let from = Utc.with_ymd_and_hms(2023, 10, 28, 23, 30, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2023, 10, 29, 2, 0, 0).unwrap();
+        let minutes: Vec<ScheduledMinute> = Schedule::new(from, to).collect();
+        assert!(minutes.len() == 150);
+        let announced: Vec<DateTime<Utc>> = minutes.iter()
+            .filter(|minute| minute.dcf.announce_daily_saving_time)
+            .map(|minute| minute.utc)
+            .collect();
+        assert!(announced.len() == 60);
+        assert!(announced[0] == Utc.with_ymd_and_hms(2023, 10, 29, 0, 1, 0).unwrap());
+        assert!(announced[59] == Utc.with_ymd_and_hms(2023, 10, 29, 1, 0, 0).unwrap());
+        let switch = minutes.iter().position(|minute| minute.dcf.standard_time).unwrap();
+        assert!(minutes[switch - 1].local.hour() == 2 && minutes[switch - 1].local.minute() == 59);
+        assert!(minutes[switch].local.hour() == 2 && minutes[switch].local.minute() == 0);
+        for minute in minutes {
+            let decoded_minutes = from_dcf77(minute.frame).unwrap();
+            assert!(to_utc(decoded_minutes) == minute.utc);
+            assert!(decoded_minutes.announce_daily_saving_time == minute.dcf.announce_daily_saving_time);
+            assert!(!minute.leap_second);
+        }
+    }
+    #[test]
+    fn test_leap_seconds() {
+        let after_leap_second = Utc.with_ymd_and_hms(2017, 1, 1, 0, 0, 0).unwrap();
+        assert!(leap_seconds().len() == LEAP_SECOND_MONTHS.len());
+        assert!(leap_seconds().contains(&after_leap_second));
+        let minute = scheduled_minute(after_leap_second, &leap_seconds());
+        assert!(minute.leap_second && minute.dcf.bit_leap_second);
+        assert!(minute.local.hour() == 1);
+        let minute = scheduled_minute(after_leap_second - chrono::Duration::minutes(60), &leap_seconds());
+        assert!(!minute.dcf.bit_leap_second);
+        let minute = scheduled_minute(after_leap_second, &[]);
+        assert!(!minute.leap_second && !minute.dcf.bit_leap_second);
+        let schedule = Schedule::new(after_leap_second, after_leap_second + chrono::Duration::days(1))
+            .with_leap_seconds(Vec::new());
+        assert!(schedule.size_hint() == (1440, Some(1440)));
+        assert!(schedule.filter(|minute| minute.dcf.bit_leap_second).count() == 0);
+    }
+    #[test]
+    fn test_day_of_week() {
+        let from = Utc.with_ymd_and_hms(1995, 6, 1, 12, 0, 30).unwrap();
+        let minute = Schedule::new(from, from + chrono::Duration::hours(1)).next().unwrap();
+        assert!(minute.utc == Utc.with_ymd_and_hms(1995, 6, 1, 12, 1, 0).unwrap());
+        assert!(dcf77::date::process_day_of_week(minute.frame).unwrap() == 4);
+        assert!(from_dcf77(minute.frame).is_ok());
+    }
+}
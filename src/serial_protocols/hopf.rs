@@ -0,0 +1,228 @@
+//! Hopf 6021 compatible telegram
+//!
+//! The telegram is `<STX>swhhmmssddmmyy<LF><CR><ETX>` where `s` and `w` are
+//! two hexadecimal nibbles:
+//!
+//! | Nibble | Bits | Content                                                   |
+//! |--------|------|-----------------------------------------------------------|
+//! | `s`    | 0    | Announcement of a Daylight Saving Time switch             |
+//! | `s`    | 1    | Daylight Saving Time is active                            |
+//! | `s`    | 2..4 | 0 time not valid, 1 quartz, 2 radio, 3 radio high accuracy |
+//! | `w`    | 0..3 | Day of the week [1..7], Monday being 1                    |
+//! | `w`    | 3    | The time is given in UTC instead of local time            |
+//!
+//! The telegram has no flag for the antenna bit nor for the announcement of a
+//! leap second, which are lost when parsing. The time is always given in local
+//! time, and telegrams flagged as UTC are rejected.
+
+use std::io::Error;
+use std::io::ErrorKind;
+use chrono::prelude::*;
+use crate::DCF77;
+use super::ReceiverStatus;
+
+/// Start of text character that opens the telegram
+const STX: char = '\u{02}';
+
+/// Characters that close the telegram
+const END: &str = "\n\r\u{03}";
+
+/// Status bit for the announcement of a Daylight Saving Time switch
+const STATUS_ANNOUNCE_DAYLIGHT_SAVING: u32 = 0x1;
+
+/// Status bit for Daylight Saving Time
+const STATUS_DAYLIGHT_SAVING: u32 = 0x2;
+
+/// Position of the operation mode in the status nibble
+const STATUS_MODE_POSITION: u32 = 2;
+
+/// Operation mode for a time that is not valid
+const MODE_INVALID: u32 = 0;
+
+/// Operation mode for a clock running on its quartz
+const MODE_QUARTZ: u32 = 1;
+
+/// Operation mode for a clock synchronized by the radio signal
+const MODE_RADIO: u32 = 2;
+
+/// Weekday nibble bit for a time given in UTC
+const WEEKDAY_UTC: u32 = 0x8;
+
+/// Mask of the day of the week in the weekday nibble
+const WEEKDAY_MASK: u32 = 0x7;
+
+/// Creates the Hopf 6021 telegram of a decoded DCF77 minute
+///
+/// A receiver that has not been synchronized since the last reset is reported
+/// as not valid, otherwise as running on radio or quartz.
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::DCF77;
+///use dcf77_chrono::serial_protocols::ReceiverStatus;
+///use dcf77_chrono::serial_protocols::hopf::*;
+///let test_time = DCF77 {
+///    date: Utc.with_ymd_and_hms(23, 2, 1, 12, 34, 56).unwrap(),
+///    antenna: false,
+///    announce_daily_saving_time: false,
+///    daily_saving_time: false,
+///    standard_time: true,
+///    bit_leap_second: false,
+///    third_party: 0
+///};
+///let status = ReceiverStatus {
+///    synchronized_since_reset: true,
+///    radio_operation: true
+///};
+///assert!(to_hopf6021(test_time, status) == "\u{02}83123456010223\n\r\u{03}");
+/// ```
+pub fn to_hopf6021(dcf_data: DCF77, status: ReceiverStatus) -> String {
+    let given_date = dcf_data.date.date_naive();
+    let given_time = dcf_data.date.time();
+    let mode = if !status.synchronized_since_reset {
+        MODE_INVALID
+    } else if status.radio_operation {
+        MODE_RADIO
+    } else {
+        MODE_QUARTZ
+    };
+    let mut status_nibble = mode << STATUS_MODE_POSITION;
+    if dcf_data.announce_daily_saving_time {
+        status_nibble |= STATUS_ANNOUNCE_DAYLIGHT_SAVING;
+    }
+    if dcf_data.daily_saving_time {
+        status_nibble |= STATUS_DAYLIGHT_SAVING;
+    }
+    let weekday_nibble = given_date.weekday().number_from_monday();
+    format!("{}{:X}{:X}{:02}{:02}{:02}{:02}{:02}{:02}{}",
+            STX,
+            status_nibble,
+            weekday_nibble,
+            given_time.hour(),
+            given_time.minute(),
+            given_time.second(),
+            given_date.day(),
+            given_date.month(),
+            given_date.year().rem_euclid(100),
+            END)
+}
+
+/// Creates the error returned for a malformed telegram
+fn invalid_telegram(input: &str) -> Error {
+    let error_payload = format!("Invalid Hopf 6021 telegram: {:?}", input);
+    Error::new(ErrorKind::InvalidData, error_payload)
+}
+
+/// Parses a Hopf 6021 telegram into a DCF77 struct and the state of the receiver
+///
+/// The year is given with two digits, the same way `from_dcf77` does. The day
+/// of the week is checked against the date, and a time flagged as UTC is
+/// rejected.
+pub fn from_hopf6021(input: &str) -> Result<(DCF77, ReceiverStatus), Error> {
+    let body = input.strip_prefix(STX)
+        .and_then(|body| body.strip_suffix(END))
+        .ok_or_else(|| invalid_telegram(input))?;
+    if body.len() != 14 || !body.is_ascii() {
+        return Err(invalid_telegram(input));
+    }
+    let status_nibble = u32::from_str_radix(&body[0..1], 16).map_err(|_| invalid_telegram(input))?;
+    let weekday_nibble = u32::from_str_radix(&body[1..2], 16).map_err(|_| invalid_telegram(input))?;
+    let mut values = [0; 6];
+    for (index, value) in values.iter_mut().enumerate() {
+        let digits = &body[2 + 2 * index..4 + 2 * index];
+        if !digits.bytes().all(|digit| digit.is_ascii_digit()) {
+            return Err(invalid_telegram(input));
+        }
+        *value = digits.parse().map_err(|_| invalid_telegram(input))?;
+    }
+    if weekday_nibble & WEEKDAY_UTC > 0 {
+        let error_payload = format!("Hopf 6021 telegram in UTC instead of local time: {:?}", input);
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    let [hour, minute, second, day, month, year] = values;
+    let date = Utc.with_ymd_and_hms(year as i32, month, day, hour, minute, second)
+        .single()
+        .ok_or_else(|| invalid_telegram(input))?;
+    if date.weekday().number_from_monday() != weekday_nibble & WEEKDAY_MASK {
+        let error_payload = format!("Day of the week {} does not match the date {}", weekday_nibble & WEEKDAY_MASK, date);
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    let mode = status_nibble >> STATUS_MODE_POSITION;
+    let status = ReceiverStatus {
+        synchronized_since_reset: mode != MODE_INVALID,
+        radio_operation: mode >= MODE_RADIO
+    };
+    let daily_saving_time = status_nibble & STATUS_DAYLIGHT_SAVING > 0;
+    let output = DCF77 {
+        date,
+        antenna: false,
+        announce_daily_saving_time: status_nibble & STATUS_ANNOUNCE_DAYLIGHT_SAVING > 0,
+        daily_saving_time,
+        standard_time: !daily_saving_time,
+        bit_leap_second: false,
+        third_party: 0};
+    Ok((output, status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_round_trip() {
+        let original_test_time = Utc.with_ymd_and_hms(23, 1, 1, 0, 0, 0).unwrap();
+        for fake_input in 0..=0xfff {
+            let test_time = DCF77 {
+                date: original_test_time + chrono::Duration::seconds(fake_input * 7_919),
+                antenna: false,
+                announce_daily_saving_time: fake_input & 1 > 0,
+                daily_saving_time: fake_input & 2 > 0,
+                standard_time: fake_input & 2 == 0,
+                bit_leap_second: fake_input & 4 > 0,
+                third_party: 0
+            };
+            let status = ReceiverStatus {
+                synchronized_since_reset: fake_input & 8 > 0,
+                radio_operation: fake_input & 24 == 24
+            };
+            let telegram = to_hopf6021(test_time, status);
+            match from_hopf6021(&telegram) {
+                Ok((decoded_minutes, decoded_status)) => {
+                    assert!(test_time.date == decoded_minutes.date);
+                    assert!(test_time.announce_daily_saving_time == decoded_minutes.announce_daily_saving_time);
+                    assert!(test_time.daily_saving_time == decoded_minutes.daily_saving_time);
+                    assert!(test_time.standard_time == decoded_minutes.standard_time);
+                    assert!(!decoded_minutes.bit_leap_second);
+                    assert!(status == decoded_status);
+                }
+                Err(error) => {
+                    panic!("Error on parsing {:?} {:?}", telegram, error)
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_known_telegrams() {
+        let (decoded_minutes, status) = from_hopf6021("\u{02}F7235959311223\n\r\u{03}").unwrap();
+        assert!(decoded_minutes.date == Utc.with_ymd_and_hms(23, 12, 31, 23, 59, 59).unwrap());
+        assert!(decoded_minutes.announce_daily_saving_time);
+        assert!(decoded_minutes.daily_saving_time);
+        assert!(status.synchronized_since_reset);
+        assert!(status.radio_operation);
+        let (decoded_minutes, status) = from_hopf6021("\u{02}41000000010124\n\r\u{03}").unwrap();
+        assert!(decoded_minutes.standard_time);
+        assert!(!decoded_minutes.bit_leap_second);
+        assert!(status.synchronized_since_reset);
+        assert!(!status.radio_operation);
+    }
+    #[test]
+    fn test_invalid_telegrams() {
+        assert!(from_hopf6021("83123456010223").is_err());
+        assert!(from_hopf6021("\u{02}84123456010223\n\r\u{03}").is_err());
+        assert!(from_hopf6021("\u{02}8312345601022\n\r\u{03}").is_err());
+        assert!(from_hopf6021("\u{02}83123456320223\n\r\u{03}").is_err());
+        assert!(from_hopf6021("\u{02}8312+456010223\n\r\u{03}").is_err());
+        // Flagged as UTC
+        assert!(from_hopf6021("\u{02}8B123456010223\n\r\u{03}").is_err());
+    }
+}
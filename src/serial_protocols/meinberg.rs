@@ -0,0 +1,225 @@
+//! Meinberg Standard Time String
+//!
+//! The telegram is `<STX>D:dd.mm.yy;T:w;U:hh.mm.ss;uvxy<ETX>` where:
+//! - `w` is the day of the week [1..7], Monday being 1
+//! - `u` is `#` when the clock has not been synchronized since the last reset
+//! - `v` is `*` when the clock runs on its quartz instead of the radio signal
+//! - `x` is `S` during Daylight Saving Time, `U` for UTC and a space for standard time
+//! - `y` is `!` when a Daylight Saving Time switch is announced, `A` when a
+//!   leap second is announced and a space otherwise
+//!
+//! The telegram has no flag for the antenna bit, which is lost when parsing.
+//! When both announcements are set, the Daylight Saving Time one is sent.
+
+use std::io::Error;
+use std::io::ErrorKind;
+use chrono::prelude::*;
+use crate::DCF77;
+use super::ReceiverStatus;
+
+/// Start of text character that opens the telegram
+const STX: char = '\u{02}';
+
+/// End of text character that closes the telegram
+const ETX: char = '\u{03}';
+
+/// Creates the Standard Time String of a decoded DCF77 minute
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::DCF77;
+///use dcf77_chrono::serial_protocols::ReceiverStatus;
+///use dcf77_chrono::serial_protocols::meinberg::*;
+///let test_time = DCF77 {
+///    date: Utc.with_ymd_and_hms(23, 10, 29, 2, 59, 0).unwrap(),
+///    antenna: false,
+///    announce_daily_saving_time: true,
+///    daily_saving_time: true,
+///    standard_time: false,
+///    bit_leap_second: false,
+///    third_party: 0
+///};
+///let status = ReceiverStatus {
+///    synchronized_since_reset: true,
+///    radio_operation: true
+///};
+///let telegram = to_standard_time_string(test_time, status);
+///assert!(telegram == "\u{02}D:29.10.23;T:7;U:02.59.00;  S!\u{03}");
+/// ```
+pub fn to_standard_time_string(dcf_data: DCF77, status: ReceiverStatus) -> String {
+    let given_date = dcf_data.date.date_naive();
+    let given_time = dcf_data.date.time();
+    let time_zone = if dcf_data.daily_saving_time {
+        'S'
+    } else if dcf_data.standard_time {
+        ' '
+    } else {
+        'U'
+    };
+    let announcement = if dcf_data.announce_daily_saving_time {
+        '!'
+    } else if dcf_data.bit_leap_second {
+        'A'
+    } else {
+        ' '
+    };
+    format!("{}D:{:02}.{:02}.{:02};T:{};U:{:02}.{:02}.{:02};{}{}{}{}{}",
+            STX,
+            given_date.day(),
+            given_date.month(),
+            given_date.year().rem_euclid(100),
+            given_date.weekday().number_from_monday(),
+            given_time.hour(),
+            given_time.minute(),
+            given_time.second(),
+            if status.synchronized_since_reset { ' ' } else { '#' },
+            if status.radio_operation { ' ' } else { '*' },
+            time_zone,
+            announcement,
+            ETX)
+}
+
+/// Creates the error returned for a malformed telegram
+fn invalid_telegram(input: &str) -> Error {
+    let error_payload = format!("Invalid Meinberg Standard Time String: {:?}", input);
+    Error::new(ErrorKind::InvalidData, error_payload)
+}
+
+/// Parses the three two digit numbers of a `dd.mm.yy` or `hh.mm.ss` field
+fn parse_triplet(field: &str, input: &str) -> Result<[u32; 3], Error> {
+    let mut output = [0; 3];
+    let mut parts = field.split('.');
+    for value in output.iter_mut() {
+        match parts.next() {
+            Some(part) if part.len() == 2 => {
+                *value = part.parse().map_err(|_| invalid_telegram(input))?;
+            }
+            _ => return Err(invalid_telegram(input))
+        }
+    }
+    if parts.next().is_some() {
+        return Err(invalid_telegram(input));
+    }
+    Ok(output)
+}
+
+/// Parses a Standard Time String into a DCF77 struct and the state of the receiver
+///
+/// The year is given with two digits, the same way `from_dcf77` does. The day
+/// of the week is checked against the date.
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::serial_protocols::meinberg::*;
+///let (decoded_minutes, status) = from_standard_time_string("\u{02}D:30.06.15;T:2;U:01.59.59;  SA\u{03}").unwrap();
+///assert!(decoded_minutes.date == Utc.with_ymd_and_hms(15, 6, 30, 1, 59, 59).unwrap());
+///assert!(decoded_minutes.bit_leap_second);
+///assert!(status.radio_operation);
+/// ```
+pub fn from_standard_time_string(input: &str) -> Result<(DCF77, ReceiverStatus), Error> {
+    let body = input.strip_prefix(STX)
+        .and_then(|body| body.strip_suffix(ETX))
+        .ok_or_else(|| invalid_telegram(input))?;
+    let fields: Vec<&str> = body.split(';').collect();
+    if fields.len() != 4 {
+        return Err(invalid_telegram(input));
+    }
+    let [day, month, year] = parse_triplet(fields[0].strip_prefix("D:").ok_or_else(|| invalid_telegram(input))?, input)?;
+    let day_of_week: u32 = fields[1].strip_prefix("T:")
+        .and_then(|field| field.parse().ok())
+        .ok_or_else(|| invalid_telegram(input))?;
+    let [hour, minute, second] = parse_triplet(fields[2].strip_prefix("U:").ok_or_else(|| invalid_telegram(input))?, input)?;
+    let flags: Vec<char> = fields[3].chars().collect();
+    if flags.len() != 4 {
+        return Err(invalid_telegram(input));
+    }
+    let date = Utc.with_ymd_and_hms(year as i32, month, day, hour, minute, second)
+        .single()
+        .ok_or_else(|| invalid_telegram(input))?;
+    if date.weekday().number_from_monday() != day_of_week {
+        let error_payload = format!("Day of the week {} does not match the date {}", day_of_week, date);
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    let status = ReceiverStatus {
+        synchronized_since_reset: match flags[0] { ' ' => true, '#' => false, _ => return Err(invalid_telegram(input)) },
+        radio_operation: match flags[1] { ' ' => true, '*' => false, _ => return Err(invalid_telegram(input)) }
+    };
+    let (daily_saving_time, standard_time) = match flags[2] {
+        'S' => (true, false),
+        ' ' => (false, true),
+        'U' => (false, false),
+        _ => return Err(invalid_telegram(input))
+    };
+    let (announce_daily_saving_time, bit_leap_second) = match flags[3] {
+        '!' => (true, false),
+        'A' => (false, true),
+        ' ' => (false, false),
+        _ => return Err(invalid_telegram(input))
+    };
+    let output = DCF77 {
+        date,
+        antenna: false,
+        announce_daily_saving_time,
+        daily_saving_time,
+        standard_time,
+        bit_leap_second,
+        third_party: 0};
+    Ok((output, status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_round_trip() {
+        let original_test_time = Utc.with_ymd_and_hms(23, 1, 1, 0, 0, 0).unwrap();
+        for fake_input in 0..=0xfff {
+            let test_time = DCF77 {
+                date: original_test_time + chrono::Duration::seconds(fake_input * 7_919),
+                antenna: false,
+                announce_daily_saving_time: fake_input & 1 > 0,
+                daily_saving_time: fake_input & 2 > 0,
+                standard_time: fake_input & 2 == 0,
+                bit_leap_second: fake_input & 5 == 4,
+                third_party: 0
+            };
+            let status = ReceiverStatus {
+                synchronized_since_reset: fake_input & 8 > 0,
+                radio_operation: fake_input & 16 > 0
+            };
+            let telegram = to_standard_time_string(test_time, status);
+            match from_standard_time_string(&telegram) {
+                Ok((decoded_minutes, decoded_status)) => {
+                    assert!(test_time.date == decoded_minutes.date);
+                    assert!(test_time.announce_daily_saving_time == decoded_minutes.announce_daily_saving_time);
+                    assert!(test_time.daily_saving_time == decoded_minutes.daily_saving_time);
+                    assert!(test_time.standard_time == decoded_minutes.standard_time);
+                    assert!(test_time.bit_leap_second == decoded_minutes.bit_leap_second);
+                    assert!(status == decoded_status);
+                }
+                Err(error) => {
+                    panic!("Error on parsing {:?} {:?}", telegram, error)
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_known_telegrams() {
+        let (decoded_minutes, status) = from_standard_time_string("\u{02}D:01.01.24;T:1;U:00.00.00;#*U \u{03}").unwrap();
+        assert!(decoded_minutes.date == Utc.with_ymd_and_hms(24, 1, 1, 0, 0, 0).unwrap());
+        assert!(!decoded_minutes.daily_saving_time);
+        assert!(!decoded_minutes.standard_time);
+        assert!(!status.synchronized_since_reset);
+        assert!(!status.radio_operation);
+    }
+    #[test]
+    fn test_invalid_telegrams() {
+        assert!(from_standard_time_string("D:01.01.24;T:1;U:00.00.00;    ").is_err());
+        assert!(from_standard_time_string("\u{02}D:01.01.24;T:2;U:00.00.00;    \u{03}").is_err());
+        assert!(from_standard_time_string("\u{02}D:01.13.24;T:1;U:00.00.00;    \u{03}").is_err());
+        assert!(from_standard_time_string("\u{02}D:01.01.24;T:1;U:00.00.00;   X\u{03}").is_err());
+        assert!(from_standard_time_string("\u{02}D:01.01.24;T:1;U:0.00.00;    \u{03}").is_err());
+    }
+}
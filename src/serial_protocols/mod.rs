@@ -0,0 +1,16 @@
+//! Serial time telegrams of commercial DCF77 receivers
+//!
+//! Each submodule renders a decoded `DCF77` struct and the state of the
+//! receiver into the telegram of a family of receivers, and parses it back.
+
+pub mod hopf;
+pub mod meinberg;
+
+/// State of the receiver reported along with the time in the serial telegrams
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ReceiverStatus {
+    /// The clock has been synchronized by the radio signal since the last reset
+    pub synchronized_since_reset: bool,
+    /// The clock is currently synchronized by the radio signal instead of running on its quartz
+    pub radio_operation: bool
+}
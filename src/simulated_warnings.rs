@@ -0,0 +1,345 @@
+//! Simulated civil protection warnings carried by the third party bits
+//!
+//! The German Federal Office of Civil Protection (BBK) sends warnings over
+//! the third party bits, sharing them with the Meteotime packets. The layout
+//! of those warnings is not published, so this module does NOT decode them:
+//! it defines a simulation format of its own, carried by the 42 bit packets of
+//! the `third_party` module, to generate and check test signals. Packets
+//! received from DCF77 are not expected to follow it.
+//!
+//! A warning is a header packet followed by up to 15 packets of region
+//! codes, all sent in consecutive packets. From the most significant bit:
+//! - 8 bits: signature `0xA5`
+//! - 1 bit: 0 for a header, 1 for regions
+//! - 4 bits: identifier of the warning
+//! - header: 3 bits of level, 5 bits of message type, 4 bits with the number
+//!   of region packets and 9 bits set to 0
+//! - regions: two 10 bit region codes, 0 when unused, and 1 bit set to 0
+//! - 8 bits: XOR of the bytes of the previous 34 bits
+//!
+//! Packets without the signature or with a wrong checksum are taken as
+//! weather data and ignored.
+//!
+//! Only available with the `simulated-warnings` feature.
+
+use std::io::Error;
+use std::io::ErrorKind;
+use chrono::prelude::*;
+use crate::schedule::{Schedule, ScheduledMinute};
+use crate::third_party::{CHUNKS_PER_PACKET, ThirdPartyPacket, split_packet};
+
+/// Signature of the warning packets
+pub const SIGNATURE: u64 = 0xA5;
+
+/// Largest number of region packets of a warning
+pub const MAX_REGION_PACKETS: usize = 15;
+
+/// Region codes carried by a region packet
+pub const REGIONS_PER_PACKET: usize = 2;
+
+/// Largest region code
+pub const MAX_REGION: u16 = 0x3FF;
+
+/// Largest warning identifier
+pub const MAX_IDENTIFIER: u8 = 0xF;
+
+/// Level of a warning
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WarningLevel {
+    /// End of a previous warning
+    AllClear,
+    /// Advisory
+    Advisory,
+    /// Warning
+    Warning,
+    /// Severe warning
+    Severe,
+    /// Extreme danger
+    Extreme
+}
+
+/// Every warning level, indexed by its code
+pub const WARNING_LEVELS: [WarningLevel; 5] = [WarningLevel::AllClear, WarningLevel::Advisory, WarningLevel::Warning, WarningLevel::Severe, WarningLevel::Extreme];
+
+/// Type of a warning message
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MessageType {
+    /// Test message, no action required
+    Test,
+    /// Fire
+    Fire,
+    /// Flood
+    Flood,
+    /// Storm or severe weather
+    Storm,
+    /// Chemical hazard
+    Chemical,
+    /// Radiological hazard
+    Radiological,
+    /// Epidemic
+    Epidemic,
+    /// Terror or violence
+    Terror,
+    /// Failure of infrastructure such as power or water supply
+    Infrastructure,
+    /// Other danger
+    Other
+}
+
+/// Every message type, indexed by its code
+pub const MESSAGE_TYPES: [MessageType; 10] = [
+    MessageType::Test, MessageType::Fire, MessageType::Flood, MessageType::Storm, MessageType::Chemical,
+    MessageType::Radiological, MessageType::Epidemic, MessageType::Terror, MessageType::Infrastructure, MessageType::Other
+];
+
+/// Civil protection warning of the simulation format
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Warning {
+    /// Identifier of the warning [0..15]
+    pub id: u8,
+    /// Level of the warning
+    pub level: WarningLevel,
+    /// Type of the message
+    pub message_type: MessageType,
+    /// Codes of the regions concerned [1..1023]
+    pub regions: Vec<u16>,
+    /// UTC instant at which the transmission of the header started
+    pub start: DateTime<Utc>
+}
+
+/// Packet of a warning
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum WarningPacket {
+    /// First packet of a warning
+    Header {id: u8, level: WarningLevel, message_type: MessageType, region_packets: usize},
+    /// Region codes of a warning
+    Regions {id: u8, regions: [u16; REGIONS_PER_PACKET]}
+}
+
+/// XOR of the bytes of the bits of a packet above the checksum
+fn checksum(packet: u64) -> u64 {
+    let mut bits = packet >> 8;
+    let mut output = 0;
+    while bits != 0 {
+        output ^= bits & 0xFF;
+        bits >>= 8;
+    }
+    output
+}
+
+/// Value of the bits of a packet from `position` on
+fn field(packet: u64, position: u32, bits: u32) -> u64 {
+    (packet >> position) & ((1 << bits) - 1)
+}
+
+/// Reads a packet, `None` when it is not a valid warning packet
+fn read_packet(packet: u64) -> Option<WarningPacket> {
+    if field(packet, 34, 8) != SIGNATURE || field(packet, 0, 8) != checksum(packet) {
+        return None;
+    }
+    let id = field(packet, 29, 4) as u8;
+    if field(packet, 33, 1) == 0 {
+        Some(WarningPacket::Header {
+            id,
+            level: *WARNING_LEVELS.get(field(packet, 26, 3) as usize)?,
+            message_type: *MESSAGE_TYPES.get(field(packet, 21, 5) as usize)?,
+            region_packets: field(packet, 17, 4) as usize
+        })
+    } else {
+        Some(WarningPacket::Regions {id, regions: [field(packet, 19, 10) as u16, field(packet, 9, 10) as u16]})
+    }
+}
+
+/// Writes a packet with its signature and checksum
+fn write_packet(packet: WarningPacket) -> u64 {
+    let bits = match packet {
+        WarningPacket::Header {id, level, message_type, region_packets} => {
+            let level = WARNING_LEVELS.iter().position(|other| *other == level).unwrap();
+            let message_type = MESSAGE_TYPES.iter().position(|other| *other == message_type).unwrap();
+            u64::from(id) << 29 | (level as u64) << 26 | (message_type as u64) << 21 | (region_packets as u64) << 17
+        }
+        WarningPacket::Regions {id, regions} => {
+            1 << 33 | u64::from(id) << 29 | u64::from(regions[0]) << 19 | u64::from(regions[1]) << 9
+        }
+    };
+    let bits = SIGNATURE << 34 | bits;
+    bits | checksum(bits)
+}
+
+/// Codes a warning into its packets
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::simulated_warnings::*;
+///let warning = Warning {
+///    id: 3,
+///    level: WarningLevel::Severe,
+///    message_type: MessageType::Flood,
+///    regions: vec![101, 102, 250],
+///    start: Utc.with_ymd_and_hms(2023, 7, 2, 14, 30, 0).unwrap()
+///};
+///let packets = code_warning(&warning).unwrap();
+///assert!(packets.len() == 3);
+///let mut decoder = WarningDecoder::new();
+///assert!(decoder.push_packet(packets[0], warning.start).is_none());
+///assert!(decoder.push_packet(packets[1], warning.start + chrono::Duration::minutes(3)).is_none());
+///assert!(decoder.push_packet(packets[2], warning.start + chrono::Duration::minutes(6)) == Some(warning));
+/// ```
+pub fn code_warning(warning: &Warning) -> Result<Vec<u64>, Error> {
+    let region_packets = (warning.regions.len() + REGIONS_PER_PACKET - 1) / REGIONS_PER_PACKET;
+    if warning.id > MAX_IDENTIFIER || region_packets > MAX_REGION_PACKETS || warning.regions.iter().any(|region| *region == 0 || *region > MAX_REGION) {
+        let error_payload = format!("Warning cannot be coded - Identifier: {} Regions: {:?}", warning.id, warning.regions);
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    let mut packets = vec![write_packet(WarningPacket::Header {id: warning.id, level: warning.level, message_type: warning.message_type, region_packets})];
+    for regions in warning.regions.chunks(REGIONS_PER_PACKET) {
+        let regions = [regions[0], regions.get(1).copied().unwrap_or(0)];
+        packets.push(write_packet(WarningPacket::Regions {id: warning.id, regions}));
+    }
+    Ok(packets)
+}
+
+/// Places a warning in the minutes of the broadcast schedule from its start
+///
+/// The start must be a multiple of three minutes, as for every third party
+/// packet.
+pub fn warning_minutes(warning: &Warning) -> Result<Vec<ScheduledMinute>, Error> {
+    if warning.start.minute() % CHUNKS_PER_PACKET as u32 != 0 || warning.start.second() != 0 || warning.start.nanosecond() != 0 {
+        let error_payload = format!("Warning not aligned on a packet - Start: {}", warning.start);
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    let packets = code_warning(warning)?;
+    let from = warning.start + chrono::Duration::minutes(1);
+    let to = from + chrono::Duration::minutes((packets.len() * CHUNKS_PER_PACKET) as i64);
+    let chunks = packets.into_iter().flat_map(split_packet);
+    Schedule::new(from, to).zip(chunks).map(|(minute, chunk)| minute.with_third_party(chunk)).collect()
+}
+
+/// Warning being collected by a decoder
+#[derive(Clone, Debug)]
+struct PendingWarning {
+    warning: Warning,
+    region_packets: usize,
+    last_start: DateTime<Utc>
+}
+
+/// Collector of the packets of warnings
+///
+/// Warning packets must follow each other without gap; a missing or foreign
+/// packet drops the warning being collected.
+#[derive(Clone, Debug, Default)]
+pub struct WarningDecoder {
+    pending: Option<PendingWarning>
+}
+
+impl WarningDecoder {
+    /// Creates a decoder waiting for a header
+    pub fn new() -> WarningDecoder {
+        WarningDecoder::default()
+    }
+
+    /// Pushes a packet from the third party aggregator, giving the warning it completes
+    pub fn push(&mut self, packet: &ThirdPartyPacket) -> Option<Warning> {
+        self.push_packet(packet.bits(), packet.start())
+    }
+
+    /// Pushes the bits of a packet whose transmission started at `start`
+    pub fn push_packet(&mut self, packet: u64, start: DateTime<Utc>) -> Option<Warning> {
+        let pending = self.pending.take();
+        let pending = match read_packet(packet)? {
+            WarningPacket::Header {id, level, message_type, region_packets} => PendingWarning {
+                warning: Warning {id, level, message_type, regions: Vec::new(), start},
+                region_packets,
+                last_start: start
+            },
+            WarningPacket::Regions {id, regions} => {
+                let mut pending = pending?;
+                let follows = pending.last_start + chrono::Duration::minutes(CHUNKS_PER_PACKET as i64) == start;
+                if pending.warning.id != id || !follows {
+                    return None;
+                }
+                pending.warning.regions.extend(regions.iter().filter(|region| **region != 0));
+                pending.region_packets -= 1;
+                pending.last_start = start;
+                pending
+            }
+        };
+        if pending.region_packets == 0 {
+            return Some(pending.warning);
+        }
+        self.pending = Some(pending);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_dcf77;
+    use crate::third_party::ThirdPartyAggregator;
+    fn test_warning(regions: Vec<u16>) -> Warning {
+        Warning {
+            id: 9,
+            level: WarningLevel::Extreme,
+            message_type: MessageType::Chemical,
+            regions,
+            start: Utc.with_ymd_and_hms(2023, 10, 29, 0, 54, 0).unwrap()
+        }
+    }
+    #[test]
+    fn test_packets() {
+        let warning = test_warning(vec![1, 1023, 512]);
+        let packets = code_warning(&warning).unwrap();
+        assert!(packets.iter().all(|packet| packet >> 34 == SIGNATURE && *packet < 1 << 42));
+        assert!(read_packet(packets[1]) == Some(WarningPacket::Regions {id: 9, regions: [1, 1023]}));
+        assert!(read_packet(packets[2]) == Some(WarningPacket::Regions {id: 9, regions: [512, 0]}));
+        // Any single bit error is caught by the signature or the checksum
+        for bit in 0..42 {
+            assert!(read_packet(packets[0] ^ (1 << bit)).is_none());
+        }
+        assert!(code_warning(&test_warning(vec![0])).is_err());
+        assert!(code_warning(&test_warning(vec![1024])).is_err());
+        assert!(code_warning(&test_warning((1..=31).collect())).is_err());
+        assert!(code_warning(&test_warning((1..=30).collect())).unwrap().len() == 16);
+        assert!(code_warning(&test_warning(Vec::new())).unwrap().len() == 1);
+    }
+    #[test]
+    fn test_decoder() {
+        let warning = test_warning(vec![7, 8, 9]);
+        let packets = code_warning(&warning).unwrap();
+        let at = |packet: i64| warning.start + chrono::Duration::minutes(3 * packet);
+        let mut decoder = WarningDecoder::new();
+        // A weather packet in between drops the warning
+        assert!(decoder.push_packet(packets[0], at(0)).is_none());
+        assert!(decoder.push_packet(0x123_4567_89AB, at(1)).is_none());
+        assert!(decoder.push_packet(packets[1], at(2)).is_none());
+        assert!(decoder.push_packet(packets[2], at(3)).is_none());
+        // So does a gap
+        assert!(decoder.push_packet(packets[0], at(4)).is_none());
+        assert!(decoder.push_packet(packets[1], at(6)).is_none());
+        assert!(decoder.push_packet(packets[2], at(7)).is_none());
+        assert!(decoder.push_packet(packets[0], at(8)).is_none());
+        assert!(decoder.push_packet(packets[1], at(9)).is_none());
+        assert!(decoder.push_packet(packets[2], at(10)) == Some(Warning {start: at(8), ..warning}));
+        let all_clear = Warning {level: WarningLevel::AllClear, regions: Vec::new(), ..test_warning(Vec::new())};
+        assert!(decoder.push_packet(code_warning(&all_clear).unwrap()[0], all_clear.start) == Some(all_clear));
+    }
+    #[test]
+    fn test_minutes() {
+        let warning = test_warning(vec![42, 43, 44, 45, 46]);
+        let minutes = warning_minutes(&warning).unwrap();
+        assert!(minutes.len() == 4 * CHUNKS_PER_PACKET);
+        let mut aggregator = ThirdPartyAggregator::new();
+        let mut decoder = WarningDecoder::new();
+        let mut warnings = Vec::new();
+        for minute in minutes {
+            if let Some(packet) = aggregator.push(from_dcf77(minute.frame).unwrap()) {
+                warnings.extend(decoder.push(&packet));
+            }
+        }
+        assert!(warnings == [warning.clone()]);
+        let late = Warning {start: warning.start + chrono::Duration::minutes(1), ..warning};
+        assert!(warning_minutes(&late).is_err());
+    }
+}
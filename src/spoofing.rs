@@ -0,0 +1,267 @@
+//! Detection of spoofed or locally generated DCF77 signals
+//!
+//! DCF77 emulators (phone apps, GPS to DCF77 converters) send a time code
+//! that decodes as well as the real one. The `SpoofingDetector` looks for what
+//! sets them apart and scores every decoded minute:
+//! - the time must agree with the holdover clock within its error estimate
+//! - the pulses of a received signal jitter by a few milliseconds, those of a
+//!   generated signal either sit exactly on the second grid or wander far
+//!   away from it
+//! - the real transmitter adds a pseudo-random phase modulation that
+//!   emulators leave out; its presence is given by the receiver when it can
+//!   tell
+//!
+//! The checks that cannot be done, for instance without a synchronized clock,
+//! do not count. The minute should only synchronize the holdover clock after
+//! it has been assessed and found plausible.
+
+use std::time::{Duration, Instant};
+use chrono::prelude::*;
+use crate::{DCF77, to_utc};
+use crate::holdover::HoldoverClock;
+use crate::receiver::Edge;
+
+/// Largest difference with the holdover clock besides its error estimate
+pub const TIME_TOLERANCE: Duration = Duration::from_millis(50);
+
+/// Pulses needed in a minute to judge their timing
+pub const MIN_PULSES: usize = 20;
+
+/// Jitter of the pulses below which the signal looks generated
+pub const MIN_NATURAL_JITTER: Duration = Duration::from_micros(200);
+
+/// Jitter of the pulses above which the signal looks generated
+pub const MAX_NATURAL_JITTER: Duration = Duration::from_millis(20);
+
+/// Score from which a minute is taken as spoofed
+pub const SUSPICION_THRESHOLD: f64 = 0.5;
+
+/// Score of a time that contradicts the holdover clock
+const TIME_MISMATCH_SCORE: f64 = 0.6;
+
+/// Score of pulses that sit exactly on the second grid or wander far from it
+const PULSE_TIMING_SCORE: f64 = 0.3;
+
+/// Score of a missing phase modulation
+const NO_PHASE_MODULATION_SCORE: f64 = 0.3;
+
+/// Reason to suspect a minute
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Alert {
+    /// The decoded time contradicts the holdover clock
+    TimeMismatch {
+        /// Decoded UTC time of the minute marker
+        decoded: DateTime<Utc>,
+        /// UTC time of the minute marker given by the holdover clock
+        predicted: DateTime<Utc>,
+        /// Error estimate of the holdover clock
+        error_estimate: Duration
+    },
+    /// The pulses are more regular than a received signal
+    SyntheticPulses {
+        /// Jitter of the pulses (RMS distance to the second grid)
+        jitter: Duration
+    },
+    /// The pulses wander more than a received signal
+    IrregularPulses {
+        /// Jitter of the pulses (RMS distance to the second grid)
+        jitter: Duration
+    },
+    /// The receiver found no phase modulation
+    NoPhaseModulation
+}
+
+/// Assessment of a decoded minute
+#[derive(Clone, Debug, PartialEq)]
+pub struct Assessment {
+    /// Score between 0 (plausible) and 1 (spoofed)
+    pub score: f64,
+    /// Reasons of the score
+    pub alerts: Vec<Alert>
+}
+
+impl Assessment {
+    /// Whether the score reaches `SUSPICION_THRESHOLD`
+    pub fn is_suspicious(&self) -> bool {
+        self.score >= SUSPICION_THRESHOLD
+    }
+}
+
+/// Jitter of pulses, the RMS distance of their intervals to whole seconds
+///
+/// `None` with less than two pulses.
+pub fn pulse_jitter(starts: &[Duration]) -> Option<Duration> {
+    if starts.len() < 2 {
+        return None;
+    }
+    let squares: f64 = starts.windows(2).map(|pair| {
+        let interval = pair[1].saturating_sub(pair[0]).as_secs_f64();
+        (interval - interval.round()).powi(2)
+    }).sum();
+    Some(Duration::from_secs_f64((squares / (starts.len() - 1) as f64).sqrt()))
+}
+
+/// Detector of spoofed signals, fed with the edges of a minute and assessing its decoded time
+///
+/// # Examples
+/// ```
+///use std::time::{Duration, Instant};
+///use chrono::prelude::*;
+///use dcf77_chrono::holdover::HoldoverClock;
+///use dcf77_chrono::receiver::*;
+///use dcf77_chrono::schedule::*;
+///use dcf77_chrono::spoofing::*;
+///let from = Utc.with_ymd_and_hms(2023, 6, 14, 8, 0, 0).unwrap();
+///let minutes: Vec<ScheduledMinute> = Schedule::new(from, from + chrono::Duration::minutes(2)).collect();
+///let marker = Instant::now();
+///let mut clock = HoldoverClock::default();
+///clock.synchronize(minutes[0].dcf, marker);
+///// An emulator sends the right time with perfect pulses and no phase modulation
+///let mut detector = SpoofingDetector::new();
+///for edge in edges_from_frame(minutes[1].frame, Duration::ZERO, false) {
+///    detector.push_edge(edge);
+///}
+///detector.set_phase_modulation(false);
+///let assessment = detector.assess(minutes[1].dcf, &clock, marker + Duration::from_secs(60));
+///assert!(assessment.is_suspicious());
+///assert!(assessment.alerts.len() == 2);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SpoofingDetector {
+    pulse_start: Option<Duration>,
+    pulses: Vec<Duration>,
+    phase_modulation: Option<bool>
+}
+
+impl SpoofingDetector {
+    /// Creates a detector without pulses
+    pub fn new() -> SpoofingDetector {
+        SpoofingDetector::default()
+    }
+
+    /// Pushes an edge of the demodulated signal of the current minute
+    pub fn push_edge(&mut self, edge: Edge) {
+        if edge.level {
+            self.pulse_start = Some(edge.time);
+        } else if let Some(start) = self.pulse_start.take() {
+            self.push_pulse(start);
+        }
+    }
+
+    /// Pushes the start of a pulse of the current minute
+    pub fn push_pulse(&mut self, start: Duration) {
+        self.pulses.push(start);
+    }
+
+    /// Gives whether the receiver found the phase modulation during the current minute
+    pub fn set_phase_modulation(&mut self, present: bool) {
+        self.phase_modulation = Some(present);
+    }
+
+    /// Assesses a decoded minute, `marker` being the local instant of its minute marker
+    ///
+    /// The pulses and the phase modulation of the minute are dropped afterwards.
+    pub fn assess(&mut self, dcf_data: DCF77, clock: &HoldoverClock, marker: Instant) -> Assessment {
+        let mut alerts = Vec::new();
+        let mut score = 0.0;
+        if let (Some(predicted), Some(error_estimate)) = (clock.time_at(marker), clock.error_estimate_at(marker)) {
+            let decoded = to_utc(dcf_data);
+            let difference = (decoded - predicted).abs().to_std().unwrap_or(Duration::MAX);
+            if difference > error_estimate + TIME_TOLERANCE {
+                alerts.push(Alert::TimeMismatch {decoded, predicted, error_estimate});
+                score += TIME_MISMATCH_SCORE;
+            }
+        }
+        if self.pulses.len() >= MIN_PULSES {
+            let jitter = pulse_jitter(&self.pulses).unwrap();
+            if jitter < MIN_NATURAL_JITTER {
+                alerts.push(Alert::SyntheticPulses {jitter});
+                score += PULSE_TIMING_SCORE;
+            } else if jitter > MAX_NATURAL_JITTER {
+                alerts.push(Alert::IrregularPulses {jitter});
+                score += PULSE_TIMING_SCORE;
+            }
+        }
+        if self.phase_modulation == Some(false) {
+            alerts.push(Alert::NoPhaseModulation);
+            score += NO_PHASE_MODULATION_SCORE;
+        }
+        self.pulses.clear();
+        self.phase_modulation = None;
+        Assessment {score: f64::min(score, 1.0), alerts}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::receiver::edges_from_frame;
+    use crate::schedule::{Schedule, ScheduledMinute};
+    /// Edges of a minute with the start of every pulse moved by a few milliseconds
+    fn jittered_edges(minute: &ScheduledMinute, amplitude: u64) -> Vec<Edge> {
+        edges_from_frame(minute.frame, Duration::from_secs(1), false).into_iter().enumerate().map(|(index, edge)| {
+            let offset = Duration::from_millis((index as u64 / 2 * 7919) % (2 * amplitude + 1));
+            Edge {time: edge.time + offset, ..edge}
+        }).collect()
+    }
+    #[test]
+    fn test_plausible() {
+        let from = Utc.with_ymd_and_hms(2023, 6, 14, 8, 0, 0).unwrap();
+        let minutes: Vec<ScheduledMinute> = Schedule::new(from, from + chrono::Duration::minutes(11)).collect();
+        let marker = Instant::now();
+        let mut clock = HoldoverClock::default();
+        let mut detector = SpoofingDetector::new();
+        // Nothing to compare the first minute with
+        let assessment = detector.assess(minutes[0].dcf, &clock, marker);
+        assert!(assessment.score == 0.0 && assessment.alerts.is_empty());
+        clock.synchronize(minutes[0].dcf, marker);
+        for edge in jittered_edges(&minutes[10], 3) {
+            detector.push_edge(edge);
+        }
+        detector.set_phase_modulation(true);
+        let assessment = detector.assess(minutes[10].dcf, &clock, marker + Duration::from_secs(600));
+        assert!(assessment.alerts.is_empty());
+        assert!(!assessment.is_suspicious());
+    }
+    #[test]
+    fn test_spoofed() {
+        let from = Utc.with_ymd_and_hms(2023, 6, 14, 8, 0, 0).unwrap();
+        let minutes: Vec<ScheduledMinute> = Schedule::new(from, from + chrono::Duration::minutes(11)).collect();
+        let marker = Instant::now();
+        let mut clock = HoldoverClock::default();
+        clock.synchronize(minutes[0].dcf, marker);
+        let mut detector = SpoofingDetector::new();
+        // Ten minutes later an emulator sends a time five minutes late
+        for edge in jittered_edges(&minutes[5], 3) {
+            detector.push_edge(edge);
+        }
+        let assessment = detector.assess(minutes[5].dcf, &clock, marker + Duration::from_secs(600));
+        assert!(assessment.is_suspicious());
+        assert!(assessment.alerts == [Alert::TimeMismatch {
+            decoded: minutes[5].utc,
+            predicted: minutes[10].utc,
+            error_estimate: clock.error_estimate_at(marker + Duration::from_secs(600)).unwrap()
+        }]);
+        // The pulses of a phone app wander by tens of milliseconds
+        for edge in jittered_edges(&minutes[10], 60) {
+            detector.push_edge(edge);
+        }
+        let assessment = detector.assess(minutes[10].dcf, &clock, marker + Duration::from_secs(600));
+        assert!(matches!(assessment.alerts[..], [Alert::IrregularPulses {..}]));
+        assert!(!assessment.is_suspicious());
+        detector.set_phase_modulation(false);
+        for edge in edges_from_frame(minutes[10].frame, Duration::ZERO, false) {
+            detector.push_edge(edge);
+        }
+        let assessment = detector.assess(minutes[10].dcf, &clock, marker + Duration::from_secs(600));
+        assert!(assessment.alerts == [Alert::SyntheticPulses {jitter: Duration::ZERO}, Alert::NoPhaseModulation]);
+        assert!(assessment.is_suspicious());
+    }
+    #[test]
+    fn test_pulse_jitter() {
+        assert!(pulse_jitter(&[Duration::ZERO]).is_none());
+        let starts = [Duration::ZERO, Duration::from_millis(1_010), Duration::from_millis(2_000), Duration::from_millis(4_010)];
+        let jitter = pulse_jitter(&starts).unwrap();
+        assert!((jitter.as_secs_f64() - 0.01).abs() < 1e-6);
+    }
+}
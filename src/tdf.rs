@@ -0,0 +1,223 @@
+//! Coding and decoding of the TDF / ALS162 (France, 162 kHz) time code
+//!
+//! ALS162 sends a minute telegram with the same layout as DCF77 for the time
+//! and date (BCD minutes, hour, day, day of the week, month and year with their
+//! parities), but phase modulated instead of amplitude modulated. This module
+//! works on the demodulated bits and stores them in the same `u64` bit field
+//! used by `to_dcf77` and `from_dcf77`, reusing the DCF77 sections.
+//!
+//! The differences with DCF77 are:
+//! - seconds 1 to 14 carry the ALS162 warning bits, where DCF77 has its third
+//!   party bits
+//! - second 15 is not used, there is no call bit
+
+use std::io::Error;
+use std::io::ErrorKind;
+use chrono::prelude::*;
+use crate::dcf77;
+
+/// Struct that represents the TDF information
+///
+/// This struct contains a chrono `DateTime<Utc>` field and the metadata
+/// that the TDF offers.
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::tdf::*;
+///let test_time = TDF {
+///    date: Utc.with_ymd_and_hms(23, 6, 14, 8, 30, 0).unwrap(),
+///    day_of_week: 3,
+///    warning_bits: 0x2001,
+///    announce_daily_saving_time: false,
+///    daily_saving_time: true,
+///    standard_time: false,
+///    bit_leap_second: false
+///};
+///let coded_minutes = to_tdf(test_time).unwrap();
+///let decoded_minutes = from_tdf(coded_minutes).unwrap();
+///assert!(decoded_minutes.date == test_time.date);
+///assert!(decoded_minutes.warning_bits == 0x2001);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TDF {
+    /// Date in UTC timezone
+    pub date: DateTime<Utc>,
+    /// Day of the week as broadcast [1..7], Monday being 1, the one of the
+    /// date with the years below 100 taken as 20xx
+    pub day_of_week: u32,
+    /// Warning bits of the seconds 1 to 14, the second 1 being the most significant bit
+    pub warning_bits: u16,
+    /// Announce Bit for Daylight Saving Time (DST) Switching
+    pub announce_daily_saving_time: bool,
+    /// If this bit is set, Daylight Saving Time (DST) is Active
+    pub daily_saving_time: bool,
+    /// If this bit is set, Standard Time is Active
+    pub standard_time: bool,
+    /// Announce Bit for Leap Second
+    pub bit_leap_second: bool
+}
+
+/// Day of the week of a date [1..7], the years below 100 being taken as 20xx
+fn day_of_week(date: NaiveDate) -> Option<u32> {
+    let full_date = if date.year() < 100 { date.with_year(date.year() + 2000) } else { Some(date) };
+    full_date.map(|date| date.weekday().number_from_monday())
+}
+
+/// Decodes the date and metadata out of a TDF bit field
+///
+/// A TDF bitfield is given as input and a TDF struct is returned when successful.
+/// The day of the week must be the one of the date, the years below 100
+/// being taken as 20xx.
+pub fn from_tdf(input: u64) -> Result<TDF, Error> {
+    let processed_hour = dcf77::hour::process_hour(input)?;
+    let processed_minutes = dcf77::hour::process_minutes(input)?;
+    let processed_day = dcf77::date::process_day(input)?;
+    let processed_day_of_week = dcf77::date::process_day_of_week(input)?;
+    let processed_month = dcf77::date::process_month(input)?;
+    let processed_year = dcf77::date::process_year(input)? as i32;
+    dcf77::date::check_date_parity(input)?;
+    let date = match Utc.with_ymd_and_hms(processed_year,
+                                          processed_month,
+                                          processed_day,
+                                          processed_hour,
+                                          processed_minutes,
+                                          0).single() {
+        Some(date) => date,
+        None => {
+            let error_payload = format!("Invalid date - Input: 0x{:X}", input);
+            return Err(Error::new(ErrorKind::InvalidData, error_payload));
+        }
+    };
+    if day_of_week(date.date_naive()) != Some(processed_day_of_week) {
+        let error_payload = format!("Day of the week {} does not match the date {}", processed_day_of_week, date.date_naive());
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    Ok(TDF {
+        date,
+        day_of_week: processed_day_of_week,
+        warning_bits: dcf77::metadata::process_third_party(input),
+        announce_daily_saving_time: dcf77::metadata::process_announce_daylight_saving_switch(input),
+        daily_saving_time: dcf77::metadata::process_daylight_saving(input),
+        standard_time: dcf77::metadata::process_standard_time(input),
+        bit_leap_second: dcf77::metadata::process_bit_leap_second(input)})
+}
+
+/// Encodes a TDF bit field containing the information of a TDF struct
+///
+/// A TDF struct is given as input and a TDF bitfield is returned when successful.
+/// The day of the week must be the one of the date, the years below 100
+/// being taken as 20xx.
+pub fn to_tdf(tdf_data: TDF) -> Result<u64, Error> {
+    let given_date = tdf_data.date.date_naive();
+    let given_time = tdf_data.date.time();
+    if day_of_week(given_date) != Some(tdf_data.day_of_week) {
+        let error_payload = format!("Day of the week {} does not match the date {}", tdf_data.day_of_week, given_date);
+        return Err(Error::new(ErrorKind::InvalidData, error_payload));
+    }
+    let coded_hour = dcf77::hour::code_hour(given_time.hour())?;
+    let coded_minutes = dcf77::hour::code_minutes(given_time.minute())?;
+    let coded_day = dcf77::date::code_day(given_date.day())?;
+    let coded_day_of_week = dcf77::date::code_day_of_the_week(tdf_data.day_of_week.try_into().unwrap())?;
+    let coded_month = dcf77::date::code_month(given_date.month())?;
+    let coded_year = dcf77::date::code_year(given_date.year())?;
+    let coded_warning_bits = dcf77::metadata::code_third_party(tdf_data.warning_bits)?;
+    let coded_announce_daily_saving_time = dcf77::metadata::code_announce_daylight_saving_switching(tdf_data.announce_daily_saving_time);
+    let coded_daily_saving_time = dcf77::metadata::code_daylight_saving(tdf_data.daily_saving_time);
+    let coded_standard_time = dcf77::metadata::code_standard_time(tdf_data.standard_time);
+    let coded_bit_leap_second = dcf77::metadata::code_bit_leap_second(tdf_data.bit_leap_second);
+    let coded_date_parity = dcf77::date::code_date_parity(coded_day | coded_day_of_week | coded_month | coded_year);
+    let coded_start_of_time = dcf77::metadata::code_start_of_time();
+    Ok(coded_hour |
+        coded_minutes |
+        coded_day |
+        coded_day_of_week |
+        coded_month |
+        coded_year |
+        coded_date_parity |
+        coded_start_of_time |
+        coded_warning_bits |
+        coded_announce_daily_saving_time |
+        coded_daily_saving_time |
+        coded_standard_time |
+        coded_bit_leap_second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DCF77, from_dcf77, to_dcf77};
+    #[test]
+    fn test_coder_decoder() {
+        let original_test_time = Utc.with_ymd_and_hms(23, 1, 1, 0, 0, 0).unwrap();
+        for fake_input in 0..=0xfff {
+            let date = original_test_time + chrono::Duration::minutes(fake_input * 127);
+            let test_time = TDF {
+                date,
+                day_of_week: date.with_year(date.year() + 2000).unwrap().weekday().number_from_monday(),
+                warning_bits: (fake_input as u16) & 0x3FFF,
+                announce_daily_saving_time: fake_input & 1 > 0,
+                daily_saving_time: fake_input & 2 > 0,
+                standard_time: fake_input & 2 == 0,
+                bit_leap_second: fake_input & 4 > 0
+            };
+            match to_tdf(test_time) {
+                Ok(coded_minutes) => {
+                    match from_tdf(coded_minutes) {
+                        Ok(decoded_minutes) => {
+                            assert!(test_time.date == decoded_minutes.date);
+                            assert!(test_time.day_of_week == decoded_minutes.day_of_week);
+                            assert!(test_time.warning_bits == decoded_minutes.warning_bits);
+                            assert!(test_time.announce_daily_saving_time == decoded_minutes.announce_daily_saving_time);
+                            assert!(test_time.daily_saving_time == decoded_minutes.daily_saving_time);
+                            assert!(test_time.standard_time == decoded_minutes.standard_time);
+                            assert!(test_time.bit_leap_second == decoded_minutes.bit_leap_second);
+                        }
+                        Err(error) => {
+                            panic!("Error on decoding 0x{:X} {:?}", coded_minutes, error)
+                        }
+                    }
+                }
+                Err(error) => {
+                    panic!("Error on coding the tdf {:?}", error)
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_shared_layout() {
+        let test_time = TDF {
+            date: Utc.with_ymd_and_hms(23, 10, 29, 2, 59, 0).unwrap(),
+            day_of_week: 7,
+            warning_bits: 0,
+            announce_daily_saving_time: true,
+            daily_saving_time: true,
+            standard_time: false,
+            bit_leap_second: false
+        };
+        let decoded_minutes = from_dcf77(to_tdf(test_time).unwrap()).unwrap();
+        assert!(decoded_minutes.date == test_time.date);
+        assert!(decoded_minutes.announce_daily_saving_time);
+        assert!(decoded_minutes.daily_saving_time);
+        assert!(!decoded_minutes.antenna);
+        assert!(to_tdf(TDF {warning_bits: 0x2001, ..test_time}).unwrap() == to_dcf77(DCF77 {third_party: 0x2001, ..decoded_minutes}).unwrap());
+        assert!(to_tdf(TDF {warning_bits: 0x4000, ..test_time}).is_err());
+        // The 29th of October 2023 is a Sunday
+        assert!(to_tdf(TDF {day_of_week: 1, ..test_time}).is_err());
+        assert!(to_tdf(TDF {day_of_week: 0, ..test_time}).is_err());
+        // A Monday with a consistent date parity
+        let coded_minutes = dcf77::date::replace_day_of_week(to_tdf(test_time).unwrap(), 1).unwrap();
+        assert!(dcf77::date::check_date_parity(coded_minutes).is_ok());
+        assert!(from_tdf(coded_minutes).is_err());
+    }
+    #[test]
+    fn test_invalid_date() {
+        let coded_minutes = dcf77::hour::code_hour(10).unwrap() |
+            dcf77::hour::code_minutes(10).unwrap() |
+            dcf77::date::code_day(31).unwrap() |
+            dcf77::date::code_month(2).unwrap() |
+            dcf77::date::code_year(23).unwrap();
+        let coded_minutes = coded_minutes | dcf77::date::code_date_parity(coded_minutes);
+        assert!(from_tdf(coded_minutes).is_err());
+    }
+}
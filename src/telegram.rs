@@ -0,0 +1,294 @@
+//! Second by second description of a DCF77 telegram
+//!
+//! The bit of the second `n` of the minute is stored at the position `59 - n`
+//! of the bit field used by `to_dcf77` and `from_dcf77`. This module tells
+//! which field every second belongs to and checks the fields one by one, so
+//! that a telegram can be explained even when it does not decode.
+
+use std::ops::RangeInclusive;
+use crate::dcf77;
+
+/// Number of seconds in a DCF77 minute, the last one having no pulse
+pub const SECONDS_PER_MINUTE: u8 = 60;
+
+/// Field of a DCF77 telegram
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Field {
+    /// Start of minute (M), always 0
+    MinuteStart,
+    /// Third party bits: weather data and civil protection warnings
+    ThirdParty,
+    /// Call bit (R), signals irregularities of the transmitter
+    CallBit,
+    /// Announcement of a Daylight Saving Time switch (A1)
+    AnnounceDaylightSaving,
+    /// Daylight Saving Time, CEST (Z1)
+    DaylightSaving,
+    /// Standard time, CET (Z2)
+    StandardTime,
+    /// Announcement of a leap second (A2)
+    AnnounceLeapSecond,
+    /// Start of encoded time (S), always 1
+    TimeStart,
+    /// Minutes BCD
+    Minutes,
+    /// Even parity of the minutes (P1)
+    MinutesParity,
+    /// Hour BCD
+    Hour,
+    /// Even parity of the hour (P2)
+    HourParity,
+    /// Day of the month BCD
+    Day,
+    /// Day of the week BCD, Monday being 1
+    DayOfWeek,
+    /// Month BCD
+    Month,
+    /// Year within the century BCD
+    Year,
+    /// Even parity of the date (P3)
+    DateParity,
+    /// Minute marker, no pulse
+    MinuteMarker
+}
+
+/// Every field of a telegram in the order they are sent
+pub const FIELDS: [Field; 18] = [Field::MinuteStart, Field::ThirdParty, Field::CallBit,
+                                 Field::AnnounceDaylightSaving, Field::DaylightSaving,
+                                 Field::StandardTime, Field::AnnounceLeapSecond,
+                                 Field::TimeStart, Field::Minutes, Field::MinutesParity,
+                                 Field::Hour, Field::HourParity, Field::Day, Field::DayOfWeek,
+                                 Field::Month, Field::Year, Field::DateParity,
+                                 Field::MinuteMarker];
+
+/// Weights of the bits of the BCD fields, least significant second first
+const BCD_WEIGHTS: [u32; 8] = [1, 2, 4, 8, 10, 20, 40, 80];
+
+impl Field {
+    /// Seconds of the minute that carry the field
+    pub fn seconds(self) -> RangeInclusive<u8> {
+        match self {
+            Field::MinuteStart => 0..=0,
+            Field::ThirdParty => 1..=14,
+            Field::CallBit => 15..=15,
+            Field::AnnounceDaylightSaving => 16..=16,
+            Field::DaylightSaving => 17..=17,
+            Field::StandardTime => 18..=18,
+            Field::AnnounceLeapSecond => 19..=19,
+            Field::TimeStart => 20..=20,
+            Field::Minutes => 21..=27,
+            Field::MinutesParity => 28..=28,
+            Field::Hour => 29..=34,
+            Field::HourParity => 35..=35,
+            Field::Day => 36..=41,
+            Field::DayOfWeek => 42..=44,
+            Field::Month => 45..=49,
+            Field::Year => 50..=57,
+            Field::DateParity => 58..=58,
+            Field::MinuteMarker => 59..=59
+        }
+    }
+
+    /// Short name of the field as used in the DCF77 documentation
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::MinuteStart => "M",
+            Field::ThirdParty => "Third party",
+            Field::CallBit => "R",
+            Field::AnnounceDaylightSaving => "A1",
+            Field::DaylightSaving => "Z1",
+            Field::StandardTime => "Z2",
+            Field::AnnounceLeapSecond => "A2",
+            Field::TimeStart => "S",
+            Field::Minutes => "Minutes",
+            Field::MinutesParity => "P1",
+            Field::Hour => "Hour",
+            Field::HourParity => "P2",
+            Field::Day => "Day",
+            Field::DayOfWeek => "Day of week",
+            Field::Month => "Month",
+            Field::Year => "Year",
+            Field::DateParity => "P3",
+            Field::MinuteMarker => "Marker"
+        }
+    }
+
+    /// Description of the field
+    pub fn description(self) -> &'static str {
+        match self {
+            Field::MinuteStart => "Start of minute, always 0",
+            Field::ThirdParty => "Weather data and civil protection warnings",
+            Field::CallBit => "Call bit, irregularities of the transmitter",
+            Field::AnnounceDaylightSaving => "Announcement of a Daylight Saving Time switch",
+            Field::DaylightSaving => "Daylight Saving Time (CEST) active",
+            Field::StandardTime => "Standard time (CET) active",
+            Field::AnnounceLeapSecond => "Announcement of a leap second",
+            Field::TimeStart => "Start of encoded time, always 1",
+            Field::Minutes => "Minutes",
+            Field::MinutesParity => "Even parity of the minutes",
+            Field::Hour => "Hour",
+            Field::HourParity => "Even parity of the hour",
+            Field::Day => "Day of the month",
+            Field::DayOfWeek => "Day of the week, Monday being 1",
+            Field::Month => "Month",
+            Field::Year => "Year within the century",
+            Field::DateParity => "Even parity of the date",
+            Field::MinuteMarker => "Minute marker, no pulse"
+        }
+    }
+
+    /// Seconds covered by the field when it is a parity bit
+    pub fn parity_coverage(self) -> Option<RangeInclusive<u8>> {
+        match self {
+            Field::MinutesParity => Some(21..=27),
+            Field::HourParity => Some(29..=34),
+            Field::DateParity => Some(36..=57),
+            _ => None
+        }
+    }
+
+    /// Whether the field is a BCD number
+    pub fn is_bcd(self) -> bool {
+        matches!(self, Field::Minutes | Field::Hour | Field::Day | Field::DayOfWeek | Field::Month | Field::Year)
+    }
+}
+
+/// Field carried by a second of the minute
+pub fn field_of_second(second: u8) -> Option<Field> {
+    FIELDS.iter().copied().find(|field| field.seconds().contains(&second))
+}
+
+/// Weight of a second that belongs to a BCD field
+pub fn weight_of_second(second: u8) -> Option<u32> {
+    let field = field_of_second(second)?;
+    if field.is_bcd() {
+        Some(BCD_WEIGHTS[usize::from(second - field.seconds().start())])
+    } else {
+        None
+    }
+}
+
+/// Bit of a second of the minute in a DCF77 bit field
+pub fn bit_of_second(input: u64, second: u8) -> bool {
+    second < SECONDS_PER_MINUTE && 0 < input & (1 << (59 - second))
+}
+
+/// Check of one field of a telegram
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldReport {
+    /// Field checked
+    pub field: Field,
+    /// Value of the field: BCD value, bits of the third party field or the flag
+    pub value: u32,
+    /// Problem found in the field, if any
+    pub error: Option<String>
+}
+
+/// Checks every field of a telegram on its own
+///
+/// # Examples
+/// ```
+///use dcf77_chrono::telegram::*;
+///let reports = field_reports(0);
+///let start_of_time = reports.iter().find(|report| report.field == Field::TimeStart).unwrap();
+///assert!(start_of_time.error.is_some());
+/// ```
+pub fn field_reports(input: u64) -> Vec<FieldReport> {
+    FIELDS.iter().map(|field| {
+        let seconds = field.seconds();
+        let value = if field.is_bcd() {
+            seconds.clone()
+                .filter(|second| bit_of_second(input, *second))
+                .map(|second| weight_of_second(second).unwrap())
+                .sum()
+        } else {
+            seconds.clone().fold(0, |value, second| (value << 1) | u32::from(bit_of_second(input, second)))
+        };
+        let error = match field {
+            Field::MinuteStart if value != 0 => Some("Start of minute must be 0".to_string()),
+            Field::TimeStart if !dcf77::metadata::process_start_of_time(input) => Some("Start of encoded time must be 1".to_string()),
+            Field::MinuteMarker if value != 0 => Some("Minute marker must have no pulse".to_string()),
+            Field::DaylightSaving | Field::StandardTime
+                if bit_of_second(input, 17) == bit_of_second(input, 18) => Some("Exactly one of Z1 and Z2 must be set".to_string()),
+            Field::MinutesParity | Field::HourParity | Field::DateParity => {
+                let ones = field.parity_coverage().unwrap()
+                    .chain(seconds.clone())
+                    .filter(|second| bit_of_second(input, *second))
+                    .count();
+                if ones % 2 == 0 { None } else { Some("Parity error".to_string()) }
+            }
+            Field::Minutes if value > 59 => Some(format!("Minutes out of range: {}", value)),
+            Field::Hour if value > 23 => Some(format!("Hour out of range: {}", value)),
+            Field::Day if !(1..=31).contains(&value) => Some(format!("Day out of range: {}", value)),
+            Field::DayOfWeek if !(1..=7).contains(&value) => Some(format!("Day of week out of range: {}", value)),
+            Field::Month if !(1..=12).contains(&value) => Some(format!("Month out of range: {}", value)),
+            Field::Year if value > 99 => Some(format!("Year out of range: {}", value)),
+            _ => None
+        };
+        let error = match error {
+            None if field.is_bcd() && !is_valid_bcd(input, seconds) => Some("Invalid BCD digit".to_string()),
+            error => error
+        };
+        FieldReport {field: *field, value, error}
+    }).collect()
+}
+
+/// Checks that both the units and the tens of a BCD field are below 10
+fn is_valid_bcd(input: u64, seconds: RangeInclusive<u8>) -> bool {
+    let start = *seconds.start();
+    let mut units = 0;
+    for second in seconds {
+        if bit_of_second(input, second) && second - start < 4 {
+            units += BCD_WEIGHTS[usize::from(second - start)];
+        }
+    }
+    units < 10
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DCF77, to_dcf77};
+    use chrono::prelude::*;
+    #[test]
+    fn test_fields() {
+        for second in 0..SECONDS_PER_MINUTE {
+            let field = field_of_second(second).unwrap();
+            assert!(field.seconds().contains(&second));
+            assert!(field.is_bcd() == weight_of_second(second).is_some());
+        }
+        assert!(field_of_second(SECONDS_PER_MINUTE).is_none());
+        assert!(weight_of_second(57) == Some(80));
+        assert!(weight_of_second(44) == Some(4));
+    }
+    #[test]
+    fn test_reports() {
+        let test_time = DCF77 {
+            date: Utc.with_ymd_and_hms(23, 10, 29, 2, 59, 0).unwrap(),
+            antenna: true,
+            announce_daily_saving_time: true,
+            daily_saving_time: true,
+            standard_time: false,
+            bit_leap_second: false,
+            third_party: 0
+        };
+        let coded_minutes = to_dcf77(test_time).unwrap();
+        let reports = field_reports(coded_minutes);
+        assert!(reports.len() == FIELDS.len());
+        assert!(reports.iter().all(|report| report.error.is_none()));
+        let values: Vec<u32> = reports.iter().map(|report| report.value).collect();
+        assert!(values[2..=9] == [1, 1, 1, 0, 0, 1, 59, 0]);
+        assert!(values[10..=15] == [2, 1, 29, 7, 10, 23]);
+        let reports = field_reports(coded_minutes ^ (1 << (59 - 36)));
+        assert!(reports.iter().filter(|report| report.error.is_some()).count() == 1);
+        assert!(reports[16].error.is_some());
+        let reports = field_reports(coded_minutes ^ (1 << (59 - 18)));
+        assert!(reports[4].error.is_some() && reports[5].error.is_some());
+    }
+    #[test]
+    fn test_invalid_bcd() {
+        let reports = field_reports((1 << (59 - 21)) | (1 << (59 - 22)) | (1 << (59 - 24)));
+        assert!(reports[8].value == 11);
+        assert!(reports[8].error.is_some());
+    }
+}
@@ -0,0 +1,176 @@
+//! Third party bits of the seconds 1 to 14
+//!
+//! DCF77 lends the seconds 1 to 14 of every minute to third parties: the
+//! Meteotime weather data and, since 2019, civil protection warnings. A
+//! message is a 42 bit packet sent over three minutes as 14 bit chunks, the
+//! first chunk being sent in a minute that starts at a multiple of three
+//! minutes. Since the telegram sent during a minute describes the next one,
+//! the first chunk of a packet is found in a telegram whose minute is 1
+//! modulo 3.
+
+use chrono::prelude::*;
+use crate::{DCF77, to_utc};
+
+/// Number of third party bits in a minute
+pub const CHUNK_BITS: u32 = 14;
+
+/// Number of minutes of a third party packet
+pub const CHUNKS_PER_PACKET: usize = 3;
+
+/// Mask of the bits of a third party packet
+pub const PACKET_MASK: u64 = (1 << (CHUNK_BITS * CHUNKS_PER_PACKET as u32)) - 1;
+
+/// Mask of the bits of a chunk
+const CHUNK_MASK: u64 = (1 << CHUNK_BITS) - 1;
+
+/// Joins three chunks into a 42 bit packet, the first chunk being the most significant
+pub fn join_chunks(chunks: [u16; CHUNKS_PER_PACKET]) -> u64 {
+    chunks.iter().fold(0, |packet, chunk| (packet << CHUNK_BITS) | (u64::from(*chunk) & CHUNK_MASK))
+}
+
+/// Splits a 42 bit packet into its three chunks
+pub fn split_packet(packet: u64) -> [u16; CHUNKS_PER_PACKET] {
+    let mut chunks = [0; CHUNKS_PER_PACKET];
+    for (index, chunk) in chunks.iter_mut().enumerate() {
+        let shift = CHUNK_BITS * (CHUNKS_PER_PACKET - 1 - index) as u32;
+        *chunk = ((packet >> shift) & CHUNK_MASK) as u16;
+    }
+    chunks
+}
+
+/// Position in its packet of the chunk carried by the telegram of a minute
+pub fn chunk_index(dcf_data: DCF77) -> usize {
+    (dcf_data.date.minute() as usize + CHUNKS_PER_PACKET - 1) % CHUNKS_PER_PACKET
+}
+
+/// Third party packet with the minutes that carried it
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ThirdPartyPacket {
+    /// Chunks in the order they were sent
+    pub chunks: [u16; CHUNKS_PER_PACKET],
+    /// Decoded minutes whose telegrams carried the chunks
+    pub minutes: [DCF77; CHUNKS_PER_PACKET]
+}
+
+impl ThirdPartyPacket {
+    /// Bits of the packet, the first chunk being the most significant
+    pub fn bits(&self) -> u64 {
+        join_chunks(self.chunks)
+    }
+
+    /// UTC instant at which the transmission of the packet started
+    pub fn start(&self) -> DateTime<Utc> {
+        to_utc(self.minutes[0]) - chrono::Duration::minutes(1)
+    }
+}
+
+/// Collector of the chunks of consecutive minutes into packets
+///
+/// # Examples
+/// ```
+///use chrono::prelude::*;
+///use dcf77_chrono::*;
+///use dcf77_chrono::third_party::*;
+///let mut aggregator = ThirdPartyAggregator::new();
+///let mut packets = Vec::new();
+///for minute in 0..6 {
+///    let dcf_data = DCF77 {
+///        date: Utc.with_ymd_and_hms(23, 6, 14, 8, minute, 0).unwrap(),
+///        antenna: false,
+///        announce_daily_saving_time: false,
+///        daily_saving_time: true,
+///        standard_time: false,
+///        bit_leap_second: false,
+///        third_party: minute as u16
+///    };
+///    packets.extend(aggregator.push(dcf_data));
+///}
+///assert!(packets.len() == 1);
+///assert!(packets[0].chunks == [1, 2, 3]);
+///assert!(packets[0].start() == Utc.with_ymd_and_hms(2023, 6, 14, 6, 0, 0).unwrap());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ThirdPartyAggregator {
+    minutes: Vec<DCF77>
+}
+
+impl ThirdPartyAggregator {
+    /// Creates an aggregator waiting for the first chunk of a packet
+    pub fn new() -> ThirdPartyAggregator {
+        ThirdPartyAggregator::default()
+    }
+
+    /// Drops the chunks collected so far
+    pub fn reset(&mut self) {
+        self.minutes.clear();
+    }
+
+    /// Pushes a decoded minute, giving the packet that it completes
+    ///
+    /// The chunks of a packet must come from consecutive minutes; a missing
+    /// minute drops the packet being collected.
+    pub fn push(&mut self, dcf_data: DCF77) -> Option<ThirdPartyPacket> {
+        let index = chunk_index(dcf_data);
+        let follows = self.minutes.last().is_some_and(|last| to_utc(*last) + chrono::Duration::minutes(1) == to_utc(dcf_data));
+        if index == 0 {
+            self.minutes = vec![dcf_data];
+        } else if self.minutes.len() == index && follows {
+            self.minutes.push(dcf_data);
+        } else {
+            self.minutes.clear();
+        }
+        if self.minutes.len() < CHUNKS_PER_PACKET {
+            return None;
+        }
+        let minutes: [DCF77; CHUNKS_PER_PACKET] = std::mem::take(&mut self.minutes).try_into().unwrap();
+        Some(ThirdPartyPacket {chunks: minutes.map(|minute| minute.third_party), minutes})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_dcf77, to_dcf77};
+    use crate::schedule::Schedule;
+    #[test]
+    fn test_chunks() {
+        let packet = 0x2AA_AAAA_AAAA;
+        assert!(split_packet(packet) == [0x2AAA, 0x2AAA, 0x2AAA]);
+        assert!(join_chunks([0x3FFF, 0, 1]) == 0x3FF_F000_0001);
+        for fake_input in 0..=0xfff {
+            let packet = (fake_input * 0x1_2345_6789) & PACKET_MASK;
+            assert!(join_chunks(split_packet(packet)) == packet);
+        }
+    }
+    #[test]
+    fn test_aggregator() {
+        let from = Utc.with_ymd_and_hms(2023, 10, 29, 0, 50, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2023, 10, 29, 1, 20, 0).unwrap();
+        let mut aggregator = ThirdPartyAggregator::new();
+        let mut packets = Vec::new();
+        for (index, minute) in Schedule::new(from, to).enumerate() {
+            if index == 14 {
+                continue;
+            }
+            let dcf_data = DCF77 {third_party: index as u16, ..minute.dcf};
+            let decoded_minutes = from_dcf77(to_dcf77(dcf_data).unwrap()).unwrap();
+            assert!(decoded_minutes.third_party == index as u16);
+            packets.extend(aggregator.push(decoded_minutes));
+        }
+        // The packets are sent from 00:51, 00:54... 01:18 UTC, the one of 01:03
+        // misses the minute of 01:04 and the one of 01:18 is not complete
+        assert!(packets.len() == 8);
+        assert!(packets[0].chunks == [2, 3, 4]);
+        assert!(packets[0].start() == Utc.with_ymd_and_hms(2023, 10, 29, 0, 51, 0).unwrap());
+        assert!(packets[3].chunks == [11, 12, 13]);
+        assert!(packets[4].chunks == [17, 18, 19]);
+        assert!(packets.iter().all(|packet| packet.bits() == join_chunks(packet.chunks)));
+        assert!(packets.iter().all(|packet| chunk_index(packet.minutes[0]) == 0));
+    }
+    #[test]
+    fn test_too_many_bits() {
+        let mut minute = Schedule::new(Utc::now(), Utc::now() + chrono::Duration::minutes(2)).next().unwrap().dcf;
+        minute.third_party = 0x4000;
+        assert!(to_dcf77(minute).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+//! Export of DCF77 minutes and receiver edges into Value Change Dump files
+//!
+//! The dumps can be opened in a waveform viewer such as GTKWave. The time unit
+//! is the microsecond and the signals of the `dcf77` scope are:
+//! - `carrier`: 1 at full carrier, 0 while it is reduced
+//! - `bit`: value of the bit of the current second, `x` when unknown
+//! - `second`: second of the minute
+//! - `field`: index of the field of the second in `telegram::FIELDS`
+//! - `field_boundary`: 1 during the first 10 ms of every field
+//! - `parity_ok`: result of the last parity of the minute, `x` before the
+//!   first one or when a bit of the group is unknown
+
+use std::fmt::Write;
+use std::time::Duration;
+use crate::frame::Frame;
+use crate::receiver::{Edge, Receiver, ReceiverEvent, edges_from_frame};
+use crate::telegram::{FIELDS, SECONDS_PER_MINUTE, field_of_second};
+
+/// Length of the pulse of the field boundary signal
+const FIELD_BOUNDARY_WIDTH: Duration = Duration::from_millis(10);
+
+/// Signal of a dump
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Signal {
+    Carrier,
+    Bit,
+    Second,
+    Field,
+    FieldBoundary,
+    Parity
+}
+
+impl Signal {
+    /// Identifier, width and name of the signal in the dump
+    fn definition(self) -> (char, u8, &'static str) {
+        match self {
+            Signal::Carrier => ('!', 1, "carrier"),
+            Signal::Bit => ('"', 1, "bit"),
+            Signal::Second => ('%', 6, "second"),
+            Signal::Field => ('&', 5, "field"),
+            Signal::FieldBoundary => ('\'', 1, "field_boundary"),
+            Signal::Parity => ('(', 1, "parity_ok")
+        }
+    }
+}
+
+/// Every signal of a dump
+const SIGNALS: [Signal; 6] = [Signal::Carrier, Signal::Bit, Signal::Second, Signal::Field, Signal::FieldBoundary, Signal::Parity];
+
+/// Change of a signal, `None` being the unknown value
+type Change = (Duration, Signal, Option<u32>);
+
+/// Formats the value of a signal
+fn format_value(signal: Signal, value: Option<u32>) -> String {
+    let (identifier, width, _) = signal.definition();
+    match (width, value) {
+        (1, Some(value)) => format!("{}{}", value & 1, identifier),
+        (1, None) => format!("x{}", identifier),
+        (_, Some(value)) => format!("b{:b} {}", value, identifier),
+        (_, None) => format!("bx {}", identifier)
+    }
+}
+
+/// Changes of the signals derived from the bits of the minutes
+///
+/// Every bit is given by the start of its pulse, its second and its value.
+fn bit_changes(bits: &[(Duration, u8, Option<bool>)]) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let mut frame = Frame::default();
+    for (time, second, value) in bits {
+        if *second == 0 {
+            frame = Frame::default();
+            changes.push((*time, Signal::Parity, None));
+        }
+        if let Some(value) = value {
+            frame.set_bit(*second, *value);
+        }
+        changes.push((*time, Signal::Bit, value.map(u32::from)));
+        changes.push((*time, Signal::Second, Some(u32::from(*second))));
+        let Some(field) = field_of_second(*second) else {
+            continue;
+        };
+        if *field.seconds().start() == *second {
+            let index = FIELDS.iter().position(|other| *other == field).unwrap();
+            changes.push((*time, Signal::Field, Some(index as u32)));
+            changes.push((*time, Signal::FieldBoundary, Some(1)));
+            changes.push((*time + FIELD_BOUNDARY_WIDTH, Signal::FieldBoundary, Some(0)));
+        }
+        if let Some(coverage) = field.parity_coverage() {
+            let group: Vec<Option<bool>> = coverage.chain(field.seconds()).map(|second| frame.bit(second)).collect();
+            let parity_ok = if group.contains(&None) {
+                None
+            } else {
+                Some(u32::from(group.iter().filter(|bit| **bit == Some(true)).count() % 2 == 0))
+            };
+            changes.push((*time, Signal::Parity, parity_ok));
+        }
+    }
+    changes
+}
+
+/// Changes of the carrier signal out of the edges of the receiver
+fn carrier_changes(edges: &[Edge]) -> Vec<Change> {
+    edges.iter().map(|edge| (edge.time, Signal::Carrier, Some(u32::from(!edge.level)))).collect()
+}
+
+/// Writes the dump of a list of changes
+fn dump(mut changes: Vec<Change>, end: Duration) -> String {
+    changes.sort_by_key(|(time, _, _)| *time);
+    let mut output = String::new();
+    writeln!(output, "$version dcf77_chrono $end").unwrap();
+    writeln!(output, "$timescale 1 us $end").unwrap();
+    writeln!(output, "$scope module dcf77 $end").unwrap();
+    for signal in SIGNALS {
+        let (identifier, width, name) = signal.definition();
+        writeln!(output, "$var wire {} {} {} $end", width, identifier, name).unwrap();
+    }
+    writeln!(output, "$upscope $end").unwrap();
+    writeln!(output, "$enddefinitions $end").unwrap();
+    writeln!(output, "#0").unwrap();
+    writeln!(output, "$dumpvars").unwrap();
+    for signal in SIGNALS {
+        let initial = match signal {
+            Signal::Carrier => Some(1),
+            Signal::FieldBoundary => Some(0),
+            _ => None
+        };
+        writeln!(output, "{}", format_value(signal, initial)).unwrap();
+    }
+    writeln!(output, "$end").unwrap();
+    let mut current_time = Duration::ZERO;
+    for (time, signal, value) in changes {
+        if time != current_time {
+            writeln!(output, "#{}", time.as_micros()).unwrap();
+            current_time = time;
+        }
+        writeln!(output, "{}", format_value(signal, value)).unwrap();
+    }
+    if end > current_time {
+        writeln!(output, "#{}", end.as_micros()).unwrap();
+    }
+    output
+}
+
+/// Dumps the broadcast of an encoded minute, starting at the second 0
+///
+/// `leap_second` adds the inserted second 59 before the minute marker.
+///
+/// # Examples
+/// ```
+///use dcf77_chrono::vcd::*;
+///let dump = minute_to_vcd(0x486092B3312, false);
+///assert!(dump.contains("$var wire 1 ! carrier $end"));
+///assert!(dump.lines().last() == Some("#60000000"));
+/// ```
+pub fn minute_to_vcd(input: u64, leap_second: bool) -> String {
+    let frame = Frame::from(input);
+    let mut bits: Vec<(Duration, u8, Option<bool>)> = (0..SECONDS_PER_MINUTE - 1)
+        .map(|second| (Duration::from_secs(second.into()), second, frame.bit(second)))
+        .collect();
+    let marker_second = if leap_second {
+        bits.push((Duration::from_secs(59), 59, Some(false)));
+        SECONDS_PER_MINUTE
+    } else {
+        SECONDS_PER_MINUTE - 1
+    };
+    bits.push((Duration::from_secs(marker_second.into()), marker_second, None));
+    let mut changes = carrier_changes(&edges_from_frame(input, Duration::ZERO, leap_second));
+    changes.extend(bit_changes(&bits));
+    dump(changes, Duration::from_secs(u64::from(marker_second) + 1))
+}
+
+/// Dumps a captured edge stream along with what the receiver decodes out of it
+///
+/// The bits are only known once the receiver found the first minute marker.
+pub fn edges_to_vcd(edges: &[Edge]) -> String {
+    let mut receiver = Receiver::new();
+    let bits: Vec<(Duration, u8, Option<bool>)> = edges.iter()
+        .flat_map(|edge| receiver.push_edge(*edge))
+        .filter_map(|event| match event {
+            ReceiverEvent::Bit {time, second, value} => Some((time, second, value)),
+            _ => None
+        })
+        .collect();
+    let mut changes = carrier_changes(edges);
+    changes.extend(bit_changes(&bits));
+    let end = edges.iter().map(|edge| edge.time).max().unwrap_or_default();
+    dump(changes, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const TEST_FRAME: u64 = 0x486092B3312;
+    /// Values of a signal at every time of a dump
+    fn signal_values(dump: &str, identifier: &str) -> Vec<(u64, String)> {
+        let mut time = 0;
+        let mut values = Vec::new();
+        for line in dump.lines().skip_while(|line| !line.starts_with("$enddefinitions")) {
+            if let Some(new_time) = line.strip_prefix('#') {
+                time = new_time.parse().unwrap();
+            } else if let Some(value) = line.strip_suffix(identifier) {
+                values.push((time, value.trim().to_string()));
+            }
+        }
+        values
+    }
+    #[test]
+    fn test_minute() {
+        let dump = minute_to_vcd(TEST_FRAME, false);
+        assert!(dump.contains("$timescale 1 us $end"));
+        let carrier = signal_values(&dump, "!");
+        assert!(carrier.len() == 1 + 2 * 59);
+        assert!(carrier[1] == (0, "0".to_string()));
+        assert!(carrier[2] == (100_000, "1".to_string()));
+        let seconds = signal_values(&dump, "%");
+        assert!(seconds.last() == Some(&(59_000_000, "b111011".to_string())));
+        let bits = signal_values(&dump, "\"");
+        let frame: String = bits[1..60].iter().map(|(_, value)| if value == "x" { "" } else { value.as_str() }).collect();
+        assert!(frame == Frame::from(TEST_FRAME).to_string());
+        let parity = signal_values(&dump, "(");
+        assert!(parity.iter().filter(|(_, value)| value == "1").count() == 3);
+        let boundaries = signal_values(&dump, "'");
+        assert!(boundaries.iter().filter(|(_, value)| value == "1").count() == FIELDS.len());
+        let times: Vec<u64> = dump.lines().filter_map(|line| line.strip_prefix('#')).map(|time| time.parse().unwrap()).collect();
+        assert!(times.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+    #[test]
+    fn test_parity_error() {
+        let dump = minute_to_vcd(TEST_FRAME ^ (1 << (59 - 30)), true);
+        let parity: Vec<String> = signal_values(&dump, "(").into_iter().map(|(_, value)| value).collect();
+        assert!(parity == ["x", "x", "1", "0", "1"]);
+        assert!(dump.lines().last() == Some("#61000000"));
+    }
+    #[test]
+    fn test_edges() {
+        let mut edges = Vec::new();
+        for minute in 0..3 {
+            edges.extend(edges_from_frame(TEST_FRAME, Duration::from_secs(60 * minute), false));
+        }
+        let dump = edges_to_vcd(&edges);
+        assert!(signal_values(&dump, "!").len() == 1 + edges.len());
+        let seconds = signal_values(&dump, "%");
+        assert!(seconds[1] == (60_000_000, "b0".to_string()));
+        assert!(signal_values(&dump, "\"").len() == 1 + 59 * 2);
+    }
+}